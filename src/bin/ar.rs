@@ -0,0 +1,1203 @@
+//! A command-line archiver, compatible with the `ar` utility from GNU
+//! binutils.
+//!
+//! This supports the POSIX/GNU operation keys `d` (delete), `m` (move), `p`
+//! (print), `q` (quick append), `r` (replace), `s` (index), `t` (list), and
+//! `x` (extract), along with the modifiers `a`, `b`/`i`, `c`, `D`, `N`, `o`,
+//! `P`, `s`, `S`, `T`, `u`, `U`, `v`, and `V`, and `ar -M` for running an MRI
+//! script from standard input.  Its messages and exit codes are those of
+//! binutils `ar`, as configured by most distributions (that is, with the `D`
+//! modifier on by default, so that members added from files get a zero
+//! timestamp, owner, and group).  For example:
+//!
+//! ```shell
+//! ar rcs libfoo.a foo.o bar.o
+//! ar tv libfoo.a
+//! ```
+//!
+//! The symbol table lists the global symbols defined by those members that
+//! are ELF, Mach-O, or COFF object files, or LLVM bitcode (see the
+//! `ar::object` module).  There are a few differences:
+//!
+//! * `tv` lists timestamps in UTC, rather than in the local time zone.
+//! * New archives are always written in the GNU variant, and existing
+//!   archives in their own variant (except that archives without any BSD or
+//!   GNU extensions are rewritten in the GNU variant).  AIX big archives and
+//!   COFF archives can be read, but not modified.
+//! * `ar -M` reads the whole script before running any of it, and stops at
+//!   the first error (in parsing or running the script) with its own message
+//!   (see the `ar::mri` module) and exit code 1.  Binutils `ar` words its
+//!   messages differently, and may carry on past an error (such as a syntax
+//!   error, after which it exits with code 0) or exit with another code
+//!   (such as 9, for `ADDMOD` without an open archive).
+
+extern crate ar;
+
+use ar::mri::{Interpreter, Script};
+use ar::object;
+use ar::{Archive, Error, Header, MetadataPolicy, StagedBuilder, Variant,
+         write_atomically};
+use std::cell::RefCell;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+#[cfg(not(unix))]
+use std::str;
+
+// ========================================================================= //
+
+const USAGE: &str = "\
+Usage: ar [-]{dmpqrstx}[abcDiNoPsSTuUvV] [member-name] [count] archive-file \
+file...
+       ar -M [<mri-script]
+ commands:
+  d            - delete file(s) from the archive
+  m[ab]        - move file(s) in the archive
+  p            - print file(s) found in the archive
+  q            - quick append file(s) to the archive
+  r[ab][u]     - replace existing or insert new file(s) into the archive
+  s            - act as ranlib
+  t[v]         - display contents of the archive
+  x[o]         - extract file(s) from the archive
+ command specific modifiers:
+  [a]          - put file(s) after [member-name]
+  [b]          - put file(s) before [member-name] (same as [i])
+  [D]          - use zero for timestamps and uids/gids (default)
+  [U]          - use actual timestamps and uids/gids
+  [N]          - use instance [count] of name
+  [P]          - use full path names when matching
+  [o]          - preserve original dates
+  [u]          - only replace files that are newer than current archive \
+contents
+ generic modifiers:
+  [c]          - do not warn if the library had to be created
+  [s]          - create an archive index (cf. ranlib)
+  [S]          - do not build a symbol table
+  [T]          - make a thin archive
+  [v]          - be verbose
+  [V]          - display the version number
+  --thin       - make a thin archive
+";
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+    "Nov", "Dec",
+];
+
+// ========================================================================= //
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operation {
+    Delete,
+    Move,
+    Print,
+    QuickAppend,
+    Replace,
+    Index,
+    List,
+    Extract,
+}
+
+impl Operation {
+    fn from_key(key: char) -> Option<Operation> {
+        match key {
+            'd' => Some(Operation::Delete),
+            'm' => Some(Operation::Move),
+            'p' => Some(Operation::Print),
+            'q' => Some(Operation::QuickAppend),
+            'r' => Some(Operation::Replace),
+            't' => Some(Operation::List),
+            'x' => Some(Operation::Extract),
+            _ => None,
+        }
+    }
+
+    fn modifies_archive(self) -> bool {
+        match self {
+            Operation::Print | Operation::List | Operation::Extract => false,
+            Operation::Delete |
+            Operation::Move |
+            Operation::QuickAppend |
+            Operation::Replace |
+            Operation::Index => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Position {
+    After(Vec<u8>),
+    Before(Vec<u8>),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct Options {
+    operation: Operation,
+    position: Option<Position>,
+    count: Option<usize>,
+    create_quietly: bool,
+    deterministic: bool,
+    full_paths: bool,
+    only_newer: bool,
+    preserve_dates: bool,
+    symbol_table: bool,
+    thin: bool,
+    verbose: bool,
+    archive: PathBuf,
+    files: Vec<OsString>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Command {
+    Run(Options),
+    Mri,
+    Help,
+    Version,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Failure {
+    // A command-line error, with an optional message to print before the
+    // usage text.
+    Usage(Option<String>),
+    // A fatal error, with the exit code and the message to print.
+    Fatal(i32, String),
+}
+
+fn fatal<S: Into<String>>(message: S) -> Failure {
+    Failure::Fatal(1, message.into())
+}
+
+fn path_failure(path: &Path, error: &io::Error) -> Failure {
+    fatal(format!("{}: {}", path.display(), describe(error)))
+}
+
+/// Formats an I/O error the way that the C library would, without the
+/// " (os error N)" suffix that std adds.
+fn describe(error: &io::Error) -> String {
+    let message = error.to_string();
+    match message.find(" (os error ") {
+        Some(index) if error.raw_os_error().is_some() => {
+            message[..index].to_string()
+        }
+        _ => message,
+    }
+}
+
+// ========================================================================= //
+
+fn main() {
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let result = parse_args(args).and_then(|command| match command {
+        Command::Run(options) => run(options),
+        Command::Mri => run_mri_script(),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Command::Version => {
+            println!("ar (rust-ar) {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+    });
+    let code = match result {
+        Ok(()) => 0,
+        Err(Failure::Usage(message)) => {
+            if let Some(message) = message {
+                eprintln!("ar: {}", message);
+            }
+            eprint!("{}", USAGE);
+            1
+        }
+        Err(Failure::Fatal(code, message)) => {
+            eprintln!("ar: {}", message);
+            code
+        }
+    };
+    process::exit(code);
+}
+
+fn parse_args(args: Vec<OsString>) -> Result<Command, Failure> {
+    // As with getopt, long options may appear anywhere before a "--".
+    let mut thin = false;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--") => {
+                positional.extend(args.by_ref());
+                break;
+            }
+            Some("--help") => return Ok(Command::Help),
+            Some("--version") => return Ok(Command::Version),
+            Some("--thin") => thin = true,
+            Some(option) if option.starts_with("--") => {
+                let msg = format!("unrecognized option '{}'", option);
+                return Err(Failure::Usage(Some(msg)));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let mut args = positional.into_iter();
+    let key = match args.next().as_ref().and_then(|arg| arg.to_str()) {
+        Some(key) if key.starts_with('-') => key[1..].to_string(),
+        Some(key) => key.to_string(),
+        None => return Err(Failure::Usage(None)),
+    };
+    if key == "M" {
+        if args.next().is_some() {
+            return Err(Failure::Usage(None));
+        }
+        return Ok(Command::Mri);
+    }
+
+    let mut operation = None;
+    let mut index = false;
+    let mut after = None;
+    let mut counted = false;
+    let mut create_quietly = false;
+    let mut deterministic = true;
+    let mut full_paths = false;
+    let mut only_newer = false;
+    let mut preserve_dates = false;
+    let mut symbol_table = true;
+    let mut verbose = false;
+    for modifier in key.chars() {
+        if let Some(op) = Operation::from_key(modifier) {
+            if operation.is_some() && operation != Some(op) {
+                return Err(fatal("two different operation options specified"));
+            }
+            operation = Some(op);
+            continue;
+        }
+        match modifier {
+            'a' => after = Some(true),
+            'b' | 'i' => after = Some(false),
+            'c' => create_quietly = true,
+            'D' => deterministic = true,
+            'N' => counted = true,
+            'o' => preserve_dates = true,
+            'P' => full_paths = true,
+            's' => {
+                index = true;
+                symbol_table = true;
+            }
+            'S' => symbol_table = false,
+            'T' => thin = true,
+            'u' => only_newer = true,
+            'U' => deterministic = false,
+            'v' => verbose = true,
+            'V' => return Ok(Command::Version),
+            _ => {
+                let msg = format!("invalid option -- '{}'", modifier);
+                return Err(Failure::Usage(Some(msg)));
+            }
+        }
+    }
+    let operation = match operation {
+        // Like GNU ar, treat `ts` as `s`, for compatibility with old scripts.
+        None | Some(Operation::List) if index => Operation::Index,
+        Some(operation) => operation,
+        None => return Err(fatal("no operation specified")),
+    };
+
+    let position = match after {
+        Some(after) => {
+            let name = match args.next() {
+                Some(name) => try!(os_to_bytes(&name)),
+                None => return Err(Failure::Usage(None)),
+            };
+            Some(if after {
+                Position::After(name)
+            } else {
+                Position::Before(name)
+            })
+        }
+        None => None,
+    };
+    let count = if counted {
+        if operation != Operation::Extract && operation != Operation::Delete {
+            let msg = "`N' is only meaningful with the `x' and `d' options.";
+            return Err(fatal(msg));
+        }
+        let count = args.next()
+            .and_then(|arg| arg.to_str().and_then(|arg| arg.parse().ok()));
+        match count {
+            Some(count) if count > 0 => Some(count),
+            _ => return Err(fatal("Value for `N' must be positive.")),
+        }
+    } else {
+        None
+    };
+    let archive = match args.next() {
+        Some(archive) => PathBuf::from(archive),
+        None => return Err(Failure::Usage(None)),
+    };
+    Ok(Command::Run(Options {
+        operation: operation,
+        position: position,
+        count: count,
+        create_quietly: create_quietly,
+        deterministic: deterministic,
+        full_paths: full_paths,
+        only_newer: only_newer,
+        preserve_dates: preserve_dates,
+        symbol_table: symbol_table,
+        thin: thin,
+        verbose: verbose,
+        archive: archive,
+        files: args.collect(),
+    }))
+}
+
+fn run(options: Options) -> Result<(), Failure> {
+    // GNU ar gives this warning, but then goes on to honor the `u' modifier
+    // anyway (which only matters for members that weren't added in
+    // deterministic mode, since the rest have a zero timestamp).
+    if options.only_newer && options.deterministic {
+        eprintln!("ar: `u' modifier ignored since `D' is the default (see \
+                   `U')");
+    }
+    if options.operation.modifies_archive() {
+        modify_archive(&options)
+    } else {
+        read_archive(&options)
+    }
+}
+
+fn run_mri_script() -> Result<(), Failure> {
+    let stdin = io::stdin();
+    let script = try!(
+        Script::parse(stdin.lock()).map_err(|error| fatal(error.to_string()))
+    );
+    let mut policy = MetadataPolicy::new();
+    policy.set_deterministic(true);
+    let mut interpreter = Interpreter::new();
+    interpreter.set_metadata_policy(policy);
+    interpreter.run(&script).map_err(|error| fatal(error.to_string()))
+}
+
+// ========================================================================= //
+
+/// Opens the archive named on the command line, and reads the headers of
+/// all of its entries.
+fn open_archive(options: &Options) -> Result<(Archive<File>, Vec<Header>),
+                                              Failure> {
+    let path = &options.archive;
+    let failure = |error: io::Error| match Error::from_io(&error) {
+        Some(&Error::InvalidGlobalHeader { .. }) => {
+            fatal(format!("{}: file format not recognized", path.display()))
+        }
+        _ => path_failure(path, &error),
+    };
+    let mut archive = try!(Archive::open(path).map_err(&failure));
+    let count = try!(archive.count_entries().map_err(&failure));
+    let mut headers = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = try!(archive.jump_to_entry(index).map_err(&failure));
+        headers.push(entry.header().clone());
+    }
+    Ok((archive, headers))
+}
+
+/// Returns the name by which a member is listed and named on the command
+/// line.  For a thin archive, this is the path of the member's file relative
+/// to the current directory, rather than to the archive.
+fn display_name(options: &Options, thin: bool, header: &Header)
+                -> Result<Vec<u8>, Failure> {
+    let identifier = header.identifier();
+    match options.archive.parent() {
+        Some(dir) if thin && dir != Path::new("") => {
+            let path = try!(bytes_to_path(identifier));
+            os_to_bytes(dir.join(path).as_os_str())
+        }
+        _ => Ok(identifier.to_vec()),
+    }
+}
+
+/// Returns the name of the member that a file named on the command line
+/// refers to, which is just the file name unless the `P` modifier was given
+/// (or the archive is thin).
+fn member_name(options: &Options, thin: bool, file: &OsStr)
+               -> Result<Vec<u8>, Failure> {
+    if options.full_paths || thin {
+        return os_to_bytes(file);
+    }
+    match Path::new(file).file_name() {
+        Some(name) => os_to_bytes(name),
+        None => os_to_bytes(file),
+    }
+}
+
+fn read_archive(options: &Options) -> Result<(), Failure> {
+    if !options.archive.exists() {
+        let msg = format!(
+            "{}: No such file or directory",
+            options.archive.display()
+        );
+        return Err(Failure::Fatal(9, msg));
+    }
+    let (mut archive, headers) = try!(open_archive(options));
+    let thin = archive.is_thin();
+    if thin && options.operation == Operation::Extract {
+        return Err(fatal("`x' cannot be used on thin archives."));
+    }
+    let mut names = Vec::with_capacity(headers.len());
+    for header in headers.iter() {
+        names.push(try!(display_name(options, thin, header)));
+    }
+    let mut selected = Vec::new();
+    if options.files.is_empty() {
+        selected.extend(0..headers.len());
+    }
+    for file in options.files.iter() {
+        let name = try!(member_name(options, thin, file));
+        let mut matches = names
+            .iter()
+            .enumerate()
+            .filter(|&(_, member)| *member == name)
+            .map(|(index, _)| index);
+        let found: Vec<usize> = match options.count {
+            Some(count) => matches.nth(count - 1).into_iter().collect(),
+            None => matches.collect(),
+        };
+        if found.is_empty() {
+            eprintln!("no entry {} in archive", file.to_string_lossy());
+        }
+        selected.extend(found);
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let write_failure = |error| fatal(describe(&error));
+    let archive_failure = |error| path_failure(&options.archive, &error);
+    for index in selected {
+        let header = &headers[index];
+        let name = String::from_utf8_lossy(&names[index]).into_owned();
+        match options.operation {
+            Operation::List => {
+                let result = if options.verbose {
+                    writeln!(stdout, "{} {}", describe_header(header), name)
+                } else {
+                    writeln!(stdout, "{}", name)
+                };
+                try!(result.map_err(&write_failure));
+            }
+            Operation::Print => {
+                if options.verbose {
+                    try!(
+                        write!(stdout, "\n<{}>\n\n", name)
+                            .map_err(&write_failure)
+                    );
+                }
+                let mut entry = try!(
+                    archive.jump_to_entry(index).map_err(&archive_failure)
+                );
+                try!(
+                    io::copy(&mut entry, &mut stdout).map_err(&write_failure)
+                );
+            }
+            Operation::Extract => {
+                let path = try!(bytes_to_path(header.identifier()));
+                if !is_safe_to_extract(&path) {
+                    eprintln!(
+                        "ar: illegal pathname found in archive member: {}",
+                        name
+                    );
+                    continue;
+                }
+                if options.verbose {
+                    try!(
+                        writeln!(stdout, "x - {}", name)
+                            .map_err(&write_failure)
+                    );
+                }
+                let entry = try!(
+                    archive.jump_to_entry(index).map_err(&archive_failure)
+                );
+                try!(
+                    extract_entry(options, header, entry, &path)
+                        .map_err(|error| path_failure(&path, &error))
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+    stdout.flush().map_err(&write_failure)
+}
+
+/// Formats a member's mode, owner, group, size, and timestamp for a verbose
+/// listing, like `rw-r--r-- 0/0      4 Jan  1 00:00 1970`.
+fn describe_header(header: &Header) -> String {
+    format!(
+        "{} {}/{} {:6} {}",
+        mode_string(header.mode()),
+        header.uid(),
+        header.gid(),
+        header.size(),
+        format_mtime(header.mtime())
+    )
+}
+
+fn mode_string(mode: u32) -> String {
+    let classes = [
+        (mode >> 6, mode & 0o4000 != 0, 's', 'S'),
+        (mode >> 3, mode & 0o2000 != 0, 's', 'S'),
+        (mode, mode & 0o1000 != 0, 't', 'T'),
+    ];
+    let mut string = String::with_capacity(9);
+    for &(bits, special, set, unset) in classes.iter() {
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 0o1 != 0, special) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+/// Formats a timestamp like `ctime` does, but without the weekday or
+/// seconds (e.g. `Jan  1 00:00 1970`).
+fn format_mtime(mtime: u64) -> String {
+    let (year, month, day) = civil_from_days((mtime / 86400) as i64);
+    let seconds = mtime % 86400;
+    format!(
+        "{} {:2} {:02}:{:02} {}",
+        MONTHS[month - 1],
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        year
+    )
+}
+
+/// Converts a count of days since 1970-01-01 into a (year, month, day) date
+/// in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, usize, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                           day_of_era / 146096) / 365;
+    let day_of_year = day_of_era -
+        (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as usize, day)
+}
+
+/// Returns true if a member can be extracted to the given path without
+/// escaping the current directory.
+fn is_safe_to_extract(path: &Path) -> bool {
+    path.components().all(|component| match component {
+        Component::Normal(_) | Component::CurDir => true,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+            false
+        }
+    })
+}
+
+fn extract_entry<R: Read>(options: &Options, header: &Header, mut entry: R,
+                          path: &Path)
+                          -> io::Result<()> {
+    let mut file = try!(File::create(path));
+    try!(io::copy(&mut entry, &mut file));
+    try!(set_mode(&file, header.mode()));
+    if options.preserve_dates {
+        let mtime = UNIX_EPOCH + Duration::from_secs(header.mtime());
+        try!(file.set_modified(mtime));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(file: &File, mode: u32) -> io::Result<()> {
+    file.set_permissions(fs::Permissions::from_mode(mode & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_file: &File, _mode: u32) -> io::Result<()> { Ok(()) }
+
+// ========================================================================= //
+
+/// A member of an archive that is being modified.
+struct Member {
+    header: Header,
+    // The name by which the member is named on the command line.
+    name: Vec<u8>,
+    source: Source,
+}
+
+enum Source {
+    // The entry with this index in the original archive.
+    Original(usize),
+    File(PathBuf),
+}
+
+// Symbol names, each paired with the index of the member defining it.
+type Symbols = Vec<(Vec<u8>, usize)>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Bsd,
+    Gnu,
+    Thin,
+}
+
+fn modify_archive(options: &Options) -> Result<(), Failure> {
+    let path = &options.archive;
+    let mut original = None;
+    let mut members = Vec::new();
+    let mut format = if options.thin { Format::Thin } else { Format::Gnu };
+    if path.exists() {
+        let (archive, headers) = try!(open_archive(options));
+        let thin = archive.is_thin();
+        format = match archive.variant() {
+            _ if thin => Format::Thin,
+            Variant::Common | Variant::GNU => Format::Gnu,
+            Variant::BSD => Format::Bsd,
+            _ => {
+                let msg = format!(
+                    "{}: cannot modify {:?} archives",
+                    path.display(),
+                    archive.variant()
+                );
+                return Err(fatal(msg));
+            }
+        };
+        for (index, header) in headers.into_iter().enumerate() {
+            let name = try!(display_name(options, thin, &header));
+            members.push(Member {
+                header: header,
+                name: name,
+                source: Source::Original(index),
+            });
+        }
+        original = Some(archive);
+    } else if options.operation == Operation::Index {
+        let msg = format!("'{}': No such file", path.display());
+        return Err(fatal(msg));
+    } else if !options.create_quietly {
+        eprintln!("ar: creating {}", path.display());
+    }
+
+    let thin = format == Format::Thin;
+    match options.operation {
+        Operation::Delete => try!(delete_members(options, thin, &mut members)),
+        Operation::Move => try!(move_members(options, thin, &mut members)),
+        Operation::QuickAppend | Operation::Replace => {
+            if thin && !options.thin {
+                let msg = format!(
+                    "Cannot convert existing thin library {} to normal \
+                     format",
+                    path.display()
+                );
+                return Err(fatal(msg));
+            } else if options.thin && !thin {
+                let msg = format!(
+                    "Cannot convert existing library {} to thin format",
+                    path.display()
+                );
+                return Err(fatal(msg));
+            }
+            try!(add_members(options, thin, &mut members));
+        }
+        Operation::Index => {}
+        Operation::Print | Operation::List | Operation::Extract => {
+            unreachable!()
+        }
+    }
+
+    let symbols = if options.symbol_table {
+        try!(member_symbols(options, &mut original, &members))
+    } else {
+        Vec::new()
+    };
+    write_archive(options, format, original, &members, symbols)
+}
+
+/// Returns the global symbols defined by each member, paired with the
+/// member's index, for the archive's symbol table.
+fn member_symbols(options: &Options, original: &mut Option<Archive<File>>,
+                  members: &[Member])
+                  -> Result<Symbols, Failure> {
+    let mut symbols = Vec::new();
+    let result = read_members(original, members, |index, header, data| {
+        let identifier = header.identifier();
+        let mut bytes = Vec::new();
+        try!(data.read_to_end(&mut bytes));
+        let defined = try!(object::defined_symbols(&bytes).map_err(|error| {
+            let msg =
+                format!("{}: {}", String::from_utf8_lossy(identifier), error);
+            io::Error::new(error.kind(), msg)
+        }));
+        for symbol in defined.into_iter() {
+            symbols.push((symbol, index));
+        }
+        Ok(())
+    });
+    try!(result.map_err(|error| path_failure(&options.archive, &error)));
+    Ok(symbols)
+}
+
+fn find_member(members: &[Member], name: &[u8], count: usize)
+               -> Option<usize> {
+    members
+        .iter()
+        .enumerate()
+        .filter(|&(_, member)| member.name.as_slice() == name)
+        .map(|(index, _)| index)
+        .nth(count - 1)
+}
+
+/// Returns the index at which to insert members that are being moved or
+/// added, given the index to use if there is no position modifier.  As with
+/// GNU ar, a position relative to a missing member means the end.
+fn insertion_index(options: &Options, members: &[Member], default: usize)
+                   -> usize {
+    let (name, offset) = match options.position {
+        Some(Position::After(ref name)) => (name, 1),
+        Some(Position::Before(ref name)) => (name, 0),
+        None => return default,
+    };
+    match find_member(members, name, 1) {
+        Some(index) => index + offset,
+        None => members.len(),
+    }
+}
+
+fn delete_members(options: &Options, thin: bool, members: &mut Vec<Member>)
+                  -> Result<(), Failure> {
+    for file in options.files.iter() {
+        let name = try!(member_name(options, thin, file));
+        let count = options.count.unwrap_or(1);
+        match find_member(members, &name, count) {
+            Some(index) => {
+                members.remove(index);
+                if options.verbose {
+                    println!("d - {}", file.to_string_lossy());
+                }
+            }
+            None => {
+                if options.verbose {
+                    println!("No member named `{}'", file.to_string_lossy());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn move_members(options: &Options, thin: bool, members: &mut Vec<Member>)
+                -> Result<(), Failure> {
+    for file in options.files.iter() {
+        let name = try!(member_name(options, thin, file));
+        let index = match find_member(members, &name, 1) {
+            Some(index) => index,
+            None => {
+                let msg = format!(
+                    "no entry {} in archive {}!",
+                    file.to_string_lossy(),
+                    options.archive.display()
+                );
+                return Err(fatal(msg));
+            }
+        };
+        let member = members.remove(index);
+        let len = members.len();
+        members.insert(insertion_index(options, members, len), member);
+        if options.verbose {
+            println!("m - {}", file.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+fn add_members(options: &Options, thin: bool, members: &mut Vec<Member>)
+               -> Result<(), Failure> {
+    let mut policy = MetadataPolicy::new();
+    policy.set_deterministic(options.deterministic);
+    for file in options.files.iter() {
+        let path = PathBuf::from(file);
+        let metadata = try!(
+            fs::metadata(&path).map_err(|error| path_failure(&path, &error))
+        );
+        let name = try!(member_name(options, thin, file));
+        let identifier = if thin {
+            let relative = try!(
+                relative_to_archive(&options.archive, &path)
+                    .map_err(|error| path_failure(&path, &error))
+            );
+            try!(os_to_bytes(relative.as_os_str()))
+        } else {
+            name.clone()
+        };
+        let header = Header::from_metadata(identifier, &metadata);
+        let mtime = header.mtime();
+        let member = Member {
+            header: policy.apply(&header),
+            name: name,
+            source: Source::File(path),
+        };
+        let existing = if options.operation == Operation::Replace {
+            find_member(members, &member.name, 1)
+        } else {
+            None
+        };
+        match existing {
+            Some(index) => {
+                if options.only_newer &&
+                    mtime <= members[index].header.mtime()
+                {
+                    continue;
+                }
+                members.remove(index);
+                let index = insertion_index(options, members, index);
+                members.insert(index, member);
+                if options.verbose {
+                    println!("r - {}", file.to_string_lossy());
+                }
+            }
+            None => {
+                let index = if options.operation == Operation::Replace {
+                    let len = members.len();
+                    insertion_index(options, members, len)
+                } else {
+                    members.len()
+                };
+                members.insert(index, member);
+                if options.verbose {
+                    println!("a - {}", file.to_string_lossy());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path of the given file relative to the directory containing
+/// the given archive, as stored in a thin archive.
+fn relative_to_archive(archive: &Path, file: &Path) -> io::Result<PathBuf> {
+    let current_dir = try!(env::current_dir());
+    let archive = current_dir.join(archive);
+    let dir = archive.parent().unwrap_or(&current_dir);
+    let file = current_dir.join(file);
+    Ok(relative_path(dir, &file))
+}
+
+fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir
+        .components()
+        .filter(|&component| component != Component::CurDir)
+        .collect();
+    let to: Vec<Component> = to.components()
+        .filter(|&component| component != Component::CurDir)
+        .collect();
+    let common = from.iter()
+        .zip(to.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in to[common..].iter() {
+        path.push(component.as_os_str());
+    }
+    path
+}
+
+/// Writes the modified archive to a temporary file next to the original,
+/// and then renames it over the original.
+fn write_archive(options: &Options, format: Format,
+                 original: Option<Archive<File>>, members: &[Member],
+                 symbols: Symbols)
+                 -> Result<(), Failure> {
+    let path = &options.archive;
+    if path.file_name().is_none() {
+        return Err(fatal(format!("{}: Is a directory", path.display())));
+    }
+    write_atomically(path, |writer| {
+        write_members(writer, format, original, members, symbols)
+    }).map_err(|error| path_failure(path, &error))
+}
+
+fn write_members<W: Write>(writer: W, format: Format,
+                           original: Option<Archive<File>>,
+                           members: &[Member], symbols: Symbols)
+                           -> io::Result<W> {
+    let variant = if format == Format::Bsd {
+        Variant::BSD
+    } else {
+        Variant::GNU
+    };
+    let mut builder = StagedBuilder::new(variant);
+    builder.set_thin(format == Format::Thin);
+    let original = original.map(|archive| Rc::new(RefCell::new(archive)));
+    for member in members.iter() {
+        let header = member.header.clone();
+        if format == Format::Thin {
+            builder.append(header, io::empty());
+            continue;
+        }
+        match member.source {
+            Source::Original(index) => {
+                let archive = original.as_ref().unwrap().clone();
+                builder.append(header, OriginalEntry {
+                    archive: archive,
+                    index: index,
+                    position: 0,
+                });
+            }
+            Source::File(ref path) => {
+                builder.append(header, MemberFile {
+                    path: path.clone(),
+                    file: None,
+                });
+            }
+        }
+    }
+    for (symbol, index) in symbols.into_iter() {
+        builder.add_symbol_for_entry(symbol, index);
+    }
+    builder.write(writer)
+}
+
+/// Reads the data of an entry of the original archive as it is written into
+/// the new one.  Each member shares the original archive, so this jumps back
+/// to the entry for every read.
+struct OriginalEntry {
+    archive: Rc<RefCell<Archive<File>>>,
+    index: usize,
+    position: u64,
+}
+
+impl Read for OriginalEntry {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut archive = self.archive.borrow_mut();
+        let mut entry = try!(archive.jump_to_entry(self.index));
+        try!(entry.seek(SeekFrom::Start(self.position)));
+        let len = try!(entry.read(buf));
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+/// Reads the data of a member added from a file, which isn't opened until
+/// the member is written (so that only one such file is open at a time).
+struct MemberFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl Read for MemberFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.file.is_none() {
+            self.file = Some(try!(File::open(&self.path)));
+        }
+        self.file.as_mut().unwrap().read(buf)
+    }
+}
+
+fn read_members<F>(original: &mut Option<Archive<File>>, members: &[Member],
+                   mut read: F)
+                   -> io::Result<()>
+where
+    F: FnMut(usize, &Header, &mut dyn Read) -> io::Result<()>,
+{
+    for (index, member) in members.iter().enumerate() {
+        match member.source {
+            Source::Original(original_index) => {
+                let archive = original.as_mut().unwrap();
+                let mut entry = try!(archive.jump_to_entry(original_index));
+                try!(read(index, &member.header, &mut entry));
+            }
+            Source::File(ref path) => {
+                let mut file = try!(File::open(path));
+                try!(read(index, &member.header, &mut file));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ========================================================================= //
+
+#[cfg(unix)]
+fn os_to_bytes(string: &OsStr) -> Result<Vec<u8>, Failure> {
+    Ok(string.as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn os_to_bytes(string: &OsStr) -> Result<Vec<u8>, Failure> {
+    match string.to_str() {
+        Some(utf8) => Ok(utf8.as_bytes().to_vec()),
+        None => Err(fatal(format!("{:?}: Non-UTF8 file name", string))),
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> Result<PathBuf, Failure> {
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> Result<PathBuf, Failure> {
+    match str::from_utf8(bytes) {
+        Ok(utf8) => Ok(PathBuf::from(utf8)),
+        Err(_) => {
+            let name = String::from_utf8_lossy(bytes);
+            Err(fatal(format!("{}: Non-UTF8 member name", name)))
+        }
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Failure, Operation, Options, Position,
+                format_mtime, is_safe_to_extract, mode_string, parse_args,
+                relative_path};
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+
+    fn parse(args: &[&str]) -> Result<Command, Failure> {
+        parse_args(args.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn parse_replace_command() {
+        let command = parse(&["-rcsvb", "bar.o", "libfoo.a", "foo.o"]);
+        let expected = Options {
+            operation: Operation::Replace,
+            position: Some(Position::Before(b"bar.o".to_vec())),
+            count: None,
+            create_quietly: true,
+            deterministic: true,
+            full_paths: false,
+            only_newer: false,
+            preserve_dates: false,
+            symbol_table: true,
+            thin: false,
+            verbose: true,
+            archive: PathBuf::from("libfoo.a"),
+            files: vec![OsString::from("foo.o")],
+        };
+        assert_eq!(command, Ok(Command::Run(expected)));
+    }
+
+    #[test]
+    fn parse_extract_command() {
+        let command = parse(&["xUoPN", "2", "--thin", "libfoo.a"]).unwrap();
+        match command {
+            Command::Run(options) => {
+                assert_eq!(options.operation, Operation::Extract);
+                assert_eq!(options.count, Some(2));
+                assert!(!options.deterministic);
+                assert!(options.preserve_dates);
+                assert!(options.full_paths);
+                assert!(options.thin);
+                assert!(options.files.is_empty());
+            }
+            _ => panic!("expected a command to run"),
+        }
+        for args in [["s", "libfoo.a"], ["ts", "libfoo.a"]].iter() {
+            match parse(args).unwrap() {
+                Command::Run(options) => {
+                    assert_eq!(options.operation, Operation::Index);
+                }
+                _ => panic!("expected a command to run"),
+            }
+        }
+        assert_eq!(parse(&["-M"]), Ok(Command::Mri));
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+        assert_eq!(parse(&["tV", "libfoo.a"]), Ok(Command::Version));
+    }
+
+    #[test]
+    fn parse_invalid_commands() {
+        assert_eq!(parse(&[]), Err(Failure::Usage(None)));
+        assert_eq!(parse(&["r"]), Err(Failure::Usage(None)));
+        assert_eq!(parse(&["ra", "foo.o"]), Err(Failure::Usage(None)));
+        let message = "invalid option -- 'z'".to_string();
+        assert_eq!(parse(&["rz", "libfoo.a"]),
+                   Err(Failure::Usage(Some(message))));
+        let message = "unrecognized option '--frob'".to_string();
+        assert_eq!(parse(&["--frob", "r", "libfoo.a"]),
+                   Err(Failure::Usage(Some(message))));
+        let message = "two different operation options specified";
+        assert_eq!(parse(&["rx", "libfoo.a"]),
+                   Err(Failure::Fatal(1, message.to_string())));
+        let message = "no operation specified";
+        assert_eq!(parse(&["cv", "libfoo.a"]),
+                   Err(Failure::Fatal(1, message.to_string())));
+        let message = "`N' is only meaningful with the `x' and `d' options.";
+        assert_eq!(parse(&["rN", "1", "libfoo.a"]),
+                   Err(Failure::Fatal(1, message.to_string())));
+        let message = "Value for `N' must be positive.";
+        assert_eq!(parse(&["xN", "0", "libfoo.a"]),
+                   Err(Failure::Fatal(1, message.to_string())));
+        assert_eq!(parse(&["dN", "libfoo.a"]),
+                   Err(Failure::Fatal(1, message.to_string())));
+    }
+
+    #[test]
+    fn format_modes() {
+        assert_eq!(mode_string(0o100644), "rw-r--r--");
+        assert_eq!(mode_string(0o755), "rwxr-xr-x");
+        assert_eq!(mode_string(0o6755), "rwsr-sr-x");
+        assert_eq!(mode_string(0o1644), "rw-r--r-T");
+        assert_eq!(mode_string(0), "---------");
+    }
+
+    #[test]
+    fn format_mtimes() {
+        assert_eq!(format_mtime(0), "Jan  1 00:00 1970");
+        assert_eq!(format_mtime(1487552916), "Feb 20 01:08 2017");
+        assert_eq!(format_mtime(951782400), "Feb 29 00:00 2000");
+        assert_eq!(format_mtime(4102444799), "Dec 31 23:59 2099");
+    }
+
+    #[test]
+    fn check_paths_to_extract() {
+        assert!(is_safe_to_extract(Path::new("foo.o")));
+        assert!(is_safe_to_extract(Path::new("./sub/foo.o")));
+        assert!(!is_safe_to_extract(Path::new("../foo.o")));
+        assert!(!is_safe_to_extract(Path::new("sub/../../foo.o")));
+        assert!(!is_safe_to_extract(Path::new("/etc/passwd")));
+    }
+
+    #[test]
+    fn compute_relative_paths() {
+        assert_eq!(relative_path(Path::new("/a/b"), Path::new("/a/b/c.o")),
+                   PathBuf::from("c.o"));
+        assert_eq!(relative_path(Path::new("/a/b"), Path::new("/a/c/d.o")),
+                   PathBuf::from("../c/d.o"));
+        assert_eq!(relative_path(Path::new("/a/./b"), Path::new("/e.o")),
+                   PathBuf::from("../../e.o"));
+    }
+}
+
+// ========================================================================= //
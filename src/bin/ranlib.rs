@@ -0,0 +1,306 @@
+//! A command-line tool for generating the symbol table of an archive,
+//! compatible with the `ranlib` utility from GNU binutils.
+//!
+//! Every member of each archive named on the command line is read, and the
+//! global symbols defined by those that are ELF, Mach-O, or COFF object files,
+//! or LLVM bitcode (see the `ar::object` module), are collected into a fresh
+//! symbol table, which replaces any existing one.  The archive is otherwise
+//! rewritten as is, in its own variant (except that archives without any BSD
+//! or GNU extensions are rewritten in the GNU variant, as with `ar`).  Thin
+//! archives are indexed by reading the files that their members refer to.
+//! For example:
+//!
+//! ```shell
+//! ranlib libfoo.a
+//! ```
+//!
+//! The symbol table is always written with a zero timestamp, owner, and
+//! group, as with `-D`; the `-U` and `-t` options are accepted but ignored.
+//! For BSD-variant archives, `-s` writes a `__.SYMDEF SORTED` symbol table
+//! (as Apple's `ranlib` does) and `-a` writes a `__.SYMDEF` one; by default,
+//! the flavor of the existing symbol table (if any) is kept.
+
+extern crate ar;
+
+use ar::object;
+use ar::{Archive, ArchiveEditor, StagedBuilder, Variant, write_atomically};
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// ========================================================================= //
+
+const USAGE: &str = "\
+Usage: ranlib [options] archive...
+ Generate an index to speed access to archives
+ The options are:
+  -D                           Use zero for symbol map timestamp (default)
+  -U                           Use an actual symbol map timestamp (ignored)
+  -t                           Update the archive's symbol map timestamp \
+(ignored)
+  -s                           Write a sorted BSD symbol map (__.SYMDEF \
+SORTED)
+  -a                           Write an unsorted BSD symbol map (__.SYMDEF)
+  -h --help                    Print this help message
+  -v -V --version              Print version information
+";
+
+const GLOBAL_HEADER_LEN: u64 = 8;
+const GLOBAL_HEADERS: &[&[u8]] = &[b"!<arch>\n", b"!<thin>\n", b"<bigaf>\n"];
+
+// ========================================================================= //
+
+#[derive(Debug, Eq, PartialEq)]
+struct Options {
+    // Whether to sort the symbol table of a BSD-variant archive, or None to
+    // keep the flavor of its existing symbol table.
+    sorted: Option<bool>,
+    archives: Vec<PathBuf>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Failure {
+    // A command-line error, with an optional message to print before the
+    // usage text.
+    Usage(Option<String>),
+    // A fatal error, with the message to print.
+    Fatal(String),
+}
+
+fn path_failure(path: &Path, error: &io::Error) -> Failure {
+    Failure::Fatal(format!("{}: {}", path.display(), describe(error)))
+}
+
+/// Formats an I/O error the way that the C library would, without the
+/// " (os error N)" suffix that std adds.
+fn describe(error: &io::Error) -> String {
+    let message = error.to_string();
+    match message.find(" (os error ") {
+        Some(index) if error.raw_os_error().is_some() => {
+            message[..index].to_string()
+        }
+        _ => message,
+    }
+}
+
+// ========================================================================= //
+
+fn main() {
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let result = parse_args(args).and_then(|command| match command {
+        Command::Run(options) => {
+            for path in options.archives.iter() {
+                try!(index_archive(&options, path));
+            }
+            Ok(())
+        }
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Command::Version => {
+            println!("ranlib (rust-ar) {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+    });
+    let code = match result {
+        Ok(()) => 0,
+        Err(Failure::Usage(message)) => {
+            if let Some(message) = message {
+                eprintln!("ranlib: {}", message);
+            }
+            eprint!("{}", USAGE);
+            1
+        }
+        Err(Failure::Fatal(message)) => {
+            eprintln!("ranlib: {}", message);
+            1
+        }
+    };
+    process::exit(code);
+}
+
+fn parse_args(args: Vec<OsString>) -> Result<Command, Failure> {
+    // As with getopt, options may appear anywhere before a "--".
+    let mut options = Options {
+        sorted: None,
+        archives: Vec::new(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let flags = match arg.to_str() {
+            Some("--") => {
+                options.archives.extend(args.by_ref().map(PathBuf::from));
+                break;
+            }
+            Some("--help") => return Ok(Command::Help),
+            Some("--version") => return Ok(Command::Version),
+            Some(option) if option.starts_with("--") => {
+                let msg = format!("unrecognized option '{}'", option);
+                return Err(Failure::Usage(Some(msg)));
+            }
+            Some(flags) if flags.starts_with('-') && flags.len() > 1 => {
+                flags[1..].to_string()
+            }
+            _ => {
+                options.archives.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        for flag in flags.chars() {
+            match flag {
+                'D' | 'U' | 't' => {}
+                's' => options.sorted = Some(true),
+                'a' => options.sorted = Some(false),
+                'h' => return Ok(Command::Help),
+                'v' | 'V' => return Ok(Command::Version),
+                _ => {
+                    let msg = format!("invalid option -- '{}'", flag);
+                    return Err(Failure::Usage(Some(msg)));
+                }
+            }
+        }
+    }
+    if options.archives.is_empty() {
+        return Err(Failure::Usage(None));
+    }
+    Ok(Command::Run(options))
+}
+
+// ========================================================================= //
+
+fn index_archive(options: &Options, path: &Path) -> Result<(), Failure> {
+    if !path.exists() {
+        let msg = format!("'{}': No such file", path.display());
+        return Err(Failure::Fatal(msg));
+    }
+    let failure = |error| path_failure(path, &error);
+    if !try!(is_archive(path).map_err(&failure)) {
+        let msg = format!("{}: file format not recognized", path.display());
+        return Err(Failure::Fatal(msg));
+    }
+    let mut archive = try!(Archive::open(path).map_err(&failure));
+    try!(archive.count_entries().map_err(&failure));
+    match archive.variant() {
+        Variant::Common | Variant::BSD | Variant::GNU => {}
+        _ => {
+            let msg = format!(
+                "{}: cannot modify {:?} archives",
+                path.display(),
+                archive.variant()
+            );
+            return Err(Failure::Fatal(msg));
+        }
+    }
+    if archive.is_thin() {
+        return index_thin_archive(path, archive);
+    }
+    let mut editor = try!(ArchiveEditor::new(archive).map_err(&failure));
+    if editor.variant() == Variant::Common {
+        editor.set_variant(Variant::GNU);
+    }
+    if let Some(sorted) = options.sorted {
+        editor.set_sorted_symbol_table(sorted);
+    }
+    editor.set_regenerate_symbol_table(true);
+    editor.write_to_path(path).map_err(&failure)
+}
+
+/// Returns true if the given file starts with the global header of one of
+/// the archive formats that the `ar` crate can read.
+fn is_archive(path: &Path) -> io::Result<bool> {
+    let file = try!(File::open(path));
+    let mut header = Vec::new();
+    try!(file.take(GLOBAL_HEADER_LEN).read_to_end(&mut header));
+    Ok(GLOBAL_HEADERS.contains(&header.as_slice()))
+}
+
+/// Indexes a thin archive, which `ArchiveEditor` can't edit, by reading
+/// each member's file and then writing the headers back out with a
+/// `StagedBuilder`.
+fn index_thin_archive(path: &Path, mut archive: Archive<File>)
+                      -> Result<(), Failure> {
+    let failure = |error| path_failure(path, &error);
+    let count = try!(archive.count_entries().map_err(&failure));
+    let mut builder = StagedBuilder::new(Variant::GNU);
+    builder.set_thin(true);
+    for index in 0..count {
+        let mut entry = try!(archive.jump_to_entry(index).map_err(&failure));
+        let identifier = entry.header().identifier().to_vec();
+        let mut data = Vec::new();
+        let result = entry
+            .read_to_end(&mut data)
+            .and_then(|_| object::defined_symbols(&data));
+        let defined = try!(result.map_err(|error| {
+            let msg = format!(
+                "{}: {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&identifier),
+                describe(&error)
+            );
+            Failure::Fatal(msg)
+        }));
+        for symbol in defined.into_iter() {
+            builder.add_symbol_for_entry(symbol, index);
+        }
+        builder.append(entry.header().clone(), io::empty());
+    }
+    write_atomically(path, |writer| builder.write(writer)).map_err(&failure)
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Failure, Options, parse_args};
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    fn parse(args: &[&str]) -> Result<Command, Failure> {
+        parse_args(args.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn parse_options() {
+        let expected = Options {
+            sorted: Some(true),
+            archives: vec![PathBuf::from("libfoo.a"), PathBuf::from("-a")],
+        };
+        assert_eq!(parse(&["-Dt", "libfoo.a", "-s", "--", "-a"]),
+                   Ok(Command::Run(expected)));
+        let expected = Options {
+            sorted: Some(false),
+            archives: vec![PathBuf::from("libfoo.a")],
+        };
+        assert_eq!(parse(&["-sDaU", "libfoo.a"]),
+                   Ok(Command::Run(expected)));
+        assert_eq!(parse(&["-h"]), Ok(Command::Help));
+        assert_eq!(parse(&["libfoo.a", "--help"]), Ok(Command::Help));
+        assert_eq!(parse(&["-V", "libfoo.a"]), Ok(Command::Version));
+        assert_eq!(parse(&["--version"]), Ok(Command::Version));
+    }
+
+    #[test]
+    fn parse_invalid_options() {
+        assert_eq!(parse(&[]), Err(Failure::Usage(None)));
+        assert_eq!(parse(&["-D"]), Err(Failure::Usage(None)));
+        let message = "invalid option -- 'x'".to_string();
+        assert_eq!(parse(&["-Dx", "libfoo.a"]),
+                   Err(Failure::Usage(Some(message))));
+        let message = "unrecognized option '--plugin'".to_string();
+        assert_eq!(parse(&["--plugin", "foo.so", "libfoo.a"]),
+                   Err(Failure::Usage(Some(message))));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,410 @@
+//! Decoding of the short import objects found in Windows import libraries.
+//!
+//! An import library (a `.lib` file that lets a program link against the
+//! exports of a DLL) consists mostly of entries holding a short
+//! `IMPORT_OBJECT_HEADER` record, rather than a full COFF object file.  Each
+//! such record names a DLL and one symbol that it exports.
+//!
+//! This module is only available when the `coff-import` feature is enabled.
+//!
+//! # Example usage
+//!
+//! Listing the imports in an import library:
+//!
+//! ```no_run
+//! use ar::Archive;
+//! use ar::coff_import::ImportObject;
+//! use std::str;
+//! let mut archive = Archive::open("foo.lib").unwrap();
+//! while let Some(entry_result) = archive.next_entry() {
+//!     let mut entry = entry_result.unwrap();
+//!     // Entries that aren't short import objects are skipped:
+//!     if let Some(import) = ImportObject::read(&mut entry).unwrap() {
+//!         println!(
+//!             "{} from {}",
+//!             str::from_utf8(import.symbol()).unwrap(),
+//!             str::from_utf8(import.dll()).unwrap(),
+//!         );
+//!     }
+//! }
+//! ```
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Error, ErrorKind, Read, Result};
+
+// ========================================================================= //
+
+const IMPORT_OBJECT_HEADER_LEN: usize = 20;
+
+// An import object header starts with an IMAGE_FILE_MACHINE_UNKNOWN machine
+// type, followed by 0xFFFF.  Anonymous objects (such as `/bigobj` object files
+// and LTCG objects) start the same way, but with a nonzero version number.
+const IMPORT_OBJECT_SIGNATURE: &[u8] = b"\x00\x00\xff\xff";
+const IMPORT_OBJECT_VERSION: u16 = 0;
+
+// ========================================================================= //
+
+/// The kind of thing that a short import object imports.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImportType {
+    /// Executable code (`IMPORT_OBJECT_CODE`).
+    Code,
+    /// Data (`IMPORT_OBJECT_DATA`).
+    Data,
+    /// A constant (`IMPORT_OBJECT_CONST`).
+    Const,
+}
+
+/// How the symbol of a short import object is looked up in its DLL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImportNameType {
+    /// By ordinal rather than by name (`IMPORT_OBJECT_ORDINAL`).
+    Ordinal,
+    /// By the symbol name itself (`IMPORT_OBJECT_NAME`).
+    Name,
+    /// By the symbol name, minus any leading `?`, `@`, or `_`
+    /// (`IMPORT_OBJECT_NAME_NO_PREFIX`).
+    NameNoPrefix,
+    /// By the symbol name, minus any leading `?`, `@`, or `_`, and truncated
+    /// at the first `@` (`IMPORT_OBJECT_NAME_UNDECORATE`).
+    NameUndecorate,
+    /// By a separate export name stored in the import object
+    /// (`IMPORT_OBJECT_NAME_EXPORTAS`).
+    NameExportAs,
+}
+
+// ========================================================================= //
+
+/// A decoded short import object.
+#[derive(Clone, Debug)]
+pub struct ImportObject {
+    machine: u16,
+    timestamp: u32,
+    ordinal_or_hint: u16,
+    import_type: ImportType,
+    name_type: ImportNameType,
+    symbol: Vec<u8>,
+    dll: Vec<u8>,
+    export_name: Option<Vec<u8>>,
+}
+
+impl ImportObject {
+    /// Reads a short import object from the given reader (typically an
+    /// archive `Entry`).  Returns `Ok(None)` if the data doesn't start with an
+    /// import object header (e.g. because it's a full COFF object file, or an
+    /// anonymous object such as a `/bigobj` object file), in which case some
+    /// of the data may have been consumed.
+    pub fn read<R: Read>(mut reader: R) -> Result<Option<ImportObject>> {
+        let mut header = [0; IMPORT_OBJECT_HEADER_LEN];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(ref error) if error.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        }
+        if !header.starts_with(IMPORT_OBJECT_SIGNATURE) {
+            return Ok(None);
+        }
+        let mut fields = &header[IMPORT_OBJECT_SIGNATURE.len()..];
+        if try!(fields.read_u16::<LittleEndian>()) != IMPORT_OBJECT_VERSION {
+            return Ok(None);
+        }
+        let machine = try!(fields.read_u16::<LittleEndian>());
+        let timestamp = try!(fields.read_u32::<LittleEndian>());
+        let size_of_data = try!(fields.read_u32::<LittleEndian>());
+        let ordinal_or_hint = try!(fields.read_u16::<LittleEndian>());
+        let type_info = try!(fields.read_u16::<LittleEndian>());
+        let import_type = match type_info & 0x3 {
+            0 => ImportType::Code,
+            1 => ImportType::Data,
+            2 => ImportType::Const,
+            other => {
+                let msg = format!("Invalid import object type ({})", other);
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+        };
+        let name_type = match (type_info >> 2) & 0x7 {
+            0 => ImportNameType::Ordinal,
+            1 => ImportNameType::Name,
+            2 => ImportNameType::NameNoPrefix,
+            3 => ImportNameType::NameUndecorate,
+            4 => ImportNameType::NameExportAs,
+            other => {
+                let msg =
+                    format!("Invalid import object name type ({})", other);
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+        };
+        // The size comes from the file, so let the buffer grow only as the
+        // data actually arrives.
+        let mut data = Vec::new();
+        let actual = try!(
+            reader
+                .by_ref()
+                .take(size_of_data as u64)
+                .read_to_end(&mut data)
+        );
+        if (actual as u64) < size_of_data as u64 {
+            let error = super::Error::TruncatedImportObject {
+                size: size_of_data,
+                actual: actual as u64,
+            };
+            return Err(error.into());
+        }
+        // The data holds the symbol name and the DLL name (and, for
+        // IMPORT_OBJECT_NAME_EXPORTAS, the export name), each terminated by a
+        // null byte.
+        let mut strings = data.split(|&byte| byte == 0);
+        let symbol = strings.next().unwrap_or(&[]).to_vec();
+        let dll = match strings.next() {
+            Some(dll) => dll.to_vec(),
+            None => {
+                let msg = "Import object is missing its DLL name";
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+        };
+        let export_name = if name_type == ImportNameType::NameExportAs {
+            match strings.next() {
+                Some(name) => Some(name.to_vec()),
+                None => {
+                    let msg = "Import object is missing its export name";
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Some(ImportObject {
+            machine: machine,
+            timestamp: timestamp,
+            ordinal_or_hint: ordinal_or_hint,
+            import_type: import_type,
+            name_type: name_type,
+            symbol: symbol,
+            dll: dll,
+            export_name: export_name,
+        }))
+    }
+
+    /// Returns the target machine type (one of the `IMAGE_FILE_MACHINE_*`
+    /// constants, e.g. `0x8664` for x86-64).
+    pub fn machine(&self) -> u16 { self.machine }
+
+    /// Returns the time and date that the import object was created, as
+    /// seconds since the Unix epoch.
+    pub fn timestamp(&self) -> u32 { self.timestamp }
+
+    /// Returns the kind of thing being imported.
+    pub fn import_type(&self) -> ImportType { self.import_type }
+
+    /// Returns how the symbol is looked up in the DLL.
+    pub fn name_type(&self) -> ImportNameType { self.name_type }
+
+    /// Returns the name of the symbol that this import object defines.
+    pub fn symbol(&self) -> &[u8] { &self.symbol }
+
+    /// Returns the name of the DLL that the symbol is imported from.
+    pub fn dll(&self) -> &[u8] { &self.dll }
+
+    /// Returns the ordinal that the symbol is imported by, or `None` if it's
+    /// imported by name.
+    pub fn ordinal(&self) -> Option<u16> {
+        if self.name_type == ImportNameType::Ordinal {
+            Some(self.ordinal_or_hint)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the hint (the likely index of the name in the DLL's export
+    /// name table), or `None` if the symbol is imported by ordinal.
+    pub fn hint(&self) -> Option<u16> {
+        if self.name_type == ImportNameType::Ordinal {
+            None
+        } else {
+            Some(self.ordinal_or_hint)
+        }
+    }
+
+    /// Returns the name that the symbol is looked up by in the DLL (which,
+    /// depending on the name type, may differ from the symbol name), or
+    /// `None` if it's imported by ordinal.
+    pub fn import_name(&self) -> Option<&[u8]> {
+        let symbol = self.symbol.as_slice();
+        let without_prefix = match symbol.first() {
+            Some(&b'?') | Some(&b'@') | Some(&b'_') => &symbol[1..],
+            _ => symbol,
+        };
+        match self.name_type {
+            ImportNameType::Ordinal => None,
+            ImportNameType::Name => Some(symbol),
+            ImportNameType::NameNoPrefix => Some(without_prefix),
+            ImportNameType::NameUndecorate => {
+                let end = without_prefix
+                    .iter()
+                    .position(|&byte| byte == b'@')
+                    .unwrap_or(without_prefix.len());
+                Some(&without_prefix[..end])
+            }
+            ImportNameType::NameExportAs => self.export_name.as_deref(),
+        }
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportNameType, ImportObject, ImportType};
+    use std::io::ErrorKind;
+    use {Archive, Error};
+
+    #[test]
+    fn read_import_object_by_name() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0c\x00\x00\x00\
+        \x03\x00\x04\x00bar\x00foo.dll\x00";
+        let import = ImportObject::read(input).unwrap().unwrap();
+        assert_eq!(import.machine(), 0x8664);
+        assert_eq!(import.timestamp(), 0);
+        assert_eq!(import.import_type(), ImportType::Code);
+        assert_eq!(import.name_type(), ImportNameType::Name);
+        assert_eq!(import.symbol(), b"bar");
+        assert_eq!(import.dll(), b"foo.dll");
+        assert_eq!(import.ordinal(), None);
+        assert_eq!(import.hint(), Some(3));
+        assert_eq!(import.import_name(), Some(b"bar" as &[u8]));
+    }
+
+    #[test]
+    fn read_import_object_by_ordinal() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x4c\x01\x00\x00\x00\x00\x0d\x00\x00\x00\
+        \x05\x00\x00\x00_baz\x00foo.dll\x00";
+        let import = ImportObject::read(input).unwrap().unwrap();
+        assert_eq!(import.machine(), 0x14c);
+        assert_eq!(import.import_type(), ImportType::Code);
+        assert_eq!(import.name_type(), ImportNameType::Ordinal);
+        assert_eq!(import.symbol(), b"_baz");
+        assert_eq!(import.ordinal(), Some(5));
+        assert_eq!(import.hint(), None);
+        assert_eq!(import.import_name(), None);
+    }
+
+    #[test]
+    fn read_data_import_object_without_prefix() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x4c\x01\x00\x00\x00\x00\x0d\x00\x00\x00\
+        \x00\x00\x09\x00_qux\x00foo.dll\x00";
+        let import = ImportObject::read(input).unwrap().unwrap();
+        assert_eq!(import.import_type(), ImportType::Data);
+        assert_eq!(import.name_type(), ImportNameType::NameNoPrefix);
+        assert_eq!(import.symbol(), b"_qux");
+        assert_eq!(import.import_name(), Some(b"qux" as &[u8]));
+    }
+
+    #[test]
+    fn read_undecorated_import_object() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x4c\x01\x00\x00\x00\x00\x12\x00\x00\x00\
+        \x00\x00\x0c\x00__under@8\x00foo.dll\x00";
+        let import = ImportObject::read(input).unwrap().unwrap();
+        assert_eq!(import.name_type(), ImportNameType::NameUndecorate);
+        assert_eq!(import.import_name(), Some(b"_under" as &[u8]));
+    }
+
+    #[test]
+    fn read_import_object_with_export_name() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x10\x00\x00\x00\
+        \x00\x00\x10\x00bar\x00foo.dll\x00baz\x00";
+        let import = ImportObject::read(input).unwrap().unwrap();
+        assert_eq!(import.name_type(), ImportNameType::NameExportAs);
+        assert_eq!(import.import_name(), Some(b"baz" as &[u8]));
+    }
+
+    #[test]
+    fn read_object_that_is_not_an_import_object() {
+        let input: &[u8] = b"\
+        \x64\x86\x02\x00\x00\x00\x00\x00\x9e\x00\x00\x00\x07\x00\x00\x00\
+        \x00\x00\x00\x00";
+        assert!(ImportObject::read(input).unwrap().is_none());
+        let input: &[u8] = b"\x00\x00\xff\xff";
+        assert!(ImportObject::read(input).unwrap().is_none());
+    }
+
+    #[test]
+    fn read_bigobj_object() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x02\x00\x64\x86\x00\x00\x00\x00\xc7\xa1\xba\xd1\
+        \xee\xba\xa9\x4b\xaf\x20\xfa\xf6\x6a\xa4\xdc\xb8\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00";
+        assert!(ImportObject::read(input).unwrap().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid import object name type (5)")]
+    fn read_import_object_with_invalid_name_type() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0c\x00\x00\x00\
+        \x00\x00\x14\x00bar\x00foo.dll\x00";
+        ImportObject::read(input).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Import object is missing its DLL name")]
+    fn read_import_object_without_dll_name() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x03\x00\x00\x00\
+        \x00\x00\x04\x00bar";
+        ImportObject::read(input).unwrap();
+    }
+
+    #[test]
+    fn read_truncated_import_object() {
+        let input: &[u8] = b"\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\xff\xff\xff\xff\
+        \x00\x00\x04\x00bar\x00foo.dll\x00";
+        let error = ImportObject::read(input).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(
+            error.to_string(),
+            "Unexpected EOF in the middle of import object data (12 of \
+             4294967295 bytes)"
+        );
+        let expected = Error::TruncatedImportObject {
+            size: 0xffffffff,
+            actual: 12,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn list_imports_in_archive() {
+        let input = b"\
+        !<arch>\n\
+        foo.dll/        0           0     0     644     32        `\n\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0c\x00\x00\x00\
+        \x00\x00\x04\x00bar\x00foo.dll\x00\
+        foo.o/          0           0     0     644     4         `\n\
+        \x64\x86\x00\x00\
+        foo.dll/        0           0     0     644     32        `\n\
+        \x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0c\x00\x00\x00\
+        \x05\x00\x04\x00baz\x00foo.dll\x00";
+        let mut archive = Archive::new(input as &[u8]);
+        let mut symbols = Vec::new();
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry.unwrap();
+            if let Some(import) = ImportObject::read(&mut entry).unwrap() {
+                symbols.push((import.symbol().to_vec(), import.hint()));
+            }
+        }
+        let expected =
+            vec![(b"bar".to_vec(), Some(0)), (b"baz".to_vec(), Some(5))];
+        assert_eq!(symbols, expected);
+    }
+}
+
+// ========================================================================= //
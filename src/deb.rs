@@ -0,0 +1,822 @@
+//! Reading and writing of Debian binary packages (`.deb` files).
+//!
+//! A Debian package is a common-variant archive whose members are, in order:
+//!
+//! * `debian-binary`, which holds the package format version (`2.0\n`).
+//! * `control.tar`, a tarball of the package's metadata and maintainer
+//!   scripts.
+//! * `data.tar`, a tarball of the files to be installed.
+//!
+//! Either tarball may be compressed, in which case its name has a suffix such
+//! as `.gz` or `.xz`.  Members whose names start with an underscore may appear
+//! between `debian-binary` and the tarballs, and any members after `data.tar`
+//! are reserved for future use; both kinds are skipped over.
+//!
+//! # Example usage
+//!
+//! Writing a package from a pair of existing tarballs:
+//!
+//! ```no_run
+//! use ar::deb::{Compression, DebBuilder};
+//! use std::fs::File;
+//! let mut deb = DebBuilder::new(File::create("foo.deb").unwrap());
+//! let control = File::open("control.tar.xz").unwrap();
+//! let size = control.metadata().unwrap().len();
+//! deb.append_control(Compression::Xz, size, control).unwrap();
+//! let data = File::open("data.tar.xz").unwrap();
+//! let size = data.metadata().unwrap().len();
+//! deb.append_data(Compression::Xz, size, data).unwrap();
+//! deb.into_inner().unwrap();
+//! ```
+//!
+//! Extracting the compressed data tarball from a package:
+//!
+//! ```no_run
+//! use ar::deb::DebArchive;
+//! use std::fs::File;
+//! use std::io;
+//! let mut deb = DebArchive::new(File::open("foo.deb").unwrap());
+//! let mut data = deb.data().unwrap();
+//! let name = format!("data.tar{}", data.compression().extension());
+//! let mut file = File::create(name).unwrap();
+//! io::copy(&mut data, &mut file).unwrap();
+//! ```
+
+use super::{Archive, Builder, Entry, Header, Variant};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::str;
+
+// ========================================================================= //
+
+const DEBIAN_BINARY_ID: &[u8] = b"debian-binary";
+const CONTROL_TAR_ID: &[u8] = b"control.tar";
+const DATA_TAR_ID: &[u8] = b"data.tar";
+
+// Real packages only ever contain "2.0\n", so anything much longer than that
+// isn't a package at all.
+const MAX_DEBIAN_BINARY_LEN: u64 = 64;
+
+const FORMAT_VERSION: &[u8] = b"2.0\n";
+
+// The mode that dpkg-deb gives every member of the packages that it builds.
+const MEMBER_MODE: u32 = 0o100644;
+
+// ========================================================================= //
+
+/// The compression applied to one of the tarballs in a Debian package.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    /// No compression (`.tar`).
+    None,
+    /// gzip compression (`.tar.gz`).
+    Gzip,
+    /// xz compression (`.tar.xz`).
+    Xz,
+    /// Zstandard compression (`.tar.zst`).
+    Zstd,
+    /// bzip2 compression (`.tar.bz2`); only allowed for `data.tar`.
+    Bzip2,
+    /// Legacy LZMA compression (`.tar.lzma`); only allowed for `data.tar`.
+    Lzma,
+}
+
+impl Compression {
+    /// Returns the suffix that follows `.tar` in the name of a member
+    /// compressed this way (e.g. `".gz"`), or an empty string for no
+    /// compression.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Xz => ".xz",
+            Compression::Zstd => ".zst",
+            Compression::Bzip2 => ".bz2",
+            Compression::Lzma => ".lzma",
+        }
+    }
+
+    fn from_extension(extension: &[u8]) -> Option<Compression> {
+        match extension {
+            b"" => Some(Compression::None),
+            b".gz" => Some(Compression::Gzip),
+            b".xz" => Some(Compression::Xz),
+            b".zst" => Some(Compression::Zstd),
+            b".bz2" => Some(Compression::Bzip2),
+            b".lzma" => Some(Compression::Lzma),
+            _ => None,
+        }
+    }
+
+    /// Returns true if dpkg accepts this compression for `control.tar`.
+    fn allowed_for_control(self) -> bool {
+        match self {
+            Compression::None |
+            Compression::Gzip |
+            Compression::Xz |
+            Compression::Zstd => true,
+            Compression::Bzip2 | Compression::Lzma => false,
+        }
+    }
+}
+
+// ========================================================================= //
+
+/// Which member of a Debian package we expect to read next.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Stage {
+    DebianBinary,
+    Control,
+    Data,
+    Done,
+}
+
+/// A structure for reading a Debian binary package.
+///
+/// The members of the package are read in order, so `control()` must be
+/// called before `data()` if both are wanted (calling `data()` first skips
+/// over the control tarball).
+pub struct DebArchive<R: Read> {
+    archive: Archive<R>,
+    stage: Stage,
+    format_version: String,
+}
+
+impl<R: Read> DebArchive<R> {
+    /// Create a new Debian package reader with the underlying reader object
+    /// as the source of all data read.
+    pub fn new(reader: R) -> DebArchive<R> {
+        DebArchive {
+            archive: Archive::new(reader),
+            stage: Stage::DebianBinary,
+            format_version: String::new(),
+        }
+    }
+
+    /// Unwrap this package reader, returning the underlying reader object.
+    pub fn into_inner(self) -> Result<R> { self.archive.into_inner() }
+
+    /// Returns the package format version (e.g. `"2.0"`), as given by the
+    /// `debian-binary` member.  Returns an error if the package doesn't start
+    /// with a `debian-binary` member, or if the format version isn't 2.x.
+    pub fn format_version(&mut self) -> Result<&str> {
+        try!(self.read_debian_binary_if_necessary());
+        Ok(&self.format_version)
+    }
+
+    /// Returns a reader for the `control.tar` member of the package.  Returns
+    /// an error if the control tarball has already been read past, or if the
+    /// package's members are missing or out of order.
+    pub fn control(&mut self) -> Result<Tarball<'_, R>> {
+        try!(self.read_debian_binary_if_necessary());
+        if self.stage != Stage::Control {
+            let msg = "The control.tar member has already been read past";
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let tarball = try!(next_tarball(&mut self.archive, CONTROL_TAR_ID));
+        self.stage = Stage::Data;
+        Ok(tarball)
+    }
+
+    /// Returns a reader for the `data.tar` member of the package, skipping
+    /// over the control tarball if it hasn't been read yet.  Returns an error
+    /// if the data tarball has already been read, or if the package's members
+    /// are missing or out of order.
+    pub fn data(&mut self) -> Result<Tarball<'_, R>> {
+        try!(self.read_debian_binary_if_necessary());
+        if self.stage == Stage::Control {
+            try!(self.control());
+        }
+        if self.stage != Stage::Data {
+            let msg = "The data.tar member has already been read";
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let tarball = try!(next_tarball(&mut self.archive, DATA_TAR_ID));
+        self.stage = Stage::Done;
+        Ok(tarball)
+    }
+
+    fn read_debian_binary_if_necessary(&mut self) -> Result<()> {
+        if self.stage != Stage::DebianBinary {
+            return Ok(());
+        }
+        let mut contents = Vec::new();
+        {
+            let entry = match self.archive.next_entry() {
+                Some(result) => try!(result),
+                None => {
+                    let msg = "Debian package has no members";
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+            };
+            if entry.header().identifier() != DEBIAN_BINARY_ID {
+                let msg = format!(
+                    "Expected debian-binary as the first member of Debian \
+                     package, but found {:?}",
+                    String::from_utf8_lossy(entry.header().identifier())
+                );
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+            if entry.header().size() > MAX_DEBIAN_BINARY_LEN {
+                let msg = format!(
+                    "Debian package's debian-binary member is too large ({} \
+                     bytes)",
+                    entry.header().size()
+                );
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+            try!(entry.take(MAX_DEBIAN_BINARY_LEN).read_to_end(&mut contents));
+        }
+        // Minor version increases are backwards-compatible, and dpkg ignores
+        // anything after the first line.
+        let version = match contents.iter().position(|&byte| byte == b'\n') {
+            Some(end) => &contents[..end],
+            None => &contents[..],
+        };
+        let supported = version.len() > 2 && version.starts_with(b"2.") &&
+            version[2..].iter().all(u8::is_ascii_digit) &&
+            contents.len() > version.len();
+        if !supported {
+            let msg = format!(
+                "Unsupported Debian package format version {:?}",
+                String::from_utf8_lossy(&contents)
+            );
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+        self.format_version = str::from_utf8(version).unwrap().to_string();
+        self.stage = Stage::Control;
+        Ok(())
+    }
+}
+
+/// Reads the next member of the package that isn't an underscore-prefixed
+/// extension, which must be a tarball with the given name.
+fn next_tarball<'a, R: Read>(archive: &'a mut Archive<R>, name: &[u8])
+                             -> Result<Tarball<'a, R>> {
+    let entry = match archive.next_entry_where(|header| {
+        !header.identifier().starts_with(b"_")
+    }) {
+        Some(result) => try!(result),
+        None => {
+            let msg = format!(
+                "Debian package is missing its {} member",
+                String::from_utf8_lossy(name)
+            );
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+    };
+    let compression = {
+        let identifier = entry.header().identifier();
+        match tarball_compression(identifier, name) {
+            Some(compression) => {
+                if name == CONTROL_TAR_ID &&
+                    !compression.allowed_for_control()
+                {
+                    let msg = format!(
+                        "Unsupported compression for control.tar member of \
+                         Debian package ({:?})",
+                        String::from_utf8_lossy(identifier)
+                    );
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+                compression
+            }
+            None => {
+                let msg = format!(
+                    "Expected {} member of Debian package, but found {:?}",
+                    String::from_utf8_lossy(name),
+                    String::from_utf8_lossy(identifier)
+                );
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+        }
+    };
+    Ok(Tarball {
+        entry: entry,
+        compression: compression,
+    })
+}
+
+/// If `identifier` is the given tarball name followed by a known compression
+/// suffix, returns that compression.
+fn tarball_compression(identifier: &[u8], name: &[u8]) -> Option<Compression> {
+    if identifier.starts_with(name) {
+        Compression::from_extension(&identifier[name.len()..])
+    } else {
+        None
+    }
+}
+
+// ========================================================================= //
+
+/// Representation of the control or data tarball of a Debian package.
+///
+/// Like an `Entry`, a `Tarball` implements the `Read` trait (and the `Seek`
+/// trait, if the underlying reader does), yielding the tarball's data as
+/// stored, without decompressing it.
+pub struct Tarball<'a, R: 'a + Read> {
+    entry: Entry<'a, R>,
+    compression: Compression,
+}
+
+impl<'a, R: 'a + Read> Tarball<'a, R> {
+    /// Returns the archive header for this tarball.
+    pub fn header(&self) -> &Header { self.entry.header() }
+
+    /// Returns the compression applied to this tarball, as indicated by its
+    /// name.
+    pub fn compression(&self) -> Compression { self.compression }
+}
+
+impl<'a, R: 'a + Read> Read for Tarball<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.entry.read(buf)
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> Seek for Tarball<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> { self.entry.seek(pos) }
+}
+
+// ========================================================================= //
+
+/// A structure for building Debian binary packages that dpkg will accept.
+///
+/// The `debian-binary` member is written automatically, and the remaining
+/// members must be appended in order: the control tarball, then the data
+/// tarball, optionally with underscore-prefixed members before either of
+/// them.  Every member is written with a common-variant header, with its
+/// owner, group, and mode set the way dpkg-deb sets them and its modification
+/// time set to a fixed value (zero by default), so that the same inputs
+/// always produce the same package.
+pub struct DebBuilder<W: Write> {
+    builder: Builder<W>,
+    stage: Stage,
+    mtime: u64,
+}
+
+impl<W: Write> DebBuilder<W> {
+    /// Create a new Debian package builder with the underlying writer object
+    /// as the destination of all data written.
+    pub fn new(writer: W) -> DebBuilder<W> {
+        DebBuilder {
+            builder: Builder::with_variant(writer, Variant::Common),
+            stage: Stage::DebianBinary,
+            mtime: 0,
+        }
+    }
+
+    /// Sets the modification time recorded in the headers of members
+    /// appended from now on, in seconds since the Unix epoch.
+    pub fn set_mtime(&mut self, mtime: u64) { self.mtime = mtime; }
+
+    /// Unwrap this package builder, returning the underlying writer object.
+    ///
+    /// Returns an error if the data tarball hasn't been appended yet, since
+    /// dpkg would reject the package.
+    pub fn into_inner(self) -> Result<W> {
+        let missing = match self.stage {
+            Stage::DebianBinary | Stage::Control => CONTROL_TAR_ID,
+            Stage::Data => DATA_TAR_ID,
+            Stage::Done => return self.builder.into_inner(),
+        };
+        let msg = format!(
+            "Debian package is missing its {} member",
+            String::from_utf8_lossy(missing)
+        );
+        Err(Error::new(ErrorKind::InvalidInput, msg))
+    }
+
+    /// Adds the control tarball to this package, with its name chosen to
+    /// match the given compression.
+    pub fn append_control<R: Read>(&mut self, compression: Compression,
+                                   size: u64, data: R)
+                                   -> Result<()> {
+        let mut identifier = CONTROL_TAR_ID.to_vec();
+        identifier.extend_from_slice(compression.extension().as_bytes());
+        self.append(&Header::new(identifier, size), data)
+    }
+
+    /// Adds the data tarball to this package, with its name chosen to match
+    /// the given compression.
+    pub fn append_data<R: Read>(&mut self, compression: Compression,
+                                size: u64, data: R)
+                                -> Result<()> {
+        let mut identifier = DATA_TAR_ID.to_vec();
+        identifier.extend_from_slice(compression.extension().as_bytes());
+        self.append(&Header::new(identifier, size), data)
+    }
+
+    /// Adds a new member to this package.  Only the identifier and size are
+    /// taken from the given header; the other fields are normalized as
+    /// described above.  Returns an error if the identifier isn't allowed at
+    /// this point in the package, or if it couldn't be stored in a
+    /// common-variant header.
+    pub fn append<R: Read>(&mut self, header: &Header, data: R)
+        -> Result<()> {
+        // Check this up front, so that we don't write debian-binary for a
+        // member that the builder will then reject.
+        try!(header.check_identifier(Variant::Common));
+        let next_stage = try!(self.stage_after(header.identifier()));
+        try!(self.append_debian_binary_if_necessary());
+        try!(self.builder.append(&self.normalize(header), data));
+        self.stage = next_stage;
+        Ok(())
+    }
+
+    /// Returns the stage that appending a member with the given identifier
+    /// would move us to, or an error if the member isn't allowed next.
+    fn stage_after(&self, identifier: &[u8]) -> Result<Stage> {
+        if self.stage == Stage::Done {
+            let msg = "Debian package already has its data.tar member";
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if identifier.starts_with(b"_") {
+            return Ok(match self.stage {
+                Stage::DebianBinary => Stage::Control,
+                stage => stage,
+            });
+        }
+        if let Some(compression) =
+            tarball_compression(identifier, CONTROL_TAR_ID)
+        {
+            if self.stage == Stage::Data {
+                let msg = "Debian package already has its control.tar member";
+                return Err(Error::new(ErrorKind::InvalidInput, msg));
+            }
+            if !compression.allowed_for_control() {
+                let msg = format!(
+                    "Unsupported compression for control.tar member of \
+                     Debian package ({:?})",
+                    String::from_utf8_lossy(identifier)
+                );
+                return Err(Error::new(ErrorKind::InvalidInput, msg));
+            }
+            return Ok(Stage::Data);
+        }
+        if tarball_compression(identifier, DATA_TAR_ID).is_some() {
+            if self.stage != Stage::Data {
+                let msg = "Debian package must have a control.tar member \
+                           before its data.tar member";
+                return Err(Error::new(ErrorKind::InvalidInput, msg));
+            }
+            return Ok(Stage::Done);
+        }
+        let msg = format!(
+            "Invalid Debian package member name {:?}",
+            String::from_utf8_lossy(identifier)
+        );
+        Err(Error::new(ErrorKind::InvalidInput, msg))
+    }
+
+    fn append_debian_binary_if_necessary(&mut self) -> Result<()> {
+        if self.stage != Stage::DebianBinary {
+            return Ok(());
+        }
+        let header = Header::new(
+            DEBIAN_BINARY_ID.to_vec(),
+            FORMAT_VERSION.len() as u64,
+        );
+        try!(self.builder.append(&self.normalize(&header), FORMAT_VERSION));
+        self.stage = Stage::Control;
+        Ok(())
+    }
+
+    fn normalize(&self, header: &Header) -> Header {
+        let mut normalized =
+            Header::new(header.identifier().to_vec(), header.size());
+        normalized.set_mtime(self.mtime);
+        normalized.set_mode(MEMBER_MODE);
+        normalized
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, DebArchive, DebBuilder};
+    use Header;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    const DEBIAN_BINARY: &str = "debian-binary   0           0     0     \
+                                 100644  4         `\n2.0\n";
+
+    fn member(name: &str, data: &str) -> String {
+        let mut member = format!(
+            "{:<16}0           0     0     100644  {:<10}`\n{}",
+            name,
+            data.len(),
+            data
+        );
+        if data.len() % 2 != 0 {
+            member.push('\n');
+        }
+        member
+    }
+
+    #[test]
+    fn build_deb_package() {
+        let mut deb = DebBuilder::new(Vec::new());
+        deb.set_mtime(1234567890);
+        deb.append_control(Compression::Gzip, 7, "control".as_bytes())
+            .unwrap();
+        let mut header = Header::new(b"_extra".to_vec(), 3);
+        header.set_uid(1000);
+        header.set_mode(0o100600);
+        deb.append(&header, "foo".as_bytes()).unwrap();
+        deb.append_data(Compression::None, 4, "data".as_bytes()).unwrap();
+        let actual = deb.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        debian-binary   1234567890  0     0     100644  4         `\n\
+        2.0\n\
+        control.tar.gz  1234567890  0     0     100644  7         `\n\
+        control\n\
+        _extra          1234567890  0     0     100644  3         `\n\
+        foo\n\
+        data.tar        1234567890  0     0     100644  4         `\n\
+        data";
+        assert_eq!(String::from_utf8(actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_and_read_deb_package() {
+        let mut deb = DebBuilder::new(Vec::new());
+        deb.append_control(Compression::Zstd, 7, "control".as_bytes())
+            .unwrap();
+        deb.append_data(Compression::Lzma, 5, "data!".as_bytes()).unwrap();
+        let bytes = deb.into_inner().unwrap();
+        let mut deb = DebArchive::new(bytes.as_slice());
+        assert_eq!(deb.format_version().unwrap(), "2.0");
+        assert_eq!(deb.control().unwrap().compression(), Compression::Zstd);
+        let mut data = deb.data().unwrap();
+        assert_eq!(data.compression(), Compression::Lzma);
+        assert_eq!(data.header().mtime(), 0);
+        let mut buffer = String::new();
+        data.read_to_string(&mut buffer).unwrap();
+        assert_eq!(&buffer, "data!");
+    }
+
+    #[test]
+    fn build_deb_package_with_members_out_of_order() {
+        let mut deb = DebBuilder::new(Vec::new());
+        let error = deb.append_data(Compression::Xz, 0, "".as_bytes())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package must have a control.tar member before its \
+             data.tar member"
+        );
+        deb.append_control(Compression::Xz, 0, "".as_bytes()).unwrap();
+        let error = deb.append_control(Compression::Xz, 0, "".as_bytes())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package already has its control.tar member"
+        );
+        let error = deb.into_inner().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package is missing its data.tar member"
+        );
+
+        let mut deb = DebBuilder::new(Vec::new());
+        deb.append_control(Compression::Xz, 0, "".as_bytes()).unwrap();
+        deb.append_data(Compression::Xz, 0, "".as_bytes()).unwrap();
+        let header = Header::new(b"_extra".to_vec(), 0);
+        let error = deb.append(&header, "".as_bytes()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package already has its data.tar member"
+        );
+    }
+
+    #[test]
+    fn build_deb_package_with_invalid_identifiers() {
+        let mut deb = DebBuilder::new(Vec::new());
+        let cases: &[(&[u8], &str)] = &[
+            (
+                b"_a_very_long_name",
+                "Identifier \"_a_very_long_name\" can't be written in a \
+                 common-variant header (it would need the BSD #1/ \
+                 extension)",
+            ),
+            (
+                b"_with space",
+                "Identifier \"_with space\" can't be written in a \
+                 common-variant header (it would need the BSD #1/ \
+                 extension)",
+            ),
+            (
+                b"debian-binary",
+                "Invalid Debian package member name \"debian-binary\"",
+            ),
+            (
+                b"control.tar.bz2",
+                "Unsupported compression for control.tar member of Debian \
+                 package (\"control.tar.bz2\")",
+            ),
+            (
+                b"control.tgz",
+                "Invalid Debian package member name \"control.tgz\"",
+            ),
+        ];
+        for &(identifier, message) in cases {
+            let header = Header::new(identifier.to_vec(), 0);
+            let error = deb.append(&header, "".as_bytes()).err().unwrap();
+            assert_eq!(error.to_string(), message);
+        }
+        // Nothing should have been written for the rejected members.
+        let error = deb.into_inner().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package is missing its control.tar member"
+        );
+    }
+
+    #[test]
+    fn read_deb_package() {
+        let input = format!(
+            "!<arch>\n{}{}{}{}{}",
+            DEBIAN_BINARY,
+            member("_gpgorigin", "sig"),
+            member("control.tar.gz", "control"),
+            member("data.tar.xz", "data!"),
+            member("_extra", "ignored")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        assert_eq!(deb.format_version().unwrap(), "2.0");
+        {
+            let mut control = deb.control().unwrap();
+            assert_eq!(control.header().identifier(), b"control.tar.gz");
+            assert_eq!(control.compression(), Compression::Gzip);
+            let mut buffer = String::new();
+            control.read_to_string(&mut buffer).unwrap();
+            assert_eq!(&buffer, "control");
+        }
+        {
+            let mut data = deb.data().unwrap();
+            assert_eq!(data.compression(), Compression::Xz);
+            assert_eq!(data.compression().extension(), ".xz");
+            let mut buffer = String::new();
+            data.read_to_string(&mut buffer).unwrap();
+            assert_eq!(&buffer, "data!");
+        }
+        let error = deb.data().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The data.tar member has already been read"
+        );
+    }
+
+    #[test]
+    fn read_data_without_control() {
+        let input = format!(
+            "!<arch>\n{}{}{}",
+            DEBIAN_BINARY,
+            member("control.tar", "control"),
+            member("data.tar.zst", "data")
+        );
+        let mut deb = DebArchive::new(Cursor::new(input.into_bytes()));
+        {
+            let mut data = deb.data().unwrap();
+            assert_eq!(data.compression(), Compression::Zstd);
+            data.seek(SeekFrom::Start(2)).unwrap();
+            let mut buffer = String::new();
+            data.read_to_string(&mut buffer).unwrap();
+            assert_eq!(&buffer, "ta");
+        }
+        let error = deb.control().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The control.tar member has already been read past"
+        );
+    }
+
+    #[test]
+    fn read_deb_package_with_wrong_first_member() {
+        let input = format!(
+            "!<arch>\n{}{}",
+            member("control.tar", "control"),
+            member("data.tar", "data")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        let error = deb.control().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Expected debian-binary as the first member of Debian package, \
+             but found \"control.tar\""
+        );
+    }
+
+    #[test]
+    fn read_deb_package_with_unsupported_version() {
+        for &version in &["3.0\n", "2.0", "2.x\n", "2.\n"] {
+            let input = format!(
+                "!<arch>\n{}",
+                member("debian-binary", version)
+            );
+            let mut deb = DebArchive::new(input.as_bytes());
+            let error = deb.format_version().err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "Unsupported Debian package format version {:?}",
+                    version
+                )
+            );
+        }
+        let input = format!("!<arch>\n{}", member("debian-binary", "2.1\n"));
+        let mut deb = DebArchive::new(input.as_bytes());
+        assert_eq!(deb.format_version().unwrap(), "2.1");
+    }
+
+    #[test]
+    fn read_deb_package_with_members_out_of_order() {
+        let input = format!(
+            "!<arch>\n{}{}{}",
+            DEBIAN_BINARY,
+            member("data.tar.xz", "data"),
+            member("control.tar.xz", "control")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        let error = deb.data().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Expected control.tar member of Debian package, but found \
+             \"data.tar.xz\""
+        );
+    }
+
+    #[test]
+    fn read_deb_package_with_unsupported_compression() {
+        let input = format!(
+            "!<arch>\n{}{}{}",
+            DEBIAN_BINARY,
+            member("control.tar.bz2", "control"),
+            member("data.tar.bz2", "data")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        let error = deb.control().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported compression for control.tar member of Debian \
+             package (\"control.tar.bz2\")"
+        );
+
+        let input = format!(
+            "!<arch>\n{}{}{}",
+            DEBIAN_BINARY,
+            member("control.tar", "control"),
+            member("data.tar.Z", "data")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        let error = deb.data().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Expected data.tar member of Debian package, but found \
+             \"data.tar.Z\""
+        );
+    }
+
+    #[test]
+    fn read_deb_package_with_missing_control() {
+        let input = format!("!<arch>\n{}", DEBIAN_BINARY);
+        let mut deb = DebArchive::new(input.as_bytes());
+        for _ in 0..2 {
+            let error = deb.control().err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                "Debian package is missing its control.tar member"
+            );
+        }
+        let error = deb.data().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Debian package is missing its control.tar member"
+        );
+    }
+
+    #[test]
+    fn read_deb_package_with_missing_data() {
+        let input = format!(
+            "!<arch>\n{}{}{}",
+            DEBIAN_BINARY,
+            member("control.tar.gz", "control"),
+            member("_extra", "ignored")
+        );
+        let mut deb = DebArchive::new(input.as_bytes());
+        deb.control().unwrap();
+        for _ in 0..2 {
+            let error = deb.data().err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                "Debian package is missing its data.tar member"
+            );
+        }
+    }
+}
+
+// ========================================================================= //
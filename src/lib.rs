@@ -15,7 +15,8 @@
 //! common:
 //!
 //! * The *common variant*, used for Debian package (`.deb`) files among other
-//!   things, which only supports filenames up to 16 characters.
+//!   things (see the `deb` module), which only supports filenames up to 16
+//!   characters.
 //! * The *BSD variant*, used by the `ar` utility on BSD systems (including Mac
 //!   OS X), which is backwards-compatible with the common variant, but extends
 //!   it to support longer filenames and filenames containing spaces.
@@ -24,8 +25,52 @@
 //!   stores filenames in a slightly different, incompatible way, and has its
 //!   own strategy for supporting long filenames.
 //!
-//! Currently, this crate supports reading all three of these variants, but
-//! only supports writing the BSD/common variant.
+//! This crate supports reading all three of these variants.  The `Builder`
+//! type writes the BSD/common variant by default, and can instead be made to
+//! write strictly the common variant, or the GNU variant without long names.
+//! The `GnuBuilder` type writes the GNU variant with long names.  The
+//! `StagedBuilder` type can write any of the three, and is the easiest way to
+//! produce an archive with a symbol table.  Any of these can build the symbol
+//! table automatically, given a `SymbolExtractor` to find the symbols that
+//! each entry defines; with the `object` feature (enabled by default), the
+//! `object` module provides one that understands the common object file
+//! formats.
+//!
+//! GNU *thin archives* (which store only entry headers, and refer to the
+//! entries' data in separate files) can be read as well; use `Archive::open`
+//! so that those files can be found relative to the archive.  The
+//! `ThinBuilder` type writes thin archives, as does `StagedBuilder` (see
+//! `StagedBuilder::set_thin`).
+//!
+//! Windows `.lib` files written by Microsoft's tools are read as the *COFF
+//! variant*, which extends the GNU variant with a second symbol table, sorted
+//! by name (see `Archive::sorted_symbols`).  With the `coff-import` feature
+//! enabled, the `coff_import` module can decode the short import objects that
+//! make up most of a Windows import library.
+//!
+//! Existing archives (other than thin, AIX, and COFF ones) can be edited with
+//! the `ArchiveEditor` type, which deletes, replaces, and reorders entries and
+//! then writes out a new archive with its tables regenerated (optionally
+//! building a fresh symbol table from the members with a `SymbolExtractor`,
+//! like `ranlib`).  The `mri` module runs the MRI librarian scripts
+//! understood by `ar -M`.  To replace an archive on disk safely, write the
+//! new one with `write_atomically`.
+//!
+//! For reproducible builds, each of the builders can be given a
+//! `MetadataPolicy` that writes every entry with the same timestamp, owner,
+//! group, and mode (like `ar D`), and clamps timestamps to the
+//! `SOURCE_DATE_EPOCH` environment variable (see `source_date_epoch`).
+//!
+//! AIX *big archives*, which start with `<bigaf>` rather than `!<arch>` and
+//! link their entries together by offset, are read by the same `Archive` API
+//! (with `Variant::AIX`), and can be written with the `AixBuilder` type.
+//!
+//! Reading methods report a malformed archive with an `io::Error` that wraps
+//! an `ar::Error` describing the problem (recover it with `Error::from_io`),
+//! so that programs can tell the various failures apart.  Reading never
+//! allocates much more memory than the archive's data actually takes up, and
+//! `Archive` can be given limits on the sizes of its name table, entry
+//! identifiers, and symbol tables for reading untrusted input.
 //!
 //! # Example usage
 //!
@@ -69,14 +114,31 @@
 
 extern crate byteorder;
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+#[cfg(feature = "coff-import")]
+pub mod coff_import;
+pub mod deb;
+pub mod mri;
+#[cfg(feature = "object")]
+pub mod object;
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod test_common;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::cmp;
-use std::ffi::OsStr;
-use std::fs::{File, Metadata};
-use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Result, Seek,
-              SeekFrom, Write};
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Result,
+              Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -91,19 +153,46 @@ use std::os::windows::ffi::OsStrExt;
 
 const GLOBAL_HEADER_LEN: usize = 8;
 const GLOBAL_HEADER: &'static [u8; GLOBAL_HEADER_LEN] = b"!<arch>\n";
+const THIN_GLOBAL_HEADER: &[u8; GLOBAL_HEADER_LEN] = b"!<thin>\n";
+const AIX_BIG_GLOBAL_HEADER: &[u8; GLOBAL_HEADER_LEN] = b"<bigaf>\n";
+const AIX_BIG_FIXED_HEADER_LEN: usize = 128;
 
 const ENTRY_HEADER_LEN: usize = 60;
+const AIX_BIG_ENTRY_HEADER_LEN: usize = 112;
 
 const BSD_SYMBOL_LOOKUP_TABLE_ID: &[u8] = b"__.SYMDEF";
 const BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID: &[u8] = b"__.SYMDEF SORTED";
+const BSD_SYM64_LOOKUP_TABLE_ID: &[u8] = b"__.SYMDEF_64";
+const BSD_SORTED_SYM64_LOOKUP_TABLE_ID: &[u8] = b"__.SYMDEF_64 SORTED";
 
 const GNU_NAME_TABLE_ID: &[u8] = b"//";
 const GNU_SYMBOL_LOOKUP_TABLE_ID: &[u8] = b"/";
+const GNU_SYM64_LOOKUP_TABLE_ID: &[u8] = b"/SYM64/";
+
+// The mode that GNU ar gives every entry in deterministic mode.  Note that it
+// has no file type bits.
+const DETERMINISTIC_MODE: u32 = 0o644;
+
+// How many names write_atomically tries for its temporary file, and a
+// counter that distinguishes the names chosen within this process.
+const TEMP_FILE_ATTEMPTS: usize = 100;
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// How much of an archive to move at a time when inserting a symbol table
+// before its entries.
+const INSERT_BUFFER_LEN: usize = 0x10000;
+
+// The most items of a symbol table to reserve space for before reading them.
+const MAX_TABLE_CAPACITY: u64 = 0x10000;
 
 // ========================================================================= //
 
 /// Variants of the Unix archive format.
+///
+/// More variants may be added in future versions, so matches on this type
+/// outside of this crate need a wildcard arm.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Variant {
     /// Used by Debian package files; allows only short filenames.
     Common,
@@ -111,11 +200,432 @@ pub enum Variant {
     BSD,
     /// Used by GNU `ar` (and Windows); incompatible with common variant.
     GNU,
+    /// The "big" format used by AIX `ar`; incompatible with all of the above.
+    AIX,
+    /// Used by Microsoft's tools for Windows `.lib` files; an extension of
+    /// the GNU variant with a second, sorted symbol table.
+    COFF,
+}
+
+impl Variant {
+    /// Returns true for the variants that name entries the way GNU `ar`
+    /// does.
+    fn is_gnu_like(self) -> bool {
+        self == Variant::GNU || self == Variant::COFF
+    }
+}
+
+// ========================================================================= //
+
+/// A problem with the contents of an archive, found while parsing it.
+///
+/// Methods that read archives return `io::Error`s, so that failures of the
+/// underlying reader can be passed along as is; when the archive itself is
+/// malformed, the `io::Error` wraps one of these instead of a bare message,
+/// and `Error::from_io` recovers it.  All offsets are measured in bytes from
+/// the start of the archive.
+///
+/// More variants may be added in future versions, as with `Variant`.
+#[derive(Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The archive doesn't start with any of the global headers that this
+    /// crate recognizes.
+    InvalidGlobalHeader {
+        /// The bytes found in place of the global header.
+        bytes: Vec<u8>,
+    },
+    /// A numeric field of an entry header (or of the fixed header at the
+    /// start of an AIX big archive) couldn't be parsed.
+    InvalidNumber {
+        /// The name of the field, such as `"file size"`.
+        field: &'static str,
+        /// The raw contents of the field.
+        bytes: Vec<u8>,
+        /// The offset of the header containing the field.
+        offset: u64,
+    },
+    /// The byte after an entry with an odd amount of data wasn't a newline.
+    InvalidPadding {
+        /// The byte found in place of the newline.
+        byte: u8,
+        /// The offset of that byte.
+        offset: u64,
+    },
+    /// A BSD-variant entry header gave a length for its extended identifier
+    /// (after `#1/`) larger than the entry's total size.
+    InvalidBsdNameLength {
+        /// The length of the extended identifier.
+        length: u64,
+        /// The size of the entry, including the extended identifier.
+        size: u64,
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// A GNU-variant entry header referred to a name beyond the end of the
+    /// archive's name table.
+    InvalidGnuNameIndex {
+        /// The index into the name table given by the header.
+        index: u64,
+        /// The length of the name table, in bytes.
+        name_table_len: usize,
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// An AIX big archive member header didn't end with "`\n".
+    InvalidAixTerminator {
+        /// The offset of the member header.
+        offset: u64,
+    },
+    /// An AIX big archive member linked to a member at or before its own
+    /// offset.
+    AixLinksBackwards {
+        /// The offset of the member header.
+        offset: u64,
+        /// The offset of the next member, according to that header.
+        next: u64,
+    },
+    /// An AIX big archive member (or its data) started before the end of the
+    /// data already read, which a reader that can't seek can't go back to.
+    AixMemberOverlaps {
+        /// The offset that the member (or its data) starts at.
+        offset: u64,
+        /// The offset that the archive had been read up to.
+        position: u64,
+    },
+    /// A symbol table entry referred to an offset where no entry starts.
+    InvalidSymbolOffset {
+        /// The name of the symbol.
+        symbol: Vec<u8>,
+        /// The offset that the symbol table gave for the symbol's entry.
+        entry_offset: u64,
+    },
+    /// A symbol in the sorted symbol index of a COFF archive referred to a
+    /// member number that the index doesn't list.
+    InvalidSymbolMember {
+        /// The name of the symbol.
+        symbol: Vec<u8>,
+        /// The (1-based) member number given for the symbol.
+        member_number: u16,
+        /// The number of members listed in the index.
+        num_members: usize,
+    },
+    /// A BSD symbol table entry referred to a name beyond the end of the
+    /// table's string table.
+    InvalidSymbolNameOffset {
+        /// The offset of the name within the string table.
+        name_offset: u64,
+        /// The length of the string table, in bytes.
+        string_table_len: u64,
+        /// The offset of the symbol table's entry header.
+        offset: u64,
+    },
+    /// A GNU name table was larger than the limit set with
+    /// `Archive::set_max_name_table_len`.
+    NameTableTooLarge {
+        /// The size of the name table, in bytes.
+        size: u64,
+        /// The limit, in bytes.
+        limit: u64,
+        /// The offset of the name table's entry header.
+        offset: u64,
+    },
+    /// An entry identifier was longer than the limit set with
+    /// `Archive::set_max_identifier_len`.
+    IdentifierTooLong {
+        /// The length of the identifier, in bytes.
+        length: u64,
+        /// The limit, in bytes.
+        limit: u64,
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// A symbol table was larger than the limit set with
+    /// `Archive::set_max_symbol_table_len`.
+    SymbolTableTooLarge {
+        /// The size of the symbol table, in bytes.
+        size: u64,
+        /// The limit, in bytes.
+        limit: u64,
+        /// The offset of the symbol table's entry (or member) header.
+        offset: u64,
+    },
+    /// The archive ended in the middle of its global header (or the fixed
+    /// header at the start of an AIX big archive).
+    TruncatedGlobalHeader,
+    /// The archive ended in the middle of an entry header.
+    TruncatedHeader {
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// The archive ended in the middle of the extended identifier of a
+    /// BSD-variant entry header.
+    TruncatedIdentifier {
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// The archive ended in the middle of an entry's data.
+    TruncatedEntry {
+        /// The offset of the entry header.
+        offset: u64,
+    },
+    /// An AIX big archive ended before the offset of a member that another
+    /// member or the fixed header linked to.
+    TruncatedAixMember {
+        /// The offset of the missing member header.
+        offset: u64,
+    },
+    /// A symbol table ended before all of the symbols (or, in a COFF
+    /// archive's sorted symbol index, the members) that it counted.
+    TruncatedSymbolTable {
+        /// The offset of the symbol table's entry (or member) header.
+        offset: u64,
+    },
+    /// A short import object (see the `coff_import` module) ended before the
+    /// amount of data that its header gave.
+    TruncatedImportObject {
+        /// The size of the data according to the header, in bytes.
+        size: u32,
+        /// The amount of data actually present, in bytes.
+        actual: u64,
+    },
+    /// An archive given to `Builder::from_existing` didn't end where its
+    /// last entry does, so it is either truncated or has trailing data.
+    UnexpectedArchiveLength {
+        /// The length implied by the archive's entries, in bytes.
+        expected: u64,
+        /// The actual length of the archive, in bytes.
+        actual: u64,
+    },
+}
+
+impl Error {
+    /// Returns the kind of `io::Error` that this error converts to:
+    /// `ErrorKind::UnexpectedEof` for the truncation errors, and
+    /// `ErrorKind::InvalidData` for all others.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::TruncatedGlobalHeader |
+            Error::TruncatedHeader { .. } |
+            Error::TruncatedIdentifier { .. } |
+            Error::TruncatedEntry { .. } |
+            Error::TruncatedAixMember { .. } |
+            Error::TruncatedSymbolTable { .. } |
+            Error::TruncatedImportObject { .. } => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::InvalidData,
+        }
+    }
+
+    /// Returns the `Error` wrapped by an `io::Error` returned from this
+    /// crate, or `None` if the `io::Error` came from somewhere else (such as
+    /// the underlying reader).
+    pub fn from_io(error: &io::Error) -> Option<&Error> {
+        error.get_ref().and_then(|inner| inner.downcast_ref::<Error>())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidGlobalHeader { .. } => {
+                write!(
+                    formatter,
+                    "Not an archive file (invalid global header)"
+                )
+            }
+            Error::InvalidNumber { field, ref bytes, .. } => {
+                write!(
+                    formatter,
+                    "Invalid {} field in entry header ({:?})",
+                    field,
+                    String::from_utf8_lossy(bytes)
+                )
+            }
+            Error::InvalidPadding { .. } => {
+                write!(formatter, "Invalid padding byte")
+            }
+            Error::InvalidBsdNameLength { length, size, .. } => {
+                write!(
+                    formatter,
+                    "Entry size ({}) smaller than extended entry identifier \
+                     length ({})",
+                    size,
+                    length
+                )
+            }
+            Error::InvalidGnuNameIndex { index, name_table_len, .. } => {
+                write!(
+                    formatter,
+                    "GNU filename index ({}) is past the end of the name \
+                     table ({} bytes)",
+                    index,
+                    name_table_len
+                )
+            }
+            Error::InvalidAixTerminator { .. } => {
+                write!(
+                    formatter,
+                    "Invalid terminator in AIX big archive member header"
+                )
+            }
+            Error::AixLinksBackwards { offset, next } => {
+                write!(
+                    formatter,
+                    "AIX big archive member at offset {} links backwards to \
+                     offset {}",
+                    offset,
+                    next
+                )
+            }
+            Error::AixMemberOverlaps { offset, position } => {
+                write!(
+                    formatter,
+                    "Cannot move backwards from offset {} to offset {} \
+                     without seeking",
+                    position,
+                    offset
+                )
+            }
+            Error::InvalidSymbolOffset { ref symbol, entry_offset } => {
+                write!(
+                    formatter,
+                    "Symbol {:?} refers to offset {}, which is not the start \
+                     of an archive entry",
+                    String::from_utf8_lossy(symbol),
+                    entry_offset
+                )
+            }
+            Error::InvalidSymbolMember {
+                ref symbol,
+                member_number,
+                num_members,
+            } => {
+                write!(
+                    formatter,
+                    "Symbol {:?} refers to member number {}, but there are \
+                     only {} members",
+                    String::from_utf8_lossy(symbol),
+                    member_number,
+                    num_members
+                )
+            }
+            Error::InvalidSymbolNameOffset {
+                name_offset,
+                string_table_len,
+                ..
+            } => {
+                write!(
+                    formatter,
+                    "Symbol table refers to a name at offset {}, past the end \
+                     of its string table ({} bytes)",
+                    name_offset,
+                    string_table_len
+                )
+            }
+            Error::NameTableTooLarge { size, limit, .. } => {
+                write!(
+                    formatter,
+                    "GNU name table is too large ({} bytes, but the limit is \
+                     {})",
+                    size,
+                    limit
+                )
+            }
+            Error::IdentifierTooLong { length, limit, .. } => {
+                write!(
+                    formatter,
+                    "Entry identifier is too long ({} bytes, but the limit is \
+                     {})",
+                    length,
+                    limit
+                )
+            }
+            Error::SymbolTableTooLarge { size, limit, .. } => {
+                write!(
+                    formatter,
+                    "Symbol table is too large ({} bytes, but the limit is \
+                     {})",
+                    size,
+                    limit
+                )
+            }
+            Error::TruncatedGlobalHeader => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of archive global header"
+                )
+            }
+            Error::TruncatedHeader { .. } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of archive entry header"
+                )
+            }
+            Error::TruncatedIdentifier { .. } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of extended entry \
+                     identifier"
+                )
+            }
+            Error::TruncatedEntry { .. } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of archive entry"
+                )
+            }
+            Error::TruncatedAixMember { .. } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF before the next AIX big archive member"
+                )
+            }
+            Error::TruncatedSymbolTable { .. } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of symbol table"
+                )
+            }
+            Error::TruncatedImportObject { size, actual } => {
+                write!(
+                    formatter,
+                    "Unexpected EOF in the middle of import object data ({} \
+                     of {} bytes)",
+                    actual,
+                    size
+                )
+            }
+            Error::UnexpectedArchiveLength { expected, actual } => {
+                write!(
+                    formatter,
+                    "Archive is truncated or has trailing data (expected {} \
+                     bytes, found {})",
+                    expected,
+                    actual
+                )
+            }
+        }
+    }
+}
+
+// The debug form is the quoted message, which is what an unwrapped
+// `io::Error` showed when this crate wrapped bare strings instead.
+impl fmt::Debug for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), formatter)
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error { io::Error::new(error.kind(), error) }
 }
 
 // ========================================================================= //
 
 /// Representation of an archive entry header.
+#[derive(Clone)]
 pub struct Header {
     identifier: Vec<u8>,
     mtime: u64,
@@ -196,10 +706,11 @@ impl Header {
     /// Sets the length of the file, in bytes.
     pub fn set_size(&mut self, size: u64) { self.size = size; }
 
-    /// Parses and returns the next header and its length.  Returns `Ok(None)`
-    /// if we are at EOF.
-    fn read<R>(reader: &mut R, variant: &mut Variant, name_table: &mut Vec<u8>)
-        -> Result<Option<(Header, u64)>>
+    /// Parses and returns the next header (which starts at `header_start`)
+    /// and its length.  Returns `Ok(None)` if we are at EOF.
+    fn read<R>(reader: &mut R, header_start: u64, variant: &mut Variant,
+               name_table: &mut Vec<u8>, limits: ReadLimits)
+               -> Result<Option<(Header, u64)>>
     where
         R: Read,
     {
@@ -208,79 +719,113 @@ impl Header {
         if bytes_read == 0 {
             return Ok(None);
         } else if bytes_read < buffer.len() {
-            let msg = "Unexpected EOF in the middle of archive entry header";
-            return Err(Error::new(ErrorKind::UnexpectedEof, msg));
+            let error = Error::TruncatedHeader {
+                offset: header_start,
+            };
+            return Err(error.into());
         }
+        let number = |field, range: Range<usize>, radix| {
+            parse_number(field, &buffer[range], radix, header_start)
+        };
         let mut identifier = buffer[0..16].to_vec();
         while identifier.last() == Some(&b' ') {
             identifier.pop();
         }
-        let mut size = try!(parse_number("file size", &buffer[48..58], 10));
+        let mut size = try!(number("file size", 48..58, 10));
         let mut header_len = ENTRY_HEADER_LEN as u64;
         if *variant != Variant::BSD && identifier.starts_with(b"/") {
-            *variant = Variant::GNU;
-            if identifier == GNU_SYMBOL_LOOKUP_TABLE_ID {
+            if !variant.is_gnu_like() {
+                *variant = Variant::GNU;
+            }
+            if identifier == GNU_SYMBOL_LOOKUP_TABLE_ID ||
+                identifier == GNU_SYM64_LOOKUP_TABLE_ID
+            {
                 try!(
                     io::copy(&mut reader.by_ref().take(size), &mut io::sink())
                 );
                 return Ok(Some((Header::new(identifier, size), header_len)));
             } else if identifier == GNU_NAME_TABLE_ID {
-                *name_table = vec![0; size as usize];
-                try!(reader.read_exact(name_table as &mut [u8]));
+                if size > limits.name_table {
+                    let error = Error::NameTableTooLarge {
+                        size: size,
+                        limit: limits.name_table,
+                        offset: header_start,
+                    };
+                    return Err(error.into());
+                }
+                name_table.clear();
+                try!(read_exactly(reader, size, name_table, header_start));
                 return Ok(Some((Header::new(identifier, size), header_len)));
             }
-            let start =
-                try!(
-                    parse_number("GNU filename index", &buffer[1..16], 10)
-                ) as usize;
-            let end =
-                match name_table[start..].iter().position(|&ch| ch == b'/') {
-                    Some(len) => start + len,
-                    None => name_table.len(),
+            let index = try!(number("GNU filename index", 1..16, 10));
+            if index >= name_table.len() as u64 {
+                let error = Error::InvalidGnuNameIndex {
+                    index: index,
+                    name_table_len: name_table.len(),
+                    offset: header_start,
                 };
-            identifier = name_table[start..end].to_vec();
+                return Err(error.into());
+            }
+            // Names in the table are terminated by "/\n", or by a null byte
+            // in a COFF archive.  (We can't just look for a slash, because the
+            // names in a thin archive are paths.)
+            let names = &name_table[index as usize..];
+            let len = (0..names.len())
+                .find(|&i| names[i] == 0 || names[i..].starts_with(b"/\n"))
+                .unwrap_or(names.len());
+            identifier = names[..len].to_vec();
         } else if *variant != Variant::BSD && identifier.ends_with(b"/") {
-            *variant = Variant::GNU;
+            if !variant.is_gnu_like() {
+                *variant = Variant::GNU;
+            }
             identifier.pop();
         }
-        let mtime = try!(parse_number("timestamp", &buffer[16..28], 10));
-        let uid = try!(parse_number("owner ID", &buffer[28..34], 10)) as u32;
-        let gid = try!(parse_number("group ID", &buffer[34..40], 10)) as u32;
-        let mode = try!(parse_number("file mode", &buffer[40..48], 8)) as u32;
-        if *variant != Variant::GNU && identifier.starts_with(b"#1/") {
+        let mtime = try!(number("timestamp", 16..28, 10));
+        let uid = try!(number("owner ID", 28..34, 10)) as u32;
+        let gid = try!(number("group ID", 34..40, 10)) as u32;
+        let mode = try!(number("file mode", 40..48, 8)) as u32;
+        if !variant.is_gnu_like() && identifier.starts_with(b"#1/") {
             *variant = Variant::BSD;
-            let padded_length =
-                try!(parse_number("BSD filename length", &buffer[3..16], 10));
+            let padded_length = try!(number("BSD filename length", 3..16, 10));
             if size < padded_length {
-                let msg = format!(
-                    "Entry size ({}) smaller than extended \
-                                   entry identifier length ({})",
-                    size,
-                    padded_length
-                );
-                return Err(Error::new(ErrorKind::InvalidData, msg));
+                let error = Error::InvalidBsdNameLength {
+                    length: padded_length,
+                    size: size,
+                    offset: header_start,
+                };
+                return Err(error.into());
             }
+            try!(limits.check_identifier_len(padded_length, header_start));
             size -= padded_length;
             header_len += padded_length;
-            let mut id_buffer = vec![0; padded_length as usize];
-            let bytes_read = try!(reader.read(&mut id_buffer));
-            if bytes_read < id_buffer.len() {
-                let msg = "Unexpected EOF in the middle of extended entry \
-                           identifier";
-                return Err(Error::new(ErrorKind::UnexpectedEof, msg));
+            let mut id_buffer = Vec::new();
+            let bytes_read = try!(
+                reader
+                    .by_ref()
+                    .take(padded_length)
+                    .read_to_end(&mut id_buffer)
+            );
+            if (bytes_read as u64) < padded_length {
+                let error = Error::TruncatedIdentifier {
+                    offset: header_start,
+                };
+                return Err(error.into());
             }
             while id_buffer.last() == Some(&0) {
                 id_buffer.pop();
             }
             identifier = id_buffer;
-            if identifier == BSD_SYMBOL_LOOKUP_TABLE_ID ||
-                identifier == BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID
-            {
+            if is_bsd_symbol_lookup_table_id(&identifier) {
                 try!(
                     io::copy(&mut reader.by_ref().take(size), &mut io::sink())
                 );
                 return Ok(Some((Header::new(identifier, size), header_len)));
             }
+        } else {
+            try!(limits.check_identifier_len(
+                identifier.len() as u64,
+                header_start,
+            ));
         }
         Ok(Some((
             Header {
@@ -297,20 +842,7 @@ impl Header {
 
     fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
         if self.identifier.len() > 16 || self.identifier.contains(&b' ') {
-            let padding_length = (4 - self.identifier.len() % 4) % 4;
-            let padded_length = self.identifier.len() + padding_length;
-            try!(write!(
-                writer,
-                "#1/{:<13}{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
-                padded_length,
-                self.mtime,
-                self.uid,
-                self.gid,
-                self.mode,
-                self.size + padded_length as u64
-            ));
-            try!(writer.write_all(&self.identifier));
-            try!(writer.write_all(&vec![0; padding_length]));
+            try!(self.write_bsd_extended(writer));
         } else {
             try!(writer.write_all(&self.identifier));
             try!(writer.write_all(&vec![b' '; 16 - self.identifier.len()]));
@@ -326,20 +858,445 @@ impl Header {
         }
         Ok(())
     }
+
+    /// Writes this header using the BSD `#1/` extension, regardless of the
+    /// identifier's length.
+    fn write_bsd_extended<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let padding_length = (4 - self.identifier.len() % 4) % 4;
+        let padded_length = self.identifier.len() + padding_length;
+        try!(write!(
+            writer,
+            "#1/{:<13}{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
+            padded_length,
+            self.mtime,
+            self.uid,
+            self.gid,
+            self.mode,
+            self.size + padded_length as u64
+        ));
+        try!(writer.write_all(&self.identifier));
+        try!(writer.write_all(&vec![0; padding_length]));
+        Ok(())
+    }
+
+    /// Returns an error if this header's identifier can't be written as is
+    /// in the given variant: that is, if `write()` would have to fall back to
+    /// the BSD extension for `Variant::Common`, or if `write_gnu()` would need
+    /// a name table for `Variant::GNU`.
+    fn check_identifier(&self, variant: Variant) -> Result<()> {
+        let identifier = &self.identifier;
+        let msg = match variant {
+            Variant::Common => {
+                if identifier.len() > 16 || identifier.contains(&b' ') {
+                    format!(
+                        "Identifier {:?} can't be written in a common-variant \
+                         header (it would need the BSD #1/ extension)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else if identifier.starts_with(b"#1/") {
+                    format!(
+                        "Identifier {:?} can't be written in a common-variant \
+                         header (it would be mistaken for the BSD #1/ \
+                         extension)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else if identifier.starts_with(b"/") ||
+                           identifier.ends_with(b"/")
+                {
+                    format!(
+                        "Identifier {:?} can't be written in a common-variant \
+                         header (it would be mistaken for a GNU name)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else {
+                    return Ok(());
+                }
+            }
+            Variant::GNU => {
+                if identifier.len() > 15 {
+                    format!(
+                        "Identifier {:?} is too long for a GNU archive \
+                         without a name table (use GnuBuilder instead)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else if identifier.starts_with(b"/") {
+                    format!(
+                        "Identifier {:?} can't be written in a GNU archive \
+                         (it would be mistaken for a special entry or a name \
+                         table reference)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else if identifier.contains(&b'/') {
+                    format!(
+                        "Identifier {:?} can't be written in a GNU archive \
+                         (GNU names end at the first slash)",
+                        String::from_utf8_lossy(identifier)
+                    )
+                } else {
+                    return Ok(());
+                }
+            }
+            _ => return Ok(()),
+        };
+        Err(io::Error::new(ErrorKind::InvalidInput, msg))
+    }
+
+    /// Returns the number of bytes that `write()` (if `variant` is
+    /// `Variant::Common` or `Variant::BSD`) or `write_gnu()` (otherwise) will
+    /// write for this header.
+    fn written_len(&self, variant: Variant) -> u64 {
+        let mut length = ENTRY_HEADER_LEN as u64;
+        if variant != Variant::GNU &&
+            (self.identifier.len() > 16 || self.identifier.contains(&b' '))
+        {
+            let padding_length = (4 - self.identifier.len() % 4) % 4;
+            length += (self.identifier.len() + padding_length) as u64;
+        }
+        length
+    }
+
+    /// Writes this header in the GNU format.  The identifier is written as a
+    /// reference into the name table if it appears in `names` (which must be
+    /// the case for identifiers that `GnuNameTable::needs_entry` accepts).
+    fn write_gnu<W: Write>(&self, writer: &mut W,
+                           names: &HashMap<Vec<u8>, usize>)
+                           -> Result<()> {
+        if let Some(&offset) = names.get(&self.identifier) {
+            try!(write!(writer, "/{:<15}", offset));
+        } else {
+            try!(writer.write_all(&self.identifier));
+            try!(writer.write_all(b"/"));
+            try!(writer.write_all(&vec![b' '; 15 - self.identifier.len()]));
+        }
+        try!(write!(
+            writer,
+            "{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
+            self.mtime,
+            self.uid,
+            self.gid,
+            self.mode,
+            self.size
+        ));
+        Ok(())
+    }
+
+    /// Parses an AIX big archive member header (which starts at
+    /// `header_start`), returning the header, its length (including the
+    /// member name and the trailing "`\n"), and the offset of the next
+    /// member's header.
+    fn read_aix<R: Read>(reader: &mut R, header_start: u64,
+                         limits: ReadLimits)
+                         -> Result<(Header, u64, u64)> {
+        let mut buffer = [0; AIX_BIG_ENTRY_HEADER_LEN];
+        try!(read_exact_or(reader, &mut buffer, || {
+            Error::TruncatedHeader {
+                offset: header_start,
+            }
+        }));
+        let number = |field, range: Range<usize>, radix| {
+            parse_number(field, &buffer[range], radix, header_start)
+        };
+        let size = try!(number("file size", 0..20, 10));
+        let next = try!(number("next member offset", 20..40, 10));
+        let mtime = try!(number("timestamp", 60..72, 10));
+        let uid = try!(number("owner ID", 72..84, 10)) as u32;
+        let gid = try!(number("group ID", 84..96, 10)) as u32;
+        let mode = try!(number("file mode", 96..108, 8)) as u32;
+        let name_length = try!(number("name length", 108..112, 10));
+        try!(limits.check_identifier_len(name_length, header_start));
+        let name_length = name_length as usize;
+        // The name is padded to an even length, and followed by "`\n".
+        let mut name_buffer = vec![0; name_length + name_length % 2 + 2];
+        try!(read_exact_or(reader, &mut name_buffer, || {
+            Error::TruncatedIdentifier {
+                offset: header_start,
+            }
+        }));
+        if !name_buffer.ends_with(b"`\n") {
+            let error = Error::InvalidAixTerminator {
+                offset: header_start,
+            };
+            return Err(error.into());
+        }
+        name_buffer.truncate(name_length);
+        let header = Header {
+            identifier: name_buffer,
+            mtime: mtime,
+            uid: uid,
+            gid: gid,
+            mode: mode,
+            size: size,
+        };
+        Ok((header, aix_header_len(name_length), next))
+    }
+
+    /// Writes this header in the AIX big archive format, linked to the
+    /// headers at the given offsets (or to nothing, for an offset of zero).
+    fn write_aix<W: Write>(&self, writer: &mut W, next: u64, prev: u64)
+                           -> Result<()> {
+        if self.identifier.len() > 9999 {
+            let msg = format!(
+                "Identifier is too long for an AIX big archive ({} bytes)",
+                self.identifier.len()
+            );
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        try!(write!(
+            writer,
+            "{:<20}{:<20}{:<20}{:<12}{:<12}{:<12}{:<12o}{:<4}",
+            self.size,
+            next,
+            prev,
+            self.mtime,
+            self.uid,
+            self.gid,
+            self.mode,
+            self.identifier.len()
+        ));
+        try!(writer.write_all(&self.identifier));
+        if self.identifier.len() % 2 != 0 {
+            try!(writer.write_all(&[0]));
+        }
+        try!(writer.write_all(b"`\n"));
+        Ok(())
+    }
+}
+
+/// Returns the length of an AIX big archive member header (including the
+/// padded member name and the trailing "`\n") for a name of the given length.
+fn aix_header_len(name_length: usize) -> u64 {
+    (AIX_BIG_ENTRY_HEADER_LEN + name_length + name_length % 2 + 2) as u64
 }
 
-fn parse_number(field_name: &str, bytes: &[u8], radix: u32) -> Result<u64> {
+fn parse_number(field: &'static str, bytes: &[u8], radix: u32,
+                header_start: u64)
+                -> Result<u64> {
     if let Ok(string) = str::from_utf8(bytes) {
         if let Ok(value) = u64::from_str_radix(string.trim_right(), radix) {
             return Ok(value);
         }
     }
-    let msg = format!(
-        "Invalid {} field in entry header ({:?})",
-        field_name,
-        String::from_utf8_lossy(bytes)
+    let error = Error::InvalidNumber {
+        field: field,
+        bytes: bytes.to_vec(),
+        offset: header_start,
+    };
+    Err(error.into())
+}
+
+fn is_bsd_symbol_lookup_table_id(identifier: &[u8]) -> bool {
+    identifier == BSD_SYMBOL_LOOKUP_TABLE_ID ||
+        identifier == BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID ||
+        identifier == BSD_SYM64_LOOKUP_TABLE_ID ||
+        identifier == BSD_SORTED_SYM64_LOOKUP_TABLE_ID
+}
+
+/// Reads a little-endian integer from a BSD symbol table, which is 64 bits
+/// wide in the `__.SYMDEF_64` variants and 32 bits wide otherwise.
+fn read_bsd_word<R: Read>(reader: &mut R, is_64_bit: bool) -> Result<u64> {
+    if is_64_bit {
+        reader.read_u64::<LittleEndian>()
+    } else {
+        reader.read_u32::<LittleEndian>().map(|word| word as u64)
+    }
+}
+
+/// Reads a symbol table laid out like a GNU `/SYM64/` table (of `table_len`
+/// bytes): a 64-bit big-endian symbol count, followed by that many 64-bit
+/// big-endian entry offsets, followed by the symbol names (each terminated
+/// by a null byte).
+fn read_sym64_table<R: BufRead>(reader: &mut R, table_len: u64)
+                                -> Result<SymbolTable> {
+    let num_symbols = try!(reader.read_u64::<BigEndian>());
+    let capacity = table_capacity(num_symbols, 8, table_len);
+    let mut symbol_offsets = Vec::<u64>::with_capacity(capacity);
+    for _ in 0..num_symbols {
+        let offset = try!(reader.read_u64::<BigEndian>());
+        symbol_offsets.push(offset);
+    }
+    let mut symbol_table = Vec::with_capacity(capacity);
+    for offset in symbol_offsets.into_iter() {
+        let mut buffer = Vec::<u8>::new();
+        try!(reader.read_until(0, &mut buffer));
+        if buffer.last() == Some(&0) {
+            buffer.pop();
+        }
+        buffer.shrink_to_fit();
+        symbol_table.push((buffer, offset));
+    }
+    Ok(symbol_table)
+}
+
+/// Reads a GNU `/` symbol table (of `table_len` bytes): a 32-bit big-endian
+/// symbol count, followed by that many 32-bit big-endian entry offsets,
+/// followed by the symbol names (each terminated by a null byte).
+fn read_gnu_symbol_table<R: BufRead>(reader: &mut R, table_len: u64)
+                                     -> Result<SymbolTable> {
+    let num_symbols = try!(reader.read_u32::<BigEndian>()) as u64;
+    let capacity = table_capacity(num_symbols, 4, table_len);
+    let mut symbol_offsets = Vec::<u32>::with_capacity(capacity);
+    for _ in 0..num_symbols {
+        let offset = try!(reader.read_u32::<BigEndian>());
+        symbol_offsets.push(offset);
+    }
+    let mut symbol_table = Vec::with_capacity(capacity);
+    for offset in symbol_offsets.into_iter() {
+        let mut buffer = Vec::<u8>::new();
+        try!(reader.read_until(0, &mut buffer));
+        if buffer.last() == Some(&0) {
+            buffer.pop();
+        }
+        buffer.shrink_to_fit();
+        symbol_table.push((buffer, offset as u64));
+    }
+    Ok(symbol_table)
+}
+
+/// Reads a BSD `__.SYMDEF` symbol table (of `table_len` bytes, in an entry
+/// whose header starts at `header_start`): the size of an array of ranlib
+/// structs (each a string table offset and an entry offset), the array, the
+/// size of the string table, and the string table.
+fn read_bsd_symbol_table<R: Read>(reader: &mut R, table_len: u64,
+                                  is_64_bit: bool, header_start: u64)
+                                  -> Result<SymbolTable> {
+    let ranlib_size = if is_64_bit { 16 } else { 8 };
+    let num_symbols = try!(read_bsd_word(reader, is_64_bit)) / ranlib_size;
+    let capacity = table_capacity(num_symbols, ranlib_size, table_len);
+    let mut symbol_offsets = Vec::<(u64, u64)>::with_capacity(capacity);
+    for _ in 0..num_symbols {
+        let str_offset = try!(read_bsd_word(reader, is_64_bit));
+        let file_offset = try!(read_bsd_word(reader, is_64_bit));
+        symbol_offsets.push((str_offset, file_offset));
+    }
+    let str_table_len = try!(read_bsd_word(reader, is_64_bit));
+    let mut str_table_data = Vec::new();
+    try!(read_exactly(reader, str_table_len, &mut str_table_data,
+                      header_start));
+    let mut symbol_table = Vec::with_capacity(capacity);
+    for (str_start, file_offset) in symbol_offsets.into_iter() {
+        if str_start > str_table_len {
+            let error = Error::InvalidSymbolNameOffset {
+                name_offset: str_start,
+                string_table_len: str_table_len,
+                offset: header_start,
+            };
+            return Err(error.into());
+        }
+        let str_start = str_start as usize;
+        let mut str_end = str_start;
+        while str_end < str_table_data.len() &&
+            str_table_data[str_end] != 0u8
+        {
+            str_end += 1;
+        }
+        let string = &str_table_data[str_start..str_end];
+        symbol_table.push((string.to_vec(), file_offset));
+    }
+    Ok(symbol_table)
+}
+
+/// Reads the sorted symbol index of a COFF archive (the second linker
+/// member, of `table_len` bytes), returning each symbol with the offset of
+/// the entry that defines it.
+fn read_coff_symbol_index<R: BufRead>(reader: &mut R, table_len: u64)
+                                      -> Result<SymbolTable> {
+    // The second linker member lists the offset of every entry, and then
+    // refers to entries by their (1-based) position in that list.
+    let num_members = try!(reader.read_u32::<LittleEndian>()) as usize;
+    let mut member_offsets = Vec::<u64>::with_capacity(
+        table_capacity(num_members as u64, 4, table_len),
     );
-    Err(Error::new(ErrorKind::InvalidData, msg))
+    for _ in 0..num_members {
+        let offset = try!(reader.read_u32::<LittleEndian>());
+        member_offsets.push(offset as u64);
+    }
+    let num_symbols = try!(reader.read_u32::<LittleEndian>()) as u64;
+    let capacity = table_capacity(num_symbols, 2, table_len);
+    let mut member_numbers = Vec::<u16>::with_capacity(capacity);
+    for _ in 0..num_symbols {
+        member_numbers.push(try!(reader.read_u16::<LittleEndian>()));
+    }
+    let mut symbols = Vec::with_capacity(capacity);
+    for member_number in member_numbers.into_iter() {
+        let mut buffer = Vec::<u8>::new();
+        try!(reader.read_until(0, &mut buffer));
+        if buffer.last() == Some(&0) {
+            buffer.pop();
+        }
+        buffer.shrink_to_fit();
+        let position = (member_number as usize).wrapping_sub(1);
+        match member_offsets.get(position) {
+            Some(&offset) => symbols.push((buffer, offset)),
+            None => {
+                let error = Error::InvalidSymbolMember {
+                    symbol: buffer,
+                    member_number: member_number,
+                    num_members: num_members,
+                };
+                return Err(error.into());
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// Reports an EOF in the middle of a symbol table (whose entry header starts
+/// at `header_start`) as `Error::TruncatedSymbolTable`, and passes any other
+/// error along as is.
+fn truncated_table(error: io::Error, header_start: u64) -> io::Error {
+    if error.kind() == ErrorKind::UnexpectedEof &&
+        Error::from_io(&error).is_none()
+    {
+        Error::TruncatedSymbolTable {
+            offset: header_start,
+        }.into()
+    } else {
+        error
+    }
+}
+
+/// Returns how many items of `item_len` bytes each to reserve space for when
+/// reading `count` of them from a table of `table_len` bytes.  Both numbers
+/// come from the archive, so neither can be trusted to match the data that's
+/// actually there; beyond a point, we let the vector grow as the items are
+/// read, and reading them fails at the end of the data if it's too short.
+fn table_capacity(count: u64, item_len: u64, table_len: u64) -> usize {
+    cmp::min(cmp::min(count, table_len / item_len), MAX_TABLE_CAPACITY)
+        as usize
+}
+
+/// Fills `buffer` from the reader, reporting an EOF as the error returned by
+/// `eof` (rather than as an untyped `io::Error`).
+fn read_exact_or<R, F>(reader: &mut R, buffer: &mut [u8], eof: F)
+                       -> Result<()>
+where
+    R: Read,
+    F: FnOnce() -> Error,
+{
+    match reader.read_exact(buffer) {
+        Err(ref error) if error.kind() == ErrorKind::UnexpectedEof => {
+            Err(eof().into())
+        }
+        result => result,
+    }
+}
+
+/// Reads exactly `len` bytes into `buffer`, which (unlike with
+/// `Read::read_exact`) only grows as the data actually arrives, so that a
+/// bogus length in an entry header can't make us allocate a huge buffer up
+/// front.  Reports an EOF as a truncation of the entry whose header starts at
+/// `header_start`.
+fn read_exactly<R: Read>(reader: &mut R, len: u64, buffer: &mut Vec<u8>,
+                         header_start: u64)
+                         -> Result<()> {
+    let bytes_read = try!(reader.by_ref().take(len).read_to_end(buffer));
+    if (bytes_read as u64) < len {
+        let error = Error::TruncatedEntry {
+            offset: header_start,
+        };
+        return Err(error.into());
+    }
+    Ok(())
 }
 
 // ========================================================================= //
@@ -352,6 +1309,111 @@ struct HeaderAndLocation {
 
 // ========================================================================= //
 
+/// The limits that an `Archive` places on the sizes of the tables and
+/// identifiers that it reads (see `Archive::set_max_name_table_len` and
+/// friends).
+#[derive(Clone, Copy, Debug)]
+struct ReadLimits {
+    name_table: u64,
+    identifier: u64,
+    symbol_table: u64,
+}
+
+impl ReadLimits {
+    fn check_identifier_len(&self, length: u64, header_start: u64)
+                            -> Result<()> {
+        if length > self.identifier {
+            let error = Error::IdentifierTooLong {
+                length: length,
+                limit: self.identifier,
+                offset: header_start,
+            };
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    fn check_symbol_table_len(&self, size: u64, header_start: u64)
+                              -> Result<()> {
+        if size > self.symbol_table {
+            let error = Error::SymbolTableTooLarge {
+                size: size,
+                limit: self.symbol_table,
+                offset: header_start,
+            };
+            return Err(error.into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for ReadLimits {
+    fn default() -> ReadLimits {
+        ReadLimits {
+            name_table: u64::MAX,
+            identifier: u64::MAX,
+            symbol_table: u64::MAX,
+        }
+    }
+}
+
+// ========================================================================= //
+
+/// The fixed-length header at the start of an AIX big archive, which records
+/// the offsets of the archive's member table, global symbol tables (one for
+/// 32-bit objects and one for 64-bit objects), and first and last members.
+/// Each offset is zero if the archive has no such thing.
+#[derive(Clone, Copy, Debug, Default)]
+struct AixFileHeader {
+    member_table: u64,
+    symbol_table: u64,
+    symbol_table_64: u64,
+    first_member: u64,
+    last_member: u64,
+}
+
+impl AixFileHeader {
+    /// Parses the rest of the header, after the `<bigaf>\n` magic string.
+    fn read<R: Read>(reader: &mut R) -> Result<AixFileHeader> {
+        let mut buffer = [0; AIX_BIG_FIXED_HEADER_LEN - GLOBAL_HEADER_LEN];
+        try!(read_exact_or(reader, &mut buffer, || {
+            Error::TruncatedGlobalHeader
+        }));
+        // The fixed header starts with the magic string, at offset zero.
+        let number = |field, range: Range<usize>| {
+            parse_number(field, &buffer[range], 10, 0)
+        };
+        Ok(AixFileHeader {
+            member_table: try!(number("member table offset", 0..20)),
+            symbol_table: try!(number("symbol table offset", 20..40)),
+            symbol_table_64: try!(
+                number("64-bit symbol table offset", 40..60)
+            ),
+            first_member: try!(number("first member offset", 60..80)),
+            last_member: try!(number("last member offset", 80..100)),
+        })
+    }
+
+    /// Writes the complete header, including the magic string.  The archive
+    /// is always written with an empty free list.
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        try!(writer.write_all(AIX_BIG_GLOBAL_HEADER));
+        try!(write!(
+            writer,
+            "{:<20}{:<20}{:<20}{:<20}{:<20}{:<20}",
+            self.member_table,
+            self.symbol_table,
+            self.symbol_table_64,
+            self.first_member,
+            self.last_member,
+            0
+        ));
+        Ok(())
+    }
+}
+
+// ========================================================================= //
+
 /// A structure for reading archives.
 pub struct Archive<R: Read> {
     reader: R,
@@ -362,8 +1424,28 @@ pub struct Archive<R: Read> {
     next_entry_index: usize,
     symbol_table_header: Option<HeaderAndLocation>,
     symbol_table: Option<Vec<(Vec<u8>, u64)>>,
+    // The second linker member of a COFF archive.
+    coff_index_header: Option<HeaderAndLocation>,
+    // The symbols in the second linker member of a COFF archive (which are
+    // sorted by name), each paired with the index of its defining entry.
+    sorted_symbols: Option<Vec<(Vec<u8>, usize)>>,
+    // The index of the defining entry for each symbol in symbol_table.
+    symbol_entry_indices: Option<Vec<usize>>,
+    // Maps each symbol name to the index of its defining entry.
+    symbol_lookup: Option<HashMap<Vec<u8>, usize>>,
+    thin: bool, // True if this is a GNU thin archive.
+    // The directory that entries of a thin archive are relative to.
+    thin_base_dir: Option<PathBuf>,
+    // The fixed-length header, if this is an AIX big archive.
+    aix_header: Option<AixFileHeader>,
+    // The current offset of the reader.  This is only tracked for AIX big
+    // archives, whose entries aren't necessarily stored back to back.
+    position: u64,
+    limits: ReadLimits,
     started: bool, // True if we've read past the global header.
-    padding: bool, // True if there's a padding byte before the next entry.
+    // If there's a padding byte before the next entry, the offset of the
+    // header of the entry that it pads, and the offset of the byte itself.
+    padding: Option<(u64, u64)>,
     scanned: bool, // True if entry_headers is complete.
     error: bool, // True if we have encountered an error.
 }
@@ -381,8 +1463,17 @@ impl<R: Read> Archive<R> {
             next_entry_index: 0,
             symbol_table_header: None,
             symbol_table: None,
+            coff_index_header: None,
+            sorted_symbols: None,
+            symbol_entry_indices: None,
+            symbol_lookup: None,
+            thin: false,
+            thin_base_dir: None,
+            aix_header: None,
+            position: 0,
+            limits: ReadLimits::default(),
             started: false,
-            padding: false,
+            padding: None,
             scanned: false,
             error: false,
         }
@@ -396,21 +1487,106 @@ impl<R: Read> Archive<R> {
     /// will always return `Variant::Common`.
     pub fn variant(&self) -> Variant { self.variant }
 
+    /// Returns true if this is a GNU thin archive, whose entries' data is
+    /// stored in separate files rather than in the archive itself.
+    ///
+    /// Note that this may not be accurate before the archive's global header
+    /// has been read (e.g. by calling `next_entry()`).
+    pub fn is_thin(&self) -> bool { self.thin }
+
     /// Unwrap this archive reader, returning the underlying reader object.
     pub fn into_inner(self) -> Result<R> { Ok(self.reader) }
 
+    /// Sets the largest GNU name table, in bytes, that this archive will
+    /// read; a larger one is reported as `Error::NameTableTooLarge`.  There
+    /// is no limit by default.
+    ///
+    /// Even without a limit, the memory used for the name table never
+    /// exceeds the amount of data actually present in the archive, so this
+    /// is mainly useful for bounding what a large hostile archive can cost.
+    pub fn set_max_name_table_len(&mut self, len: u64) {
+        self.limits.name_table = len;
+    }
+
+    /// Sets the longest entry identifier, in bytes, that this archive will
+    /// read; a longer one is reported as `Error::IdentifierTooLong`.  For a
+    /// BSD-variant `#1/` identifier, this applies to its stored length,
+    /// including any null padding.  There is no limit by default.
+    pub fn set_max_identifier_len(&mut self, len: u64) {
+        self.limits.identifier = len;
+    }
+
+    /// Sets the largest symbol table, in bytes, that this archive will parse
+    /// (when listing or looking up symbols); a larger one is reported as
+    /// `Error::SymbolTableTooLarge`.  This applies separately to each of the
+    /// tables of an AIX big archive, and to the second linker member of a
+    /// COFF archive.  There is no limit by default.
+    pub fn set_max_symbol_table_len(&mut self, len: u64) {
+        self.limits.symbol_table = len;
+    }
+
+    /// Returns the number of bytes of data stored in the archive itself for an
+    /// entry with the given header.  This is the same as the header's size,
+    /// except for the ordinary entries of a thin archive.
+    fn stored_data_len(&self, header: &Header) -> u64 {
+        if self.thin && !self.is_name_table_id(header.identifier()) &&
+            !self.is_symbol_lookup_table_id(header.identifier())
+        {
+            0
+        } else {
+            header.size()
+        }
+    }
+
+    /// Returns the location of the data for an entry of a thin archive, or
+    /// `None` if this isn't a thin archive.
+    fn external_file(&self, header: &Header) -> Result<Option<ExternalFile>> {
+        if !self.thin {
+            return Ok(None);
+        }
+        let relative_path = try!(bytes_to_path(header.identifier()));
+        let path = match self.thin_base_dir {
+            Some(ref dir) => dir.join(relative_path),
+            None => relative_path,
+        };
+        Ok(Some(ExternalFile {
+            path: path,
+            file: None,
+        }))
+    }
+
     fn is_name_table_id(&self, identifier: &[u8]) -> bool {
-        self.variant == Variant::GNU && identifier == GNU_NAME_TABLE_ID
+        self.variant.is_gnu_like() && identifier == GNU_NAME_TABLE_ID
     }
 
     fn is_symbol_lookup_table_id(&self, identifier: &[u8]) -> bool {
         match self.variant {
-            Variant::Common => false,
-            Variant::BSD => {
-                identifier == BSD_SYMBOL_LOOKUP_TABLE_ID ||
-                    identifier == BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID
+            Variant::Common | Variant::AIX => false,
+            Variant::BSD => is_bsd_symbol_lookup_table_id(identifier),
+            Variant::GNU | Variant::COFF => {
+                identifier == GNU_SYMBOL_LOOKUP_TABLE_ID ||
+                    identifier == GNU_SYM64_LOOKUP_TABLE_ID
+            }
+        }
+    }
+
+    /// Records the location of a symbol lookup table.  A COFF archive has a
+    /// second `/` entry right after the first one, which holds a sorted index
+    /// of the symbols rather than replacing the first table.
+    fn record_symbol_table_header(&mut self, location: HeaderAndLocation) {
+        let follows_first_linker_member = match self.symbol_table_header {
+            Some(ref first) => {
+                first.header.identifier() == GNU_SYMBOL_LOOKUP_TABLE_ID
             }
-            Variant::GNU => identifier == GNU_SYMBOL_LOOKUP_TABLE_ID,
+            None => false,
+        };
+        if self.variant.is_gnu_like() && follows_first_linker_member &&
+            location.header.identifier() == GNU_SYMBOL_LOOKUP_TABLE_ID
+        {
+            self.variant = Variant::COFF;
+            self.coff_index_header = Some(location);
+        } else {
+            self.symbol_table_header = Some(location);
         }
     }
 
@@ -419,17 +1595,40 @@ impl<R: Read> Archive<R> {
             return Ok(());
         }
         let mut buffer = [0; GLOBAL_HEADER_LEN];
-        match self.reader.read_exact(&mut buffer) {
+        let result = read_exact_or(&mut self.reader, &mut buffer, || {
+            Error::TruncatedGlobalHeader
+        });
+        match result {
             Ok(()) => {}
             Err(error) => {
                 self.error = true;
                 return Err(error);
             }
         }
-        if &buffer != GLOBAL_HEADER {
+        if &buffer == THIN_GLOBAL_HEADER {
+            self.thin = true;
+            self.variant = Variant::GNU;
+        } else if &buffer == AIX_BIG_GLOBAL_HEADER {
+            let header = match AixFileHeader::read(&mut self.reader) {
+                Ok(header) => header,
+                Err(error) => {
+                    self.error = true;
+                    return Err(error);
+                }
+            };
+            self.variant = Variant::AIX;
+            self.new_entry_start = header.first_member;
+            self.position = AIX_BIG_FIXED_HEADER_LEN as u64;
+            if header.first_member == 0 {
+                self.scanned = true;
+            }
+            self.aix_header = Some(header);
+        } else if &buffer != GLOBAL_HEADER {
             self.error = true;
-            let msg = "Not an archive file (invalid global header)";
-            return Err(Error::new(ErrorKind::InvalidData, msg));
+            let error = Error::InvalidGlobalHeader {
+                bytes: buffer.to_vec(),
+            };
+            return Err(error.into());
         }
         self.started = true;
         Ok(())
@@ -438,6 +1637,17 @@ impl<R: Read> Archive<R> {
     /// Reads the next entry from the archive, or returns None if there are no
     /// more.
     pub fn next_entry(&mut self) -> Option<Result<Entry<R>>> {
+        self.next_entry_where(|_| true)
+    }
+
+    /// Reads the next entry from the archive whose header `wanted` returns
+    /// true for, skipping over the data of any entries before it, or returns
+    /// None if there are no more such entries.
+    fn next_entry_where<F>(&mut self, mut wanted: F)
+                           -> Option<Result<Entry<'_, R>>>
+    where
+        F: FnMut(&Header) -> bool,
+    {
         loop {
             if self.error {
                 return None;
@@ -451,9 +1661,42 @@ impl<R: Read> Archive<R> {
                 Ok(()) => {}
                 Err(error) => return Some(Err(error)),
             }
-            if self.padding {
+            if self.variant == Variant::AIX {
+                // Reading the global header may have just told us that the
+                // archive is empty.
+                if self.scanned &&
+                    self.next_entry_index == self.entry_headers.len()
+                {
+                    return None;
+                }
+                if let Err(error) = self.skip_to_next_aix_entry() {
+                    self.error = true;
+                    return Some(Err(error));
+                }
+                let index = self.next_entry_index;
+                self.next_entry_index += 1;
+                if !wanted(&self.entry_headers[index].header) {
+                    // The next call to skip_to() will skip over the data.
+                    continue;
+                }
+                let header = &self.entry_headers[index].header;
+                self.position += header.size();
+                return Some(Ok(Entry {
+                    header: header,
+                    reader: self.reader.by_ref(),
+                    length: header.size(),
+                    position: 0,
+                    external: None,
+                }));
+            }
+            if let Some((padded_start, padding_offset)) = self.padding {
                 let mut buffer = [0; 1];
-                match self.reader.read_exact(&mut buffer) {
+                let result = read_exact_or(&mut self.reader, &mut buffer, || {
+                    Error::TruncatedEntry {
+                        offset: padded_start,
+                    }
+                });
+                match result {
                     Ok(()) => {}
                     Err(error) => {
                         self.error = true;
@@ -462,30 +1705,39 @@ impl<R: Read> Archive<R> {
                 }
                 if &buffer != b"\n" {
                     self.error = true;
-                    let msg = "Invalid padding byte";
-                    return Some(Err(Error::new(ErrorKind::InvalidData, msg)));
+                    let error = Error::InvalidPadding {
+                        byte: buffer[0],
+                        offset: padding_offset,
+                    };
+                    return Some(Err(error.into()));
                 }
-                self.padding = false;
+                self.padding = None;
             }
             let header_start = self.new_entry_start;
             match Header::read(
                 &mut self.reader,
+                header_start,
                 &mut self.variant,
                 &mut self.name_table,
+                self.limits,
             ) {
                 Ok(Some((header, header_len))) => {
                     let size = header.size();
-                    if size % 2 != 0 {
-                        self.padding = true;
+                    let data_len = self.stored_data_len(&header);
+                    if data_len % 2 != 0 {
+                        let padding_offset = header_start + header_len +
+                            data_len;
+                        self.padding = Some((header_start, padding_offset));
                     }
                     if self.next_entry_index == self.entry_headers.len() {
-                        self.new_entry_start += header_len + size + (size % 2);
+                        self.new_entry_start +=
+                            header_len + data_len + (data_len % 2);
                     }
                     if self.is_name_table_id(header.identifier()) {
                         continue;
                     }
                     if self.is_symbol_lookup_table_id(header.identifier()) {
-                        self.symbol_table_header = Some(HeaderAndLocation {
+                        self.record_symbol_table_header(HeaderAndLocation {
                             header: header,
                             header_start: header_start,
                             data_start: header_start + header_len,
@@ -499,14 +1751,36 @@ impl<R: Read> Archive<R> {
                             data_start: header_start + header_len,
                         });
                     }
-                    let header = &self.entry_headers[self.next_entry_index]
-                        .header;
-                    self.next_entry_index += 1;
-                    return Some(Ok(Entry {
+                    if !wanted(&self.entry_headers[self.next_entry_index]
+                        .header)
+                    {
+                        self.next_entry_index += 1;
+                        if let Err(error) =
+                            self.skip_entry_data(header_start, data_len)
+                        {
+                            self.error = true;
+                            return Some(Err(error));
+                        }
+                        continue;
+                    }
+                    // Move past this entry before resolving its external
+                    // file, so that a member whose path can't be used
+                    // doesn't stop us from reading the ones after it.
+                    let index = self.next_entry_index;
+                    self.next_entry_index += 1;
+                    let external = match self.external_file(
+                        &self.entry_headers[index].header,
+                    ) {
+                        Ok(external) => external,
+                        Err(error) => return Some(Err(error)),
+                    };
+                    let header = &self.entry_headers[index].header;
+                    return Some(Ok(Entry {
                         header: header,
                         reader: self.reader.by_ref(),
                         length: size,
                         position: 0,
+                        external: external,
                     }));
                 }
                 Ok(None) => {
@@ -520,6 +1794,115 @@ impl<R: Read> Archive<R> {
             }
         }
     }
+
+    /// Reads and discards the data of an entry (whose header starts at
+    /// `header_start`) that the reader is positioned at the start of.
+    fn skip_entry_data(&mut self, header_start: u64, data_len: u64)
+                       -> Result<()> {
+        let skipped = try!(io::copy(
+            &mut self.reader.by_ref().take(data_len),
+            &mut io::sink(),
+        ));
+        if skipped < data_len {
+            let error = Error::TruncatedEntry {
+                offset: header_start,
+            };
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Moves the reader forward to the data of the next entry of an AIX big
+    /// archive, reading the entry's header first if we haven't already.  The
+    /// entries of such an archive are linked together by offset, so rather
+    /// than assuming that each entry immediately follows the previous one, we
+    /// skip ahead to wherever the next one starts.
+    fn skip_to_next_aix_entry(&mut self) -> Result<()> {
+        let index = self.next_entry_index;
+        if index == self.entry_headers.len() {
+            let header_start = self.new_entry_start;
+            try!(self.skip_to(header_start));
+            let (header, header_len, next) = try!(Header::read_aix(
+                &mut self.reader,
+                header_start,
+                self.limits,
+            ));
+            try!(self.record_aix_entry(
+                header,
+                header_start,
+                header_len,
+                next,
+            ));
+        } else {
+            let data_start = self.entry_headers[index].data_start;
+            try!(self.skip_to(data_start));
+        }
+        self.position = self.entry_headers[index].data_start;
+        Ok(())
+    }
+
+    /// Moves the reader forward to the given offset, without seeking.
+    fn skip_to(&mut self, offset: u64) -> Result<()> {
+        if offset < self.position {
+            let error = Error::AixMemberOverlaps {
+                offset: offset,
+                position: self.position,
+            };
+            return Err(error.into());
+        }
+        let distance = offset - self.position;
+        let skipped = try!(io::copy(
+            &mut self.reader.by_ref().take(distance),
+            &mut io::sink(),
+        ));
+        if skipped < distance {
+            let error = Error::TruncatedAixMember { offset: offset };
+            return Err(error.into());
+        }
+        self.position = offset;
+        Ok(())
+    }
+
+    /// Records the header of a newly-read entry of an AIX big archive, and
+    /// notes where the entry after it (if any) starts.
+    fn record_aix_entry(&mut self, header: Header, header_start: u64,
+                        header_len: u64, next: u64)
+                        -> Result<()> {
+        let last_member = self.aix_header.map_or(0, |aix| aix.last_member);
+        if header_start == last_member || next == 0 {
+            self.scanned = true;
+        } else if next <= header_start {
+            // Requiring entries to be in order keeps a corrupt archive from
+            // sending us around in circles.
+            let error = Error::AixLinksBackwards {
+                offset: header_start,
+                next: next,
+            };
+            return Err(error.into());
+        }
+        self.new_entry_start = next;
+        self.entry_headers.push(HeaderAndLocation {
+            header: header,
+            header_start: header_start,
+            data_start: header_start + header_len,
+        });
+        Ok(())
+    }
+}
+
+impl Archive<File> {
+    /// Opens the archive file at the given path.
+    ///
+    /// If the archive turns out to be a thin archive, its entries' data will
+    /// be read from files located relative to the directory containing the
+    /// archive.  (For thin archives read via `Archive::new()`, entries are
+    /// located relative to the current working directory instead.)
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Archive<File>> {
+        let path = path.as_ref();
+        let mut archive = Archive::new(try!(File::open(path)));
+        archive.thin_base_dir = path.parent().map(Path::to_path_buf);
+        Ok(archive)
+    }
 }
 
 impl<R: Read + Seek> Archive<R> {
@@ -528,45 +1911,73 @@ impl<R: Read + Seek> Archive<R> {
             return Ok(());
         }
         try!(self.read_global_header_if_necessary());
-        loop {
-            let header_start = self.new_entry_start;
-            try!(self.reader.seek(SeekFrom::Start(header_start)));
-            if let Some((header, header_len)) =
-                try!(Header::read(
+        if self.variant == Variant::AIX {
+            while !self.scanned {
+                let header_start = self.new_entry_start;
+                try!(self.reader.seek(SeekFrom::Start(header_start)));
+                let (header, header_len, next) = try!(Header::read_aix(
                     &mut self.reader,
-                    &mut self.variant,
-                    &mut self.name_table,
-                ))
-            {
-                let size = header.size();
-                self.new_entry_start += header_len + size + (size % 2);
-                if self.is_name_table_id(header.identifier()) {
-                    continue;
-                }
-                if self.is_symbol_lookup_table_id(header.identifier()) {
-                    self.symbol_table_header = Some(HeaderAndLocation {
+                    header_start,
+                    self.limits,
+                ));
+                try!(self.record_aix_entry(
+                    header,
+                    header_start,
+                    header_len,
+                    next,
+                ));
+            }
+        } else {
+            loop {
+                let header_start = self.new_entry_start;
+                try!(self.reader.seek(SeekFrom::Start(header_start)));
+                if let Some((header, header_len)) =
+                    try!(Header::read(
+                        &mut self.reader,
+                        header_start,
+                        &mut self.variant,
+                        &mut self.name_table,
+                        self.limits,
+                    ))
+                {
+                    let data_len = self.stored_data_len(&header);
+                    self.new_entry_start +=
+                        header_len + data_len + (data_len % 2);
+                    if self.is_name_table_id(header.identifier()) {
+                        continue;
+                    }
+                    if self.is_symbol_lookup_table_id(header.identifier()) {
+                        self.record_symbol_table_header(HeaderAndLocation {
+                            header: header,
+                            header_start: header_start,
+                            data_start: header_start + header_len,
+                        });
+                        continue;
+                    }
+                    self.entry_headers.push(HeaderAndLocation {
                         header: header,
                         header_start: header_start,
                         data_start: header_start + header_len,
                     });
-                    continue;
+                } else {
+                    break;
                 }
-                self.entry_headers.push(HeaderAndLocation {
-                    header: header,
-                    header_start: header_start,
-                    data_start: header_start + header_len,
-                });
-            } else {
-                break;
             }
         }
-        // Resume our previous position in the file.
+        try!(self.resume_position());
+        self.scanned = true;
+        Ok(())
+    }
+
+    /// Seeks back to the start of the next entry to be returned by
+    /// `next_entry()`, after we've moved the reader elsewhere.
+    fn resume_position(&mut self) -> io::Result<()> {
         if self.next_entry_index < self.entry_headers.len() {
             let offset = self.entry_headers[self.next_entry_index]
                 .header_start;
             try!(self.reader.seek(SeekFrom::Start(offset)));
+            self.position = offset;
         }
-        self.scanned = true;
         Ok(())
     }
 
@@ -584,23 +1995,32 @@ impl<R: Read + Seek> Archive<R> {
         try!(self.scan_if_necessary());
         if index >= self.entry_headers.len() {
             let msg = "Entry index out of bounds";
-            return Err(Error::new(ErrorKind::InvalidInput, msg));
-        }
-        if index != self.next_entry_index {
-            let offset = self.entry_headers[index].data_start;
-            try!(self.reader.seek(SeekFrom::Start(offset)));
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
         }
+        // Even if this is the next entry, the reader is somewhere before its
+        // data (if not past the end of the archive, after scanning), so we
+        // always need to seek.
+        let offset = self.entry_headers[index].data_start;
+        try!(self.reader.seek(SeekFrom::Start(offset)));
+        let external =
+            try!(self.external_file(&self.entry_headers[index].header));
+        let header_and_loc = &self.entry_headers[index];
+        let data_len = self.stored_data_len(&header_and_loc.header);
+        self.padding = if data_len % 2 != 0 {
+            Some((header_and_loc.header_start,
+                  header_and_loc.data_start + data_len))
+        } else {
+            None
+        };
         let header = &self.entry_headers[index].header;
-        let size = header.size();
-        if size % 2 != 0 {
-            self.padding = true;
-        }
         self.next_entry_index = index + 1;
+        self.position = self.entry_headers[index].data_start + header.size();
         Ok(Entry {
             header: header,
             reader: self.reader.by_ref(),
-            length: size,
+            length: header.size(),
             position: 0,
+            external: external,
         })
     }
 
@@ -609,66 +2029,70 @@ impl<R: Read + Seek> Archive<R> {
         if self.symbol_table.is_some() {
             return Ok(());
         }
-        if let Some(ref header_and_loc) = self.symbol_table_header {
-            let offset = header_and_loc.data_start;
-            try!(self.reader.seek(SeekFrom::Start(offset)));
-            let mut reader = BufReader::new(self.reader.by_ref().take(
-                header_and_loc.header.size(),
-            ));
-            if self.variant == Variant::GNU {
-                let num_symbols = try!(reader.read_u32::<BigEndian>()) as
-                    usize;
-                let mut symbol_offsets =
-                    Vec::<u32>::with_capacity(num_symbols);
-                for _ in 0..num_symbols {
-                    let offset = try!(reader.read_u32::<BigEndian>());
-                    symbol_offsets.push(offset);
-                }
-                let mut symbol_table = Vec::with_capacity(num_symbols);
-                for offset in symbol_offsets.into_iter() {
-                    let mut buffer = Vec::<u8>::new();
-                    try!(reader.read_until(0, &mut buffer));
-                    if buffer.last() == Some(&0) {
-                        buffer.pop();
-                    }
-                    buffer.shrink_to_fit();
-                    symbol_table.push((buffer, offset as u64));
-                }
-                self.symbol_table = Some(symbol_table);
-            } else {
-                let num_symbols =
-                    (try!(reader.read_u32::<LittleEndian>()) / 8) as usize;
-                let mut symbol_offsets =
-                    Vec::<(u32, u32)>::with_capacity(num_symbols);
-                for _ in 0..num_symbols {
-                    let str_offset = try!(reader.read_u32::<LittleEndian>());
-                    let file_offset = try!(reader.read_u32::<LittleEndian>());
-                    symbol_offsets.push((str_offset, file_offset));
-                }
-                let str_table_len = try!(reader.read_u32::<LittleEndian>());
-                let mut str_table_data = vec![0u8; str_table_len as usize];
-                try!(reader.read_exact(&mut str_table_data));
-                let mut symbol_table = Vec::with_capacity(num_symbols);
-                for (str_start, file_offset) in symbol_offsets.into_iter() {
-                    let str_start = str_start as usize;
-                    let mut str_end = str_start;
-                    while str_end < str_table_data.len() &&
-                        str_table_data[str_end] != 0u8
-                    {
-                        str_end += 1;
-                    }
-                    let string = &str_table_data[str_start..str_end];
-                    symbol_table.push((string.to_vec(), file_offset as u64));
+        if let Some(aix_header) = self.aix_header {
+            // An AIX big archive has separate tables for the symbols of 32-bit
+            // and 64-bit objects (both laid out like a GNU /SYM64/ table),
+            // which we list in that order.
+            let mut symbol_table = Vec::new();
+            for &offset in
+                [aix_header.symbol_table, aix_header.symbol_table_64].iter()
+            {
+                if offset == 0 {
+                    continue;
                 }
-                self.symbol_table = Some(symbol_table);
+                try!(self.reader.seek(SeekFrom::Start(offset)));
+                let (header, _, _) = try!(Header::read_aix(
+                    &mut self.reader,
+                    offset,
+                    self.limits,
+                ));
+                let table_len = header.size();
+                try!(self.limits.check_symbol_table_len(table_len, offset));
+                let mut reader =
+                    BufReader::new(self.reader.by_ref().take(table_len));
+                let table = try!(
+                    read_sym64_table(&mut reader, table_len)
+                        .map_err(|error| truncated_table(error, offset))
+                );
+                symbol_table.extend(table);
             }
-        }
-        // Resume our previous position in the file.
-        if self.entry_headers.len() > 0 {
-            let offset = self.entry_headers[self.next_entry_index]
-                .header_start;
+            self.symbol_table = Some(symbol_table);
+        } else if let Some(ref header_and_loc) = self.symbol_table_header {
+            let table_len = header_and_loc.header.size();
+            try!(self.limits.check_symbol_table_len(
+                table_len,
+                header_and_loc.header_start,
+            ));
+            let offset = header_and_loc.data_start;
             try!(self.reader.seek(SeekFrom::Start(offset)));
+            let mut reader =
+                BufReader::new(self.reader.by_ref().take(table_len));
+            let header_start = header_and_loc.header_start;
+            let identifier = header_and_loc.header.identifier();
+            let result = if self.variant.is_gnu_like() &&
+                identifier == GNU_SYM64_LOOKUP_TABLE_ID
+            {
+                read_sym64_table(&mut reader, table_len)
+            } else if self.variant.is_gnu_like() {
+                read_gnu_symbol_table(&mut reader, table_len)
+            } else {
+                // The 64-bit variants of the BSD symbol table use 64-bit
+                // integers (and thus 16-byte ranlib structs) throughout.
+                let is_64_bit = identifier == BSD_SYM64_LOOKUP_TABLE_ID ||
+                    identifier == BSD_SORTED_SYM64_LOOKUP_TABLE_ID;
+                read_bsd_symbol_table(
+                    &mut reader,
+                    table_len,
+                    is_64_bit,
+                    header_start,
+                )
+            };
+            let symbol_table = try!(
+                result.map_err(|error| truncated_table(error, header_start))
+            );
+            self.symbol_table = Some(symbol_table);
         }
+        try!(self.resume_position());
         Ok(())
     }
 
@@ -683,6 +2107,140 @@ impl<R: Read + Seek> Archive<R> {
             index: 0,
         })
     }
+
+    fn resolve_symbol_entries_if_necessary(&mut self) -> io::Result<()> {
+        try!(self.parse_symbol_table_if_necessary());
+        if self.symbol_entry_indices.is_some() {
+            return Ok(());
+        }
+        let mut indices = Vec::new();
+        if let Some(ref table) = self.symbol_table {
+            indices.reserve(table.len());
+            for &(ref name, offset) in table.iter() {
+                indices.push(try!(self.symbol_entry_index(name, offset)));
+            }
+        }
+        self.symbol_entry_indices = Some(indices);
+        Ok(())
+    }
+
+    /// Returns the index of the entry whose header starts at the given
+    /// offset, which the given symbol's table entry refers to.
+    fn symbol_entry_index(&self, symbol: &[u8], offset: u64)
+                          -> io::Result<usize> {
+        match self.entry_headers
+            .binary_search_by_key(&offset, |entry| entry.header_start)
+        {
+            Ok(index) => Ok(index),
+            Err(_) => {
+                let error = Error::InvalidSymbolOffset {
+                    symbol: symbol.to_vec(),
+                    entry_offset: offset,
+                };
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Scans the archive and returns an iterator over the symbols in the
+    /// archive's symbol table, each paired with the index of the entry that
+    /// defines it (suitable for passing to `jump_to_entry()`).  Returns an
+    /// error if any symbol refers to an offset that isn't the start of an
+    /// entry.
+    pub fn symbol_entries(&mut self) -> io::Result<SymbolEntries<'_, R>> {
+        try!(self.resolve_symbol_entries_if_necessary());
+        Ok(SymbolEntries {
+            archive: self,
+            index: 0,
+        })
+    }
+
+    fn build_symbol_lookup_if_necessary(&mut self) -> io::Result<()> {
+        try!(self.resolve_symbol_entries_if_necessary());
+        if self.symbol_lookup.is_some() {
+            return Ok(());
+        }
+        let mut lookup = HashMap::new();
+        if let (Some(table), Some(indices)) =
+            (&self.symbol_table, &self.symbol_entry_indices)
+        {
+            lookup.reserve(table.len());
+            for ((name, _), &index) in table.iter().zip(indices.iter()) {
+                // If a symbol is defined more than once, the first definition
+                // wins, just as it would for a linker.
+                lookup.entry(name.clone()).or_insert(index);
+            }
+        }
+        self.symbol_lookup = Some(lookup);
+        Ok(())
+    }
+
+    /// Looks up a symbol in the archive's symbol table, and returns the index
+    /// of the entry that defines it (suitable for passing to
+    /// `jump_to_entry()`), or `None` if the symbol isn't in the table.
+    ///
+    /// The first call to this method builds a hash table of all symbols, so
+    /// subsequent lookups take constant time.
+    pub fn find_symbol(&mut self, symbol: &[u8]) -> io::Result<Option<usize>> {
+        try!(self.build_symbol_lookup_if_necessary());
+        Ok(self.symbol_lookup
+            .as_ref()
+            .and_then(|lookup| lookup.get(symbol).cloned()))
+    }
+
+    /// Looks up a symbol in the archive's symbol table, and jumps to the
+    /// entry that defines it.  Returns `None` if the symbol isn't in the
+    /// table.
+    pub fn jump_to_symbol(&mut self, symbol: &[u8])
+                          -> io::Result<Option<Entry<'_, R>>> {
+        match try!(self.find_symbol(symbol)) {
+            Some(index) => self.jump_to_entry(index).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_sorted_symbols_if_necessary(&mut self) -> io::Result<()> {
+        try!(self.scan_if_necessary());
+        if self.sorted_symbols.is_some() {
+            return Ok(());
+        }
+        let mut symbols = Vec::new();
+        if let Some(ref header_and_loc) = self.coff_index_header {
+            let header_start = header_and_loc.header_start;
+            let table_len = header_and_loc.header.size();
+            try!(self.limits.check_symbol_table_len(table_len, header_start));
+            let offset = header_and_loc.data_start;
+            try!(self.reader.seek(SeekFrom::Start(offset)));
+            let mut reader =
+                BufReader::new(self.reader.by_ref().take(table_len));
+            symbols = try!(
+                read_coff_symbol_index(&mut reader, table_len)
+                    .map_err(|error| truncated_table(error, header_start))
+            );
+        }
+        let mut sorted_symbols = Vec::with_capacity(symbols.len());
+        for (name, offset) in symbols.into_iter() {
+            let index = try!(self.symbol_entry_index(&name, offset));
+            sorted_symbols.push((name, index));
+        }
+        self.sorted_symbols = Some(sorted_symbols);
+        try!(self.resume_position());
+        Ok(())
+    }
+
+    /// Scans the archive and returns an iterator over the symbols in the
+    /// sorted symbol index of a COFF archive (its second linker member), in
+    /// order by name, each paired with the index of the entry that defines it
+    /// (suitable for passing to `jump_to_entry()`).  If the archive doesn't
+    /// have such an index, this method will still succeed, but the iterator
+    /// won't produce any values.
+    pub fn sorted_symbols(&mut self) -> io::Result<SortedSymbols<'_, R>> {
+        try!(self.parse_sorted_symbols_if_necessary());
+        Ok(SortedSymbols {
+            archive: self,
+            index: 0,
+        })
+    }
 }
 
 // ========================================================================= //
@@ -692,11 +2250,30 @@ impl<R: Read + Seek> Archive<R> {
 /// `Entry` objects implement the `Read` trait, and can be used to extract the
 /// data from this archive entry.  If the underlying reader supports the `Seek`
 /// trait, then the `Entry` object supports `Seek` as well.
+///
+/// For entries of a thin archive, the data is read from the external file
+/// that the entry refers to, which is opened on the first read or seek.
 pub struct Entry<'a, R: 'a + Read> {
     header: &'a Header,
     reader: &'a mut R,
     length: u64,
     position: u64,
+    external: Option<ExternalFile>,
+}
+
+/// The file holding the data for an entry of a thin archive.
+struct ExternalFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl ExternalFile {
+    fn file(&mut self) -> Result<&mut File> {
+        if self.file.is_none() {
+            self.file = Some(try!(File::open(&self.path)));
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
 }
 
 impl<'a, R: 'a + Read> Entry<'a, R> {
@@ -712,7 +2289,12 @@ impl<'a, R: 'a + Read> Read for Entry<'a, R> {
         }
         let max_len =
             cmp::min(self.length - self.position, buf.len() as u64) as usize;
-        let bytes_read = try!(self.reader.read(&mut buf[0..max_len]));
+        let bytes_read = match self.external {
+            Some(ref mut external) => {
+                try!(try!(external.file()).read(&mut buf[0..max_len]))
+            }
+            None => try!(self.reader.read(&mut buf[0..max_len])),
+        };
         self.position += bytes_read as u64;
         debug_assert!(self.position <= self.length);
         Ok(bytes_read)
@@ -734,7 +2316,7 @@ impl<'a, R: 'a + Read + Seek> Seek for Entry<'a, R> {
                 "Invalid seek to negative position ({})",
                 new_position
             );
-            return Err(Error::new(ErrorKind::InvalidInput, msg));
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
         }
         let new_position = new_position as u64;
         if new_position > self.length {
@@ -743,9 +2325,17 @@ impl<'a, R: 'a + Read + Seek> Seek for Entry<'a, R> {
                 new_position,
                 self.length
             );
-            return Err(Error::new(ErrorKind::InvalidInput, msg));
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        match self.external {
+            Some(ref mut external) => {
+                let file = try!(external.file());
+                try!(file.seek(SeekFrom::Start(new_position)));
+            }
+            None => {
+                try!(self.reader.seek(SeekFrom::Current(delta)));
+            }
         }
-        try!(self.reader.seek(SeekFrom::Current(delta)));
         self.position = new_position;
         Ok(self.position)
     }
@@ -753,7 +2343,7 @@ impl<'a, R: 'a + Read + Seek> Seek for Entry<'a, R> {
 
 impl<'a, R: 'a + Read> Drop for Entry<'a, R> {
     fn drop(&mut self) {
-        if self.position < self.length {
+        if self.position < self.length && self.external.is_none() {
             // Consume the rest of the data in this entry.
             let mut remaining = self.reader.take(self.length - self.position);
             let _ = io::copy(&mut remaining, &mut io::sink());
@@ -797,166 +2387,4224 @@ impl<'a, R: Read> ExactSizeIterator for Symbols<'a, R> {}
 
 // ========================================================================= //
 
+/// An iterator over the symbols in the symbol table of an archive, along with
+/// the index of the entry that defines each symbol.
+pub struct SymbolEntries<'a, R: 'a + Read> {
+    archive: &'a Archive<R>,
+    index: usize,
+}
+
+impl<'a, R: Read> Iterator for SymbolEntries<'a, R> {
+    type Item = (&'a [u8], usize);
+
+    fn next(&mut self) -> Option<(&'a [u8], usize)> {
+        if let (Some(table), Some(indices)) =
+            (&self.archive.symbol_table, &self.archive.symbol_entry_indices)
+        {
+            if self.index < table.len() {
+                let name = table[self.index].0.as_slice();
+                let next = (name, indices[self.index]);
+                self.index += 1;
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if let Some(ref table) = self.archive.symbol_table {
+            table.len() - self.index
+        } else {
+            0
+        };
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, R: Read> ExactSizeIterator for SymbolEntries<'a, R> {}
+
+// ========================================================================= //
+
+/// An iterator over the symbols in the sorted symbol index of a COFF archive,
+/// along with the index of the entry that defines each symbol.
+pub struct SortedSymbols<'a, R: 'a + Read> {
+    archive: &'a Archive<R>,
+    index: usize,
+}
+
+impl<'a, R: Read> Iterator for SortedSymbols<'a, R> {
+    type Item = (&'a [u8], usize);
+
+    fn next(&mut self) -> Option<(&'a [u8], usize)> {
+        if let Some(ref symbols) = self.archive.sorted_symbols {
+            if self.index < symbols.len() {
+                let (ref name, index) = symbols[self.index];
+                self.index += 1;
+                return Some((name.as_slice(), index));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if let Some(ref symbols) = self.archive.sorted_symbols
+        {
+            symbols.len() - self.index
+        } else {
+            0
+        };
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, R: Read> ExactSizeIterator for SortedSymbols<'a, R> {}
+
+// ========================================================================= //
+
+/// Returns the value of the `SOURCE_DATE_EPOCH` environment variable, which
+/// reproducible builds use to pass along a timestamp that outputs shouldn't
+/// be any newer than, or None if it isn't set.  Returns an error if it is set
+/// to anything other than a number of seconds since the Unix epoch.
+pub fn source_date_epoch() -> Result<Option<u64>> {
+    parse_source_date_epoch(env::var_os("SOURCE_DATE_EPOCH"))
+}
+
+fn parse_source_date_epoch(value: Option<OsString>) -> Result<Option<u64>> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.to_str().and_then(|value| value.parse().ok()) {
+        Some(epoch) => Ok(Some(epoch)),
+        None => {
+            let msg = format!("Invalid SOURCE_DATE_EPOCH value ({:?})", value);
+            Err(io::Error::new(ErrorKind::InvalidData, msg))
+        }
+    }
+}
+
+/// How a builder rewrites the metadata of each header that it writes, for
+/// reproducible builds.  The default policy leaves headers as they are.
+///
+/// # Example usage
+///
+/// ```no_run
+/// use ar::{Builder, MetadataPolicy, source_date_epoch};
+/// use std::fs::File;
+/// let mut policy = MetadataPolicy::new();
+/// policy.set_mtime_clamp(source_date_epoch().unwrap());
+/// let mut builder = Builder::new(File::create("libfoo.a").unwrap());
+/// builder.set_metadata_policy(policy);
+/// builder.append_path("foo.o").unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MetadataPolicy {
+    deterministic: bool,
+    mtime_clamp: Option<u64>,
+}
+
+impl MetadataPolicy {
+    /// Creates a policy that leaves headers as they are.
+    pub fn new() -> MetadataPolicy { MetadataPolicy::default() }
+
+    /// Returns whether every header is written with a zero timestamp, owner,
+    /// and group, and with mode 644.
+    pub fn deterministic(&self) -> bool { self.deterministic }
+
+    /// Sets whether to write every header with a zero timestamp, owner, and
+    /// group, and with mode 644, whatever it says (like the `D` modifier of
+    /// GNU `ar`).  This is off by default.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Returns the timestamp that timestamps are clamped to, if any.
+    pub fn mtime_clamp(&self) -> Option<u64> { self.mtime_clamp }
+
+    /// Sets a timestamp that headers' timestamps are clamped to, so that
+    /// later ones are replaced by it.  Pass the result of
+    /// `source_date_epoch()` to follow the `SOURCE_DATE_EPOCH` convention.
+    pub fn set_mtime_clamp(&mut self, clamp: Option<u64>) {
+        self.mtime_clamp = clamp;
+    }
+
+    /// Returns a copy of the given header, rewritten according to this
+    /// policy.  Builders call this on each header that they write; it is
+    /// useful for headers that are written by other means.
+    pub fn apply(&self, header: &Header) -> Header {
+        let mut header = header.clone();
+        if self.deterministic {
+            header.mtime = 0;
+            header.uid = 0;
+            header.gid = 0;
+            header.mode = DETERMINISTIC_MODE;
+        }
+        if let Some(clamp) = self.mtime_clamp {
+            header.mtime = cmp::min(header.mtime, clamp);
+        }
+        header
+    }
+}
+
+// ========================================================================= //
+
+/// A source of the symbols defined by archive entries, which lets a builder
+/// write a symbol table automatically (see `Builder::set_symbol_extractor`).
+///
+/// With the `object` feature, `object::ObjectSymbolExtractor` finds the
+/// symbols in ELF, Mach-O, and COFF object files and in LLVM bitcode; other
+/// formats can be supported by implementing this trait.  It is also
+/// implemented for closures with the same signature as `extract_symbols`.
+///
+/// A builder keeps its extractor until the archive is written, so the
+/// extractor must be `'static`: a closure can own the values that it
+/// captures (with `move`), but not borrow them.
+pub trait SymbolExtractor {
+    /// Returns the names of the global symbols defined by the entry with the
+    /// given header and data, or an empty list if the entry isn't an object
+    /// file.  An error stops the archive from being written.
+    fn extract_symbols(&mut self, header: &Header, data: &[u8])
+                       -> Result<Vec<Vec<u8>>>;
+}
+
+impl<F> SymbolExtractor for F
+where
+    F: FnMut(&Header, &[u8]) -> Result<Vec<Vec<u8>>>,
+{
+    fn extract_symbols(&mut self, header: &Header, data: &[u8])
+                       -> Result<Vec<Vec<u8>>> {
+        self(header, data)
+    }
+}
+
+// ========================================================================= //
+
 /// A structure for building archives.
 ///
 /// This structure has methods for building up an archive from scratch into any
 /// arbitrary writer.
+///
+/// By default, the archive is written in the BSD variant: each header is
+/// written in the common format unless its identifier is longer than 16 bytes
+/// or contains a space, in which case the BSD `#1/` extension is used.  Use
+/// `Builder::new_with_variant` to insist on the common variant instead, or to
+/// write the GNU variant.  To add entries to the end of an existing archive,
+/// use `Builder::from_existing`.
 pub struct Builder<W: Write> {
     writer: W,
+    variant: Variant,
+    metadata: MetadataPolicy,
     started: bool,
+    symbol_table: Option<SymbolTableWriter<W>>,
+    symbol_collector: Option<SymbolCollector<W>>,
 }
 
 impl<W: Write> Builder<W> {
     /// Create a new archive builder with the underlying writer object as the
     /// destination of all data written.
     pub fn new(writer: W) -> Builder<W> {
+        Builder::with_variant(writer, Variant::BSD)
+    }
+
+    /// Create a new archive builder that writes the given variant, which must
+    /// be `Variant::Common`, `Variant::BSD`, or `Variant::GNU`.
+    ///
+    /// For the common variant, appending an entry whose identifier would need
+    /// the BSD extension is an error, rather than silently switching formats.
+    /// For the GNU variant, identifiers longer than 15 bytes are an error,
+    /// since they would need a name table at the start of the archive (use
+    /// `GnuBuilder` for those).
+    pub fn new_with_variant(writer: W, variant: Variant)
+                            -> Result<Builder<W>> {
+        match variant {
+            Variant::Common | Variant::BSD | Variant::GNU => {}
+            Variant::AIX => {
+                let msg = "Builder cannot write AIX big archives (use \
+                           AixBuilder instead)";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            Variant::COFF => {
+                let msg = "Builder cannot write COFF archives";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+        }
+        Ok(Builder::with_variant(writer, variant))
+    }
+
+    fn with_variant(writer: W, variant: Variant) -> Builder<W> {
         Builder {
             writer: writer,
+            variant: variant,
+            metadata: MetadataPolicy::default(),
             started: false,
+            symbol_table: None,
+            symbol_collector: None,
         }
     }
 
+    /// Sets how the timestamp, owner, group, and mode of each entry are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
     /// Unwrap this archive builder, returning the underlying writer object.
-    pub fn into_inner(self) -> Result<W> { Ok(self.writer) }
+    /// If this builder has a symbol extractor, the symbol table is inserted
+    /// first (see `set_symbol_extractor`).
+    ///
+    /// Returns an error if this builder has a symbol table that refers to an
+    /// entry that was never appended.
+    pub fn into_inner(mut self) -> Result<W> {
+        if let Some(ref table) = self.symbol_table {
+            try!(table.check_complete());
+        }
+        if let Some(collector) = self.symbol_collector.take() {
+            try!(collector.insert_table(&mut self.writer));
+        }
+        Ok(self.writer)
+    }
 
-    /// Adds a new entry to this archive.
+    /// Adds a new entry to this archive.  Returns an error if the entry's
+    /// identifier can't be written in this builder's variant.
     pub fn append<R: Read>(&mut self, header: &Header, mut data: R)
         -> Result<()> {
+        try!(header.check_identifier(self.variant));
+        let header = &self.metadata.apply(header);
         if !self.started {
             try!(self.writer.write_all(GLOBAL_HEADER));
+            if let Some(ref mut table) = self.symbol_table {
+                try!(table.write_table(&mut self.writer));
+            }
             self.started = true;
         }
-        try!(header.write(&mut self.writer));
-        let actual_size = try!(io::copy(&mut data, &mut self.writer));
-        if actual_size != header.size() {
-            let msg = format!(
-                "Wrong file size (header.size() = {}, actual \
-                               size was {})",
-                header.size(),
-                actual_size
-            );
-            return Err(Error::new(ErrorKind::InvalidData, msg));
+        if let Some(ref mut table) = self.symbol_table {
+            try!(table.record_entry(&mut self.writer, header.identifier()));
         }
-        if actual_size % 2 != 0 {
-            try!(self.writer.write_all(&['\n' as u8]));
+        let mut buffered = None;
+        if let Some(ref mut collector) = self.symbol_collector {
+            let mut bytes = Vec::new();
+            try!(data.read_to_end(&mut bytes));
+            try!(collector.record_entry(&mut self.writer, header, &bytes));
+            buffered = Some(bytes);
         }
+        if self.variant == Variant::GNU {
+            try!(header.write_gnu(&mut self.writer, &HashMap::new()));
+        } else {
+            try!(header.write(&mut self.writer));
+        }
+        match buffered {
+            Some(bytes) => {
+                write_entry_data(&mut self.writer, header, bytes.as_slice())
+            }
+            None => write_entry_data(&mut self.writer, header, data),
+        }
+    }
+
+    /// Adds a file on the local filesystem to this archive, using the file
+    /// name as its identifier.
+    pub fn append_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let name: &OsStr = try!(path.as_ref().file_name().ok_or_else(|| {
+            let msg = "Given path doesn't have a file name";
+            io::Error::new(ErrorKind::InvalidInput, msg)
+        }));
+        let identifier = try!(osstr_to_bytes(name));
+        let mut file = try!(File::open(&path));
+        self.append_file_id(identifier, &mut file)
+    }
+
+    /// Adds a file to this archive, with the given name as its identifier.
+    pub fn append_file(&mut self, name: &[u8], file: &mut File) -> Result<()> {
+        self.append_file_id(name.to_vec(), file)
+    }
+
+    /// Copies an entry from another archive into this archive, with the same
+    /// identifier and metadata.  The identifier is written in this archive's
+    /// own format, whichever variant the entry was read from.
+    pub fn append_entry<R: Read>(&mut self, entry: &mut Entry<R>)
+                                 -> Result<()> {
+        let header = entry.header().clone();
+        self.append(&header, entry)
+    }
+
+    /// Copies every entry that hasn't been read yet from another archive into
+    /// this archive, as with `append_entry`.  The other archive's symbol
+    /// table (if any) is not copied.
+    pub fn append_archive<R: Read>(&mut self, archive: &mut Archive<R>)
+                                   -> Result<()> {
+        copy_entries(archive, |entry| self.append_entry(entry))
+    }
+
+    fn append_file_id(&mut self, id: Vec<u8>, file: &mut File) -> Result<()> {
+        let metadata = try!(file.metadata());
+        let header = Header::from_metadata(id, &metadata);
+        self.append(&header, file)
+    }
+}
+
+impl<W: Write + Seek> Builder<W> {
+    /// Create a new archive builder that will begin the archive with a BSD
+    /// symbol table (`__.SYMDEF`).  Each element of `symbol_table` is a pair
+    /// of a symbol name and the identifier of the archive entry that defines
+    /// it.
+    ///
+    /// The symbol table is written (with placeholder offsets) before the
+    /// first entry, and the offset for each entry is filled in when that entry
+    /// is appended, which is why the writer must support seeking.  Since the
+    /// table is written before the size of the archive is known, it always
+    /// has 32-bit offsets, and appending an entry that defines a symbol is an
+    /// error if the entry would start 4 GiB or more into the archive.
+    pub fn new_with_symbol_table(mut writer: W,
+                                 symbol_table: Vec<(Vec<u8>, Vec<u8>)>)
+                                 -> Result<Builder<W>> {
+        let symbol_table = try!(SymbolTableWriter::new(
+            &mut writer,
+            Variant::BSD,
+            symbol_table,
+        ));
+        Ok(Builder {
+            writer: writer,
+            variant: Variant::BSD,
+            metadata: MetadataPolicy::default(),
+            started: false,
+            symbol_table: symbol_table,
+            symbol_collector: None,
+        })
+    }
+}
+
+impl<W: Read + Write + Seek> Builder<W> {
+    /// Sets a symbol extractor (such as `object::ObjectSymbolExtractor`) to
+    /// run over the data of each entry as it is appended, so that the archive
+    /// gets a symbol table listing the symbols that it finds.  If it finds
+    /// none, no symbol table is written.
+    ///
+    /// Since the symbol table must come before the entries, it is inserted
+    /// by `into_inner`, which moves the rest of the archive along to make
+    /// room; this is why the writer must support reading as well as seeking
+    /// (so a `File` must be opened for both).  A `BufWriter` doesn't
+    /// implement `Read`, so to buffer the output, use a `StagedBuilder`
+    /// (which only needs a `Write`) instead.  Each entry's data is buffered
+    /// in memory while it is appended.  Returns an error if any entries have
+    /// already been written (as with `Builder::from_existing`), if this
+    /// builder already has a symbol table, or if it writes the common variant
+    /// (which can't have one).
+    pub fn set_symbol_extractor<E>(&mut self, extractor: E) -> Result<()>
+    where
+        E: SymbolExtractor + 'static,
+    {
+        if self.started {
+            let msg = "Cannot set a symbol extractor after entries have been \
+                       written";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if self.symbol_table.is_some() {
+            let msg = "Builder already has a symbol table";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if self.variant == Variant::Common {
+            let msg = "Common-variant archives cannot have a symbol table";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let collector = try!(SymbolCollector::new(
+            &mut self.writer,
+            self.variant,
+            extractor,
+        ));
+        self.symbol_collector = Some(collector);
         Ok(())
     }
 
+    /// Create a new archive builder that appends entries to the end of the
+    /// existing archive in `writer` (for example, a file opened for both
+    /// reading and writing), without rewriting the entries already there.
+    /// New entries are written in the same variant as the existing ones (see
+    /// `Builder::new_with_variant`), except that an archive that appears to be
+    /// in the common variant (as any archive without long names does) is
+    /// continued in the BSD variant, which is what `Builder::new` writes.  If
+    /// `writer` is empty, a new archive is started instead, as with
+    /// `Builder::new`.
+    ///
+    /// If the archive's last entry is missing its trailing padding byte, the
+    /// byte is added.  Returns an error if the archive is truncated, or if it
+    /// has a symbol table (which would be left out of date by appending).
+    /// Thin archives, AIX big archives, and COFF archives can't be appended
+    /// to this way.
+    pub fn from_existing(mut writer: W) -> Result<Builder<W>> {
+        let len = try!(writer.seek(SeekFrom::End(0)));
+        if len == 0 {
+            return Ok(Builder::new(writer));
+        }
+        try!(writer.seek(SeekFrom::Start(0)));
+        let (variant, end) = {
+            let mut archive = Archive::new(&mut writer);
+            try!(archive.count_entries());
+            if archive.is_thin() {
+                let msg = "Cannot append to a thin archive with Builder (use \
+                           ThinBuilder instead)";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            if archive.symbol_table_header.is_some() {
+                let msg = "Cannot append to an archive with a symbol table \
+                           (use ArchiveEditor instead)";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            let variant = match archive.variant() {
+                Variant::Common => Variant::BSD,
+                variant => variant,
+            };
+            (variant, archive.new_entry_start)
+        };
+        let mut builder = try!(Builder::new_with_variant(writer, variant));
+        try!(builder.writer.seek(SeekFrom::End(0)));
+        // Entries always start at even offsets, so the archive should end at
+        // one too, unless the padding byte after the last entry was left off.
+        if len + 1 == end && end % 2 == 0 {
+            try!(builder.writer.write_all(b"\n"));
+        } else if len != end {
+            let error = Error::UnexpectedArchiveLength {
+                expected: end,
+                actual: len,
+            };
+            return Err(error.into());
+        }
+        builder.started = true;
+        Ok(builder)
+    }
+}
+
+/// Copies the data for an entry into the writer, followed by a padding byte if
+/// needed, and checks that the amount of data matches the header's size.
+fn write_entry_data<W: Write, R: Read>(writer: &mut W, header: &Header,
+                                       mut data: R)
+                                       -> Result<()> {
+    let actual_size = try!(io::copy(&mut data, writer));
+    if actual_size != header.size() {
+        let msg = format!(
+            "Wrong file size (header.size() = {}, actual \
+                           size was {})",
+            header.size(),
+            actual_size
+        );
+        return Err(io::Error::new(ErrorKind::InvalidData, msg));
+    }
+    if actual_size % 2 != 0 {
+        try!(writer.write_all(&['\n' as u8]));
+    }
+    Ok(())
+}
+
+/// Calls `append_entry` with each entry that hasn't been read yet from the
+/// given archive; this is the loop behind each builder's `append_archive`.
+fn copy_entries<R, F>(archive: &mut Archive<R>, mut append_entry: F)
+                      -> Result<()>
+where
+    R: Read,
+    F: FnMut(&mut Entry<R>) -> Result<()>,
+{
+    while let Some(entry) = archive.next_entry() {
+        try!(append_entry(&mut try!(entry)));
+    }
+    Ok(())
+}
+
+/// Returns a header for the file at the given path, using the file name as
+/// its identifier.
+fn header_for_path(path: &Path) -> Result<Header> {
+    let name: &OsStr = try!(path.file_name().ok_or_else(|| {
+        let msg = "Given path doesn't have a file name";
+        io::Error::new(ErrorKind::InvalidInput, msg)
+    }));
+    let identifier = try!(osstr_to_bytes(name));
+    let metadata = try!(fs::metadata(path));
+    Ok(Header::from_metadata(identifier, &metadata))
+}
+
+/// Writes a file at the given path with the `write` function, atomically
+/// replacing any existing file there (keeping its permissions).  The file is
+/// first written, through a buffer, to a temporary file in the same
+/// directory, which is then renamed over the destination, so that an error
+/// part-way through leaves the destination untouched.  This is how
+/// `ArchiveEditor::write_to_path` writes, and is useful for writing an
+/// archive with any of the builders.
+///
+/// # Example usage
+///
+/// ```no_run
+/// use ar::{StagedBuilder, Variant, write_atomically};
+/// let mut builder = StagedBuilder::new(Variant::GNU);
+/// builder.append_path("foo.o").unwrap();
+/// write_atomically("libfoo.a", |writer| builder.write(writer)).unwrap();
+/// ```
+pub fn write_atomically<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(BufWriter<File>) -> Result<BufWriter<File>>,
+{
+    let path = path.as_ref();
+    let (temp_path, file) = try!(create_temp_file(path));
+    let result = write(BufWriter::new(file)).and_then(|writer| {
+        let file = try!(writer.into_inner().map_err(io::Error::from));
+        if let Ok(metadata) = fs::metadata(path) {
+            try!(file.set_permissions(metadata.permissions()));
+        }
+        file.sync_all()
+    });
+    match result {
+        Ok(()) => fs::rename(&temp_path, path),
+        Err(error) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(error)
+        }
+    }
+}
+
+/// Creates a new temporary file next to the given path, for
+/// `write_atomically`.  Its name includes the process ID and a counter, and
+/// names that are already taken (whether by another thread, or left behind
+/// by an earlier process with the same ID) are skipped.
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File)> {
+    let name: &OsStr = try!(path.file_name().ok_or_else(|| {
+        let msg = "Given path doesn't have a file name";
+        io::Error::new(ErrorKind::InvalidInput, msg)
+    }));
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let count = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".{}.{}.tmp", process::id(), count));
+        let temp_path = path.with_file_name(temp_name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(ref error) if error.kind() == ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error),
+        }
+    }
+    let msg = "Couldn't find an unused name for a temporary file";
+    Err(io::Error::new(ErrorKind::AlreadyExists, msg))
+}
+
+#[cfg(unix)]
+fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
+    Ok(string.as_bytes().to_vec())
+}
+
+#[cfg(windows)]
+fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
+    let mut bytes = Vec::<u8>::new();
+    for wide in string.encode_wide() {
+        // Little-endian:
+        bytes.push((wide & 0xff) as u8);
+        bytes.push((wide >> 8) as u8);
+    }
+    Ok(bytes)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
+    let utf8: &str = try!(string.to_str().ok_or_else(|| {
+        io::Error::new(ErrorKind::InvalidData, "Non-UTF8 file name")
+    }));
+    Ok(utf8.as_bytes().to_vec())
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> Result<PathBuf> {
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> Result<PathBuf> {
+    let utf8: &str = try!(str::from_utf8(bytes).map_err(|_| {
+        io::Error::new(ErrorKind::InvalidData, "Non-UTF8 file name")
+    }));
+    Ok(PathBuf::from(utf8))
+}
+
+// ========================================================================= //
+
+/// A structure for building GNU-variant archives (with file names stored in a
+/// separate name table).
+///
+/// This structure has methods for building up an archive from scratch into any
+/// arbitrary writer.  Because the name table must appear near the start of the
+/// archive, the complete list of entry identifiers must be given up front.
+/// Identifiers longer than 15 bytes, or containing a slash (which GNU readers
+/// take as the end of a name in an entry header), go in the name table.
+pub struct GnuBuilder<W: Write> {
+    writer: W,
+    short_names: HashSet<Vec<u8>>,
+    name_table: GnuNameTable,
+    metadata: MetadataPolicy,
+    started: bool,
+    symbol_table: Option<SymbolTableWriter<W>>,
+    symbol_collector: Option<SymbolCollector<W>>,
+}
+
+impl<W: Write> GnuBuilder<W> {
+    /// Create a new archive builder with the underlying writer object as the
+    /// destination of all data written.  The `identifiers` parameter must give
+    /// the complete list of entry identifiers that will be included in this
+    /// archive.
+    pub fn new(writer: W, identifiers: Vec<Vec<u8>>) -> GnuBuilder<W> {
+        let name_table =
+            GnuNameTable::new(identifiers.iter().map(|id| id.as_slice()));
+        let short_names = identifiers
+            .into_iter()
+            .filter(|id| !GnuNameTable::needs_entry(id))
+            .collect();
+        GnuBuilder {
+            writer: writer,
+            short_names: short_names,
+            name_table: name_table,
+            metadata: MetadataPolicy::default(),
+            started: false,
+            symbol_table: None,
+            symbol_collector: None,
+        }
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each entry are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Unwrap this archive builder, returning the underlying writer object.
+    /// If this builder has a symbol extractor, the symbol table is inserted
+    /// first (see `set_symbol_extractor`).
+    ///
+    /// Returns an error if this builder has a symbol table that refers to an
+    /// entry that was never appended.
+    pub fn into_inner(mut self) -> Result<W> {
+        if let Some(ref table) = self.symbol_table {
+            try!(table.check_complete());
+        }
+        if let Some(collector) = self.symbol_collector.take() {
+            try!(collector.insert_table(&mut self.writer));
+        }
+        Ok(self.writer)
+    }
+
+    /// Adds a new entry to this archive.
+    pub fn append<R: Read>(&mut self, header: &Header, mut data: R)
+        -> Result<()> {
+        let known = if GnuNameTable::needs_entry(header.identifier()) {
+            self.name_table.long_names.contains_key(header.identifier())
+        } else {
+            self.short_names.contains(header.identifier())
+        };
+        if !known {
+            let msg = format!(
+                "Identifier {:?} was not in the list of identifiers passed \
+                 to GnuBuilder::new()",
+                String::from_utf8_lossy(header.identifier())
+            );
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if !self.started {
+            try!(self.writer.write_all(GLOBAL_HEADER));
+            if let Some(ref mut table) = self.symbol_table {
+                try!(table.write_table(&mut self.writer));
+            }
+            try!(self.name_table.write(&mut self.writer));
+            self.started = true;
+        }
+        if let Some(ref mut table) = self.symbol_table {
+            try!(table.record_entry(&mut self.writer, header.identifier()));
+        }
+        let header = &self.metadata.apply(header);
+        let mut buffered = None;
+        if let Some(ref mut collector) = self.symbol_collector {
+            let mut bytes = Vec::new();
+            try!(data.read_to_end(&mut bytes));
+            try!(collector.record_entry(&mut self.writer, header, &bytes));
+            buffered = Some(bytes);
+        }
+        try!(header.write_gnu(&mut self.writer, &self.name_table.long_names));
+        match buffered {
+            Some(bytes) => {
+                write_entry_data(&mut self.writer, header, bytes.as_slice())
+            }
+            None => write_entry_data(&mut self.writer, header, data),
+        }
+    }
+
     /// Adds a file on the local filesystem to this archive, using the file
     /// name as its identifier.
     pub fn append_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let name: &OsStr = try!(path.as_ref().file_name().ok_or_else(|| {
             let msg = "Given path doesn't have a file name";
-            Error::new(ErrorKind::InvalidInput, msg)
+            io::Error::new(ErrorKind::InvalidInput, msg)
         }));
         let identifier = try!(osstr_to_bytes(name));
         let mut file = try!(File::open(&path));
         self.append_file_id(identifier, &mut file)
     }
 
-    /// Adds a file to this archive, with the given name as its identifier.
-    pub fn append_file(&mut self, name: &[u8], file: &mut File) -> Result<()> {
-        self.append_file_id(name.to_vec(), file)
+    /// Adds a file to this archive, with the given name as its identifier.
+    pub fn append_file(&mut self, name: &[u8], file: &mut File) -> Result<()> {
+        self.append_file_id(name.to_vec(), file)
+    }
+
+    /// Copies an entry from another archive into this archive, with the same
+    /// identifier and metadata.  The identifier is written in this archive's
+    /// own format, whichever variant the entry was read from.
+    pub fn append_entry<R: Read>(&mut self, entry: &mut Entry<R>)
+                                 -> Result<()> {
+        let header = entry.header().clone();
+        self.append(&header, entry)
+    }
+
+    /// Copies every entry that hasn't been read yet from another archive into
+    /// this archive, as with `append_entry`.  The other archive's symbol
+    /// table (if any) is not copied.
+    pub fn append_archive<R: Read>(&mut self, archive: &mut Archive<R>)
+                                   -> Result<()> {
+        copy_entries(archive, |entry| self.append_entry(entry))
+    }
+
+    fn append_file_id(&mut self, id: Vec<u8>, file: &mut File) -> Result<()> {
+        let metadata = try!(file.metadata());
+        let header = Header::from_metadata(id, &metadata);
+        self.append(&header, file)
+    }
+}
+
+impl<W: Write + Seek> GnuBuilder<W> {
+    /// Create a new archive builder that will begin the archive with a GNU
+    /// symbol table.  The `identifiers` parameter is as for `new()`, and each
+    /// element of `symbol_table` is a pair of a symbol name and the identifier
+    /// of the archive entry that defines it.
+    ///
+    /// The symbol table is written (with placeholder offsets) before the
+    /// first entry, and the offset for each entry is filled in when that entry
+    /// is appended, which is why the writer must support seeking.  Since the
+    /// table is written before the size of the archive is known, it always
+    /// has 32-bit offsets, and appending an entry that defines a symbol is an
+    /// error if the entry would start 4 GiB or more into the archive.  For
+    /// larger archives, use `set_symbol_extractor` or a `StagedBuilder`
+    /// instead, which write a 64-bit (`/SYM64/`) symbol table when needed.
+    pub fn new_with_symbol_table(mut writer: W, identifiers: Vec<Vec<u8>>,
+                                 symbol_table: Vec<(Vec<u8>, Vec<u8>)>)
+                                 -> Result<GnuBuilder<W>> {
+        let symbol_table = try!(SymbolTableWriter::new(
+            &mut writer,
+            Variant::GNU,
+            symbol_table,
+        ));
+        let mut builder = GnuBuilder::new(writer, identifiers);
+        builder.symbol_table = symbol_table;
+        Ok(builder)
+    }
+}
+
+impl<W: Read + Write + Seek> GnuBuilder<W> {
+    /// Sets a symbol extractor to run over the data of each entry as it is
+    /// appended, so that the archive gets a symbol table listing the symbols
+    /// that it finds, as with `Builder::set_symbol_extractor`.  The symbol
+    /// table uses 64-bit offsets (`/SYM64/`) if the archive is too large for
+    /// 32-bit ones.
+    pub fn set_symbol_extractor<E>(&mut self, extractor: E) -> Result<()>
+    where
+        E: SymbolExtractor + 'static,
+    {
+        if self.started {
+            let msg = "Cannot set a symbol extractor after entries have been \
+                       written";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if self.symbol_table.is_some() {
+            let msg = "GnuBuilder already has a symbol table";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let collector = try!(SymbolCollector::new(
+            &mut self.writer,
+            Variant::GNU,
+            extractor,
+        ));
+        self.symbol_collector = Some(collector);
+        Ok(())
+    }
+}
+
+// ========================================================================= //
+
+/// A structure for building GNU thin archives.
+///
+/// A thin archive contains only the headers of its entries (along with the
+/// usual name table and, optionally, symbol table); each entry's identifier is
+/// the path of the file holding that entry's data, relative to the directory
+/// containing the archive.  As with `GnuBuilder`, the complete list of entry
+/// identifiers must be given up front.
+pub struct ThinBuilder<W: Write> {
+    writer: W,
+    name_table: GnuNameTable,
+    metadata: MetadataPolicy,
+    started: bool,
+    symbol_table: Option<SymbolTableWriter<W>>,
+}
+
+impl<W: Write> ThinBuilder<W> {
+    /// Create a new thin archive builder with the underlying writer object as
+    /// the destination of all data written.  The `identifiers` parameter must
+    /// give the complete list of entry identifiers (that is, relative paths)
+    /// that will be included in this archive.
+    pub fn new(writer: W, identifiers: Vec<Vec<u8>>) -> ThinBuilder<W> {
+        ThinBuilder {
+            writer: writer,
+            name_table: GnuNameTable::new_thin(
+                identifiers.iter().map(|id| id.as_slice()),
+            ),
+            metadata: MetadataPolicy::default(),
+            started: false,
+            symbol_table: None,
+        }
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each entry are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Unwrap this archive builder, returning the underlying writer object.
+    ///
+    /// Returns an error if this builder has a symbol table that refers to an
+    /// entry that was never appended.
+    pub fn into_inner(self) -> Result<W> {
+        if let Some(ref table) = self.symbol_table {
+            try!(table.check_complete());
+        }
+        Ok(self.writer)
+    }
+
+    /// Adds a new entry to this archive.  Only the header is written; the
+    /// header's identifier should be the path of the file holding the entry's
+    /// data, and its size should be the size of that file.
+    pub fn append(&mut self, header: &Header) -> Result<()> {
+        if !self.name_table.long_names.contains_key(header.identifier()) {
+            let msg = format!(
+                "Identifier {:?} was not in the list of identifiers passed \
+                 to ThinBuilder::new()",
+                String::from_utf8_lossy(header.identifier())
+            );
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        if !self.started {
+            try!(self.writer.write_all(THIN_GLOBAL_HEADER));
+            if let Some(ref mut table) = self.symbol_table {
+                try!(table.write_table(&mut self.writer));
+            }
+            try!(self.name_table.write(&mut self.writer));
+            self.started = true;
+        }
+        if let Some(ref mut table) = self.symbol_table {
+            try!(table.record_entry(&mut self.writer, header.identifier()));
+        }
+        let header = self.metadata.apply(header);
+        header.write_gnu(&mut self.writer, &self.name_table.long_names)
+    }
+
+    /// Adds a reference to a file on the local filesystem to this archive,
+    /// using the path (exactly as given) as its identifier, and taking the
+    /// other header fields from the file's metadata.  The path should thus be
+    /// relative to the directory that will contain the archive.
+    pub fn append_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let identifier = try!(osstr_to_bytes(path.as_ref().as_os_str()));
+        let metadata = try!(fs::metadata(&path));
+        self.append(&Header::from_metadata(identifier, &metadata))
+    }
+
+    /// Adds an entry from another archive to this archive, with the same
+    /// identifier and metadata.  As with `append`, only the header is
+    /// written, so the identifier must be the path of a file holding the
+    /// entry's data.
+    pub fn append_entry<R: Read>(&mut self, entry: &mut Entry<R>)
+                                 -> Result<()> {
+        self.append(entry.header())
+    }
+
+    /// Adds every entry that hasn't been read yet from another archive to
+    /// this archive, as with `append_entry`.  The other archive's symbol
+    /// table (if any) is not copied.
+    pub fn append_archive<R: Read>(&mut self, archive: &mut Archive<R>)
+                                   -> Result<()> {
+        copy_entries(archive, |entry| self.append_entry(entry))
+    }
+}
+
+impl<W: Write + Seek> ThinBuilder<W> {
+    /// Create a new thin archive builder that will begin the archive with a
+    /// GNU symbol table.  The `identifiers` parameter is as for `new()`, and
+    /// each element of `symbol_table` is a pair of a symbol name and the
+    /// identifier of the archive entry that defines it.
+    ///
+    /// As with `GnuBuilder::new_with_symbol_table`, the symbol table always
+    /// has 32-bit offsets, and appending an entry that defines a symbol is an
+    /// error if its header would start 4 GiB or more into the archive.
+    pub fn new_with_symbol_table(mut writer: W, identifiers: Vec<Vec<u8>>,
+                                 symbol_table: Vec<(Vec<u8>, Vec<u8>)>)
+                                 -> Result<ThinBuilder<W>> {
+        let symbol_table = try!(SymbolTableWriter::new(
+            &mut writer,
+            Variant::GNU,
+            symbol_table,
+        ));
+        let mut builder = ThinBuilder::new(writer, identifiers);
+        builder.symbol_table = symbol_table;
+        Ok(builder)
+    }
+}
+
+// ========================================================================= //
+
+/// A structure for building AIX big archives.
+///
+/// A big archive ends with a table of its members (followed by any global
+/// symbol tables), and begins with a fixed-length header that records where
+/// those tables are.  That header is filled in by `into_inner()`, once the
+/// whole archive has been written, which is why the writer must support
+/// seeking.
+pub struct AixBuilder<W: Write + Seek> {
+    writer: W,
+    started: bool,
+    archive_start: u64,
+    // The identifier and header offset of each member appended so far.
+    members: Vec<(Vec<u8>, u64)>,
+    // The offset at which the next member's header will be written.
+    next_member: u64,
+    symbols: Vec<(Vec<u8>, Vec<u8>)>,
+    symbols_64: Vec<(Vec<u8>, Vec<u8>)>,
+    metadata: MetadataPolicy,
+}
+
+impl<W: Write + Seek> AixBuilder<W> {
+    /// Create a new AIX big archive builder with the underlying writer object
+    /// as the destination of all data written.
+    pub fn new(writer: W) -> AixBuilder<W> {
+        AixBuilder {
+            writer: writer,
+            started: false,
+            archive_start: 0,
+            members: Vec::new(),
+            next_member: AIX_BIG_FIXED_HEADER_LEN as u64,
+            symbols: Vec::new(),
+            symbols_64: Vec::new(),
+            metadata: MetadataPolicy::default(),
+        }
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each entry are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Finishes writing the archive (including its member table and symbol
+    /// tables), and returns the underlying writer object.
+    ///
+    /// Returns an error if a symbol refers to an entry that was never
+    /// appended.
+    pub fn into_inner(mut self) -> Result<W> {
+        try!(self.start_if_necessary());
+        let mut file_header = AixFileHeader::default();
+        if let Some(&(_, last_member)) = self.members.last() {
+            file_header.first_member = AIX_BIG_FIXED_HEADER_LEN as u64;
+            file_header.last_member = last_member;
+        }
+        // The member table and symbol tables are written after the last
+        // member, in this order, and are linked together like members.
+        let tables = [
+            if self.members.is_empty() {
+                None
+            } else {
+                Some(try!(self.member_table()))
+            },
+            try!(self.symbol_table(&self.symbols)),
+            try!(self.symbol_table(&self.symbols_64)),
+        ];
+        let mut offsets = [0; 3];
+        let mut end = self.next_member;
+        for (index, table) in tables.iter().enumerate() {
+            if let Some(ref data) = *table {
+                offsets[index] = end;
+                let size = data.len() as u64;
+                end += aix_header_len(0) + size + (size % 2);
+            }
+        }
+        let mut prev = file_header.last_member;
+        for (index, table) in tables.iter().enumerate() {
+            if let Some(ref data) = *table {
+                let next = offsets[index + 1..]
+                    .iter()
+                    .cloned()
+                    .find(|&offset| offset != 0)
+                    .unwrap_or(0);
+                let header = Header::new(Vec::new(), data.len() as u64);
+                try!(header.write_aix(&mut self.writer, next, prev));
+                try!(write_entry_data(
+                    &mut self.writer,
+                    &header,
+                    data.as_slice(),
+                ));
+                prev = offsets[index];
+            }
+        }
+        file_header.member_table = offsets[0];
+        file_header.symbol_table = offsets[1];
+        file_header.symbol_table_64 = offsets[2];
+        try!(self.writer.seek(SeekFrom::Start(self.archive_start)));
+        try!(file_header.write(&mut self.writer));
+        try!(self.writer.seek(SeekFrom::Start(self.archive_start + end)));
+        Ok(self.writer)
+    }
+
+    /// Adds a new entry to this archive.
+    pub fn append<R: Read>(&mut self, header: &Header, data: R)
+        -> Result<()> {
+        try!(self.start_if_necessary());
+        let header = &self.metadata.apply(header);
+        let offset = self.next_member;
+        let prev = self.members.last().map_or(0, |&(_, prev)| prev);
+        let size = header.size();
+        let next = offset + aix_header_len(header.identifier().len()) + size +
+            (size % 2);
+        try!(header.write_aix(&mut self.writer, next, prev));
+        try!(write_entry_data(&mut self.writer, header, data));
+        self.members.push((header.identifier().to_vec(), offset));
+        self.next_member = next;
+        Ok(())
+    }
+
+    /// Adds a file on the local filesystem to this archive, using the file
+    /// name as its identifier.
+    pub fn append_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let name: &OsStr = try!(path.as_ref().file_name().ok_or_else(|| {
+            let msg = "Given path doesn't have a file name";
+            io::Error::new(ErrorKind::InvalidInput, msg)
+        }));
+        let identifier = try!(osstr_to_bytes(name));
+        let mut file = try!(File::open(&path));
+        self.append_file_id(identifier, &mut file)
+    }
+
+    /// Adds a file to this archive, with the given name as its identifier.
+    pub fn append_file(&mut self, name: &[u8], file: &mut File) -> Result<()> {
+        self.append_file_id(name.to_vec(), file)
+    }
+
+    /// Copies an entry from another archive into this archive, with the same
+    /// identifier and metadata.
+    pub fn append_entry<R: Read>(&mut self, entry: &mut Entry<R>)
+                                 -> Result<()> {
+        let header = entry.header().clone();
+        self.append(&header, entry)
+    }
+
+    /// Copies every entry that hasn't been read yet from another archive into
+    /// this archive, as with `append_entry`.  The other archive's symbol
+    /// table (if any) is not copied.
+    pub fn append_archive<R: Read>(&mut self, archive: &mut Archive<R>)
+                                   -> Result<()> {
+        copy_entries(archive, |entry| self.append_entry(entry))
+    }
+
+    fn append_file_id(&mut self, id: Vec<u8>, file: &mut File) -> Result<()> {
+        let metadata = try!(file.metadata());
+        let header = Header::from_metadata(id, &metadata);
+        self.append(&header, file)
+    }
+
+    /// Adds a symbol to this archive's global symbol table for 32-bit
+    /// objects, defined by the entry with the given identifier.  Symbols are
+    /// written in the order they are added.
+    pub fn add_symbol(&mut self, symbol: Vec<u8>, identifier: Vec<u8>) {
+        self.symbols.push((symbol, identifier));
+    }
+
+    /// Adds a symbol to this archive's global symbol table for 64-bit
+    /// objects, defined by the entry with the given identifier.  Symbols are
+    /// written in the order they are added.
+    pub fn add_symbol_64(&mut self, symbol: Vec<u8>, identifier: Vec<u8>) {
+        self.symbols_64.push((symbol, identifier));
+    }
+
+    /// Writes a placeholder for the fixed-length header, if we haven't yet.
+    fn start_if_necessary(&mut self) -> Result<()> {
+        if !self.started {
+            self.archive_start = try!(self.writer.stream_position());
+            try!(AixFileHeader::default().write(&mut self.writer));
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the data for the member table, which lists the offset and
+    /// identifier of each member.
+    fn member_table(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        try!(write!(data, "{:<20}", self.members.len()));
+        for &(_, offset) in self.members.iter() {
+            try!(write!(data, "{:<20}", offset));
+        }
+        for (identifier, _) in self.members.iter() {
+            data.extend_from_slice(identifier);
+            data.push(0);
+        }
+        Ok(data)
+    }
+
+    /// Returns the data for a global symbol table holding the given symbols,
+    /// or `None` if there are none.
+    fn symbol_table(&self, symbols: &[(Vec<u8>, Vec<u8>)])
+                    -> Result<Option<Vec<u8>>> {
+        if symbols.is_empty() {
+            return Ok(None);
+        }
+        let mut member_offsets = HashMap::<&[u8], u64>::new();
+        for (identifier, offset) in self.members.iter() {
+            member_offsets.entry(identifier).or_insert(*offset);
+        }
+        let mut data = Vec::new();
+        try!(data.write_u64::<BigEndian>(symbols.len() as u64));
+        for (_, identifier) in symbols.iter() {
+            match member_offsets.get(identifier.as_slice()) {
+                Some(&offset) => try!(data.write_u64::<BigEndian>(offset)),
+                None => {
+                    let msg = format!(
+                        "Symbol table refers to entry {:?}, which was never \
+                         appended",
+                        String::from_utf8_lossy(identifier)
+                    );
+                    return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+                }
+            }
+        }
+        for (symbol, _) in symbols.iter() {
+            data.extend_from_slice(symbol);
+            data.push(0);
+        }
+        Ok(Some(data))
+    }
+}
+
+// ========================================================================= //
+
+/// A source of data for an entry in a `StagedBuilder`.
+enum StagedData<'a> {
+    Path(PathBuf),
+    Reader(Box<dyn Read + 'a>),
+    Bytes(Vec<u8>),
+    // The entry with this index in the archive that an ArchiveEditor is
+    // editing.
+    Entry(usize),
+}
+
+/// The entry that defines a symbol in a `StagedBuilder`.
+enum DefiningEntry {
+    // The first entry with this identifier.
+    Identifier(Vec<u8>),
+    // The entry with this index (which lets entries with the same identifier
+    // define different symbols).
+    Index(usize),
+}
+
+/// A structure for building archives in two passes.
+///
+/// Unlike `Builder` and `GnuBuilder`, which write each entry as soon as it is
+/// appended, this structure first collects the headers and data sources for
+/// all entries, and then computes the final layout of the archive before
+/// writing it out.  This allows it to write a symbol table (which must come
+/// before the entries, but contains their offsets) to any writer, without
+/// needing to seek.  Entry data is still streamed into the writer rather than
+/// held in memory (unless it was given as a buffer in the first place).
+pub struct StagedBuilder<'a> {
+    variant: Variant,
+    entries: Vec<(Header, StagedData<'a>)>,
+    symbols: Vec<(Vec<u8>, DefiningEntry)>,
+    sorted_symbols: bool,
+    // Whether a BSD symbol table should have 64-bit offsets even if the
+    // archive is small enough for 32-bit ones.
+    wide_symbols: bool,
+    thin: bool,
+    metadata: MetadataPolicy,
+    extractor: Option<Box<dyn SymbolExtractor>>,
+}
+
+impl<'a> StagedBuilder<'a> {
+    /// Create a new staged builder that will write an archive of the given
+    /// variant.
+    pub fn new(variant: Variant) -> StagedBuilder<'a> {
+        StagedBuilder {
+            variant: variant,
+            entries: Vec::new(),
+            symbols: Vec::new(),
+            sorted_symbols: false,
+            wide_symbols: false,
+            thin: false,
+            metadata: MetadataPolicy::default(),
+            extractor: None,
+        }
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each entry are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Adds a new entry to this archive, whose data will be read from the
+    /// given reader when the archive is written.
+    pub fn append<R: Read + 'a>(&mut self, header: Header, data: R) {
+        self.entries.push((header, StagedData::Reader(Box::new(data))));
+    }
+
+    /// Adds a new entry to this archive, with the given data.  The header's
+    /// size is set to the length of the data.
+    pub fn append_bytes(&mut self, mut header: Header, data: Vec<u8>) {
+        header.set_size(data.len() as u64);
+        self.entries.push((header, StagedData::Bytes(data)));
+    }
+
+    /// Adds a file on the local filesystem to this archive, using the file
+    /// name as its identifier.  The file's metadata is read immediately, but
+    /// the file won't be opened again until the archive is written.
+    pub fn append_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let header = try!(header_for_path(path));
+        self.entries.push((header, StagedData::Path(path.to_path_buf())));
+        Ok(())
+    }
+
+    /// Copies an entry from another archive into this archive, with the same
+    /// identifier and metadata.  Since the entry borrows the other archive,
+    /// its data is read into memory now rather than when the archive is
+    /// written.
+    pub fn append_entry<R: Read>(&mut self, entry: &mut Entry<R>)
+                                 -> Result<()> {
+        let mut data = Vec::new();
+        try!(entry.read_to_end(&mut data));
+        let header = entry.header().clone();
+        self.append_bytes(header, data);
+        Ok(())
+    }
+
+    /// Copies every entry that hasn't been read yet from another archive into
+    /// this archive, as with `append_entry`.  The other archive's symbol
+    /// table (if any) is not copied.
+    pub fn append_archive<R: Read>(&mut self, archive: &mut Archive<R>)
+                                   -> Result<()> {
+        copy_entries(archive, |entry| self.append_entry(entry))
+    }
+
+    /// Adds a symbol to this archive's symbol table, defined by the entry
+    /// with the given identifier.  Symbols are written in the order they are
+    /// added (unless `set_sorted_symbol_table` is used).
+    pub fn add_symbol(&mut self, symbol: Vec<u8>, identifier: Vec<u8>) {
+        self.symbols
+            .push((symbol, DefiningEntry::Identifier(identifier)));
+    }
+
+    /// Adds a symbol to this archive's symbol table, defined by the entry
+    /// with the given index (counting from zero, in the order that entries
+    /// were appended).  Unlike `add_symbol`, this can attribute symbols to
+    /// any of several entries that share the same identifier.
+    pub fn add_symbol_for_entry(&mut self, symbol: Vec<u8>, index: usize) {
+        self.symbols.push((symbol, DefiningEntry::Index(index)));
+    }
+
+    /// Sets whether a BSD-variant archive's symbol table should be written
+    /// as a `__.SYMDEF SORTED` table, with its symbols sorted by name (as
+    /// Apple's `ranlib` does by default), rather than as a `__.SYMDEF` table.
+    /// This is off by default, and has no effect on other variants.
+    pub fn set_sorted_symbol_table(&mut self, sorted: bool) {
+        self.sorted_symbols = sorted;
+    }
+
+    /// Sets whether to write a GNU thin archive (see `ThinBuilder`), which
+    /// holds only the header of each entry.  Each entry's identifier should
+    /// then be the path of the file holding its data, relative to the
+    /// directory that will contain the archive; its data is only read if a
+    /// symbol extractor is set.  This is off by default, and only the GNU
+    /// variant can be written this way.
+    pub fn set_thin(&mut self, thin: bool) { self.thin = thin; }
+
+    /// Sets a symbol extractor (such as `object::ObjectSymbolExtractor`) to
+    /// run over the data of each entry when the archive is written, adding
+    /// the symbols that it finds to the symbol table (before any added with
+    /// `add_symbol`).  This means that the data of every entry is read an
+    /// extra time (so any that was given as a reader is buffered in memory).
+    pub fn set_symbol_extractor<E>(&mut self, extractor: E)
+    where
+        E: SymbolExtractor + 'static,
+    {
+        self.extractor = Some(Box::new(extractor));
+    }
+
+    /// Writes the complete archive into the given writer, returning the
+    /// writer when done.
+    ///
+    /// If the archive is too large for its symbol table to use 32-bit
+    /// offsets, a 64-bit (`/SYM64/` or `__.SYMDEF_64`) symbol table is
+    /// written instead.  AIX big archives can't be written this way (use
+    /// `AixBuilder` instead), and neither can COFF archives.  For the common
+    /// variant, it is an error for any entry's identifier to need the BSD
+    /// extension.
+    pub fn write<W: Write>(mut self, writer: W) -> Result<W> {
+        // Only an ArchiveEditor stages entries of an existing archive.
+        try!(self.extract_symbols(|_, _| unreachable!()));
+        self.write_copying_entries(writer, |_, _, _| unreachable!())
+    }
+
+    /// Adds symbols defined by `StagedData::Entry` entries, given by the
+    /// index they refer to.  Symbols whose entry isn't staged are dropped.
+    fn add_entry_symbols<I>(&mut self, symbols: I)
+    where
+        I: IntoIterator<Item = (Vec<u8>, usize)>,
+    {
+        let mut members = HashMap::<usize, usize>::new();
+        for (member, entry) in self.entries.iter().enumerate() {
+            if let StagedData::Entry(index) = entry.1 {
+                members.entry(index).or_insert(member);
+            }
+        }
+        for (symbol, index) in symbols {
+            if let Some(&member) = members.get(&index) {
+                let entry = DefiningEntry::Index(member);
+                self.symbols.push((symbol, entry));
+            }
+        }
+    }
+
+    /// Runs the symbol extractor (if any) over every entry, putting the
+    /// symbols that it finds at the start of the symbol table.  The
+    /// `read_entry` function reads the data of a `StagedData::Entry`.
+    fn extract_symbols<F>(&mut self, mut read_entry: F) -> Result<()>
+    where
+        F: FnMut(usize, &mut Vec<u8>) -> Result<usize>,
+    {
+        let mut extractor = match self.extractor.take() {
+            Some(extractor) => extractor,
+            None => return Ok(()),
+        };
+        let mut symbols = Vec::new();
+        for index in 0..self.entries.len() {
+            let result =
+                self.entry_symbols(&mut *extractor, index, &mut read_entry);
+            let entry_symbols = try!(result.map_err(|error| {
+                let identifier = self.entries[index].0.identifier();
+                let msg = format!(
+                    "{}: {}",
+                    String::from_utf8_lossy(identifier),
+                    error
+                );
+                io::Error::new(error.kind(), msg)
+            }));
+            for symbol in entry_symbols.into_iter() {
+                symbols.push((symbol, DefiningEntry::Index(index)));
+            }
+        }
+        symbols.append(&mut self.symbols);
+        self.symbols = symbols;
+        Ok(())
+    }
+
+    /// Returns the symbols defined by the entry with the given index,
+    /// buffering its data first if it was given as a reader.
+    fn entry_symbols<F>(&mut self, extractor: &mut dyn SymbolExtractor,
+                        index: usize, read_entry: &mut F)
+                        -> Result<Vec<Vec<u8>>>
+    where
+        F: FnMut(usize, &mut Vec<u8>) -> Result<usize>,
+    {
+        let (ref header, ref mut staged) = self.entries[index];
+        let mut data = Vec::new();
+        match *staged {
+            StagedData::Path(ref path) => {
+                let mut file = try!(File::open(path));
+                try!(file.read_to_end(&mut data));
+            }
+            StagedData::Reader(ref mut reader) => {
+                try!(reader.read_to_end(&mut data));
+            }
+            StagedData::Bytes(ref bytes) => {
+                return extractor.extract_symbols(header, bytes);
+            }
+            StagedData::Entry(entry_index) => {
+                try!(read_entry(entry_index, &mut data));
+            }
+        }
+        let symbols = extractor.extract_symbols(header, &data);
+        if let StagedData::Reader(_) = *staged {
+            *staged = StagedData::Bytes(data);
+        }
+        symbols
+    }
+
+    /// Writes the complete archive, using `copy_entry` to write the data for
+    /// each `StagedData::Entry`.
+    fn write_copying_entries<W, F>(self, mut writer: W, mut copy_entry: F)
+                                   -> Result<W>
+    where
+        W: Write,
+        F: FnMut(&mut W, &Header, usize) -> Result<()>,
+    {
+        match self.variant {
+            Variant::AIX => {
+                let msg = "StagedBuilder cannot write AIX big archives (use \
+                           AixBuilder instead)";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            Variant::COFF => {
+                let msg = "StagedBuilder cannot write COFF archives";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            Variant::Common => {
+                for (header, _) in self.entries.iter() {
+                    try!(header.check_identifier(Variant::Common));
+                }
+            }
+            Variant::BSD | Variant::GNU => {}
+        }
+        if self.thin && self.variant != Variant::GNU {
+            let msg = "Only GNU-variant archives can be thin";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let identifiers =
+            self.entries.iter().map(|entry| entry.0.identifier());
+        let name_table = if self.thin {
+            GnuNameTable::new_thin(identifiers)
+        } else if self.variant == Variant::GNU {
+            GnuNameTable::new(identifiers)
+        } else {
+            GnuNameTable::new(None.into_iter())
+        };
+        let (format, symbol_table) =
+            try!(self.layout_symbol_table(&name_table));
+        if self.thin {
+            try!(writer.write_all(THIN_GLOBAL_HEADER));
+        } else {
+            try!(writer.write_all(GLOBAL_HEADER));
+        }
+        if !symbol_table.is_empty() {
+            try!(write_symbol_table(&mut writer, format, &symbol_table));
+        }
+        try!(name_table.write(&mut writer));
+        let metadata = self.metadata;
+        for (header, data) in self.entries.into_iter() {
+            let header = metadata.apply(&header);
+            if self.variant == Variant::GNU {
+                try!(header.write_gnu(&mut writer, &name_table.long_names));
+            } else {
+                try!(header.write(&mut writer));
+            }
+            if self.thin {
+                continue;
+            }
+            match data {
+                StagedData::Path(path) => {
+                    let file = try!(File::open(&path));
+                    try!(write_entry_data(&mut writer, &header, file));
+                }
+                StagedData::Reader(reader) => {
+                    try!(write_entry_data(&mut writer, &header, reader));
+                }
+                StagedData::Bytes(bytes) => {
+                    try!(write_entry_data(
+                        &mut writer,
+                        &header,
+                        bytes.as_slice(),
+                    ));
+                }
+                StagedData::Entry(index) => {
+                    try!(copy_entry(&mut writer, &header, index));
+                }
+            }
+        }
+        Ok(writer)
+    }
+
+    /// Chooses a format for the symbol table and computes the offset of each
+    /// symbol's defining entry, given the layout of the archive.
+    fn layout_symbol_table(&self, name_table: &GnuNameTable)
+                           -> Result<(SymbolTableFormat, SymbolTable)> {
+        let mut format = SymbolTableFormat::for_variant(self.variant);
+        if format.is_bsd() && self.wide_symbols {
+            format = format.to_64_bit();
+        }
+        if self.sorted_symbols {
+            format = format.to_sorted();
+        }
+        if self.symbols.is_empty() {
+            return Ok((format, Vec::new()));
+        }
+        if self.variant == Variant::Common {
+            let msg = "Common-variant archives cannot have a symbol table";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let mut symbol_table: Vec<(Vec<u8>, u64)> = self.symbols
+            .iter()
+            .map(|(symbol, _)| (symbol.clone(), 0))
+            .collect();
+        try!(self.fill_symbol_offsets(format, name_table, &mut symbol_table));
+        if !format.is_64_bit() &&
+            symbol_table.iter().any(|&(_, offset)| offset > u32::MAX as u64)
+        {
+            // The archive is too large for 32-bit offsets, so switch to a
+            // 64-bit symbol table (which changes the layout slightly).
+            format = format.to_64_bit();
+            try!(self.fill_symbol_offsets(
+                format,
+                name_table,
+                &mut symbol_table,
+            ));
+        }
+        if format == SymbolTableFormat::BsdSorted ||
+            format == SymbolTableFormat::Bsd64Sorted
+        {
+            symbol_table.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok((format, symbol_table))
+    }
+
+    fn fill_symbol_offsets(&self, format: SymbolTableFormat,
+                           name_table: &GnuNameTable,
+                           symbol_table: &mut [(Vec<u8>, u64)])
+                           -> Result<()> {
+        let mut offset = GLOBAL_HEADER_LEN as u64 +
+            symbol_table_entry_len(format, symbol_table) +
+            name_table.entry_len();
+        let mut entry_offsets = Vec::with_capacity(self.entries.len());
+        let mut offsets_by_identifier = HashMap::<&[u8], u64>::new();
+        for (header, _) in self.entries.iter() {
+            entry_offsets.push(offset);
+            offsets_by_identifier.entry(header.identifier()).or_insert(offset);
+            offset += header.written_len(self.variant);
+            if !self.thin {
+                let size = header.size();
+                offset += size + (size % 2);
+            }
+        }
+        for (index, (_, entry)) in self.symbols.iter().enumerate() {
+            let identifier = match *entry {
+                DefiningEntry::Identifier(ref identifier) => identifier,
+                DefiningEntry::Index(entry_index) => {
+                    match entry_offsets.get(entry_index) {
+                        Some(&offset) => symbol_table[index].1 = offset,
+                        None => {
+                            let msg = format!(
+                                "Symbol table refers to entry index {}, but \
+                                 only {} entries were appended",
+                                entry_index,
+                                entry_offsets.len()
+                            );
+                            let kind = ErrorKind::InvalidInput;
+                            return Err(io::Error::new(kind, msg));
+                        }
+                    }
+                    continue;
+                }
+            };
+            match offsets_by_identifier.get(identifier.as_slice()) {
+                Some(&offset) => symbol_table[index].1 = offset,
+                None => {
+                    let msg = format!(
+                        "Symbol table refers to entry {:?}, which was never \
+                         appended",
+                        String::from_utf8_lossy(identifier)
+                    );
+                    return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ========================================================================= //
+
+/// A structure for editing an existing archive, in the manner of `ar d`
+/// (delete), `ar m` (move), and `ar r`/`ar u` (replace).
+///
+/// Edits are made to a list of the archive's members, and nothing is written
+/// until `write` or `write_to_path` is called, at which point a complete new
+/// archive is written in the same variant as the original, with its GNU name
+/// table and symbol table regenerated.  Members that weren't replaced are
+/// copied from the original archive, and keep the symbols that its symbol
+/// table gave them; symbols defined by new or replacement members can be
+/// added with `add_symbol`.  Alternatively, the editor can build a fresh
+/// symbol table from the members with a `SymbolExtractor` (see
+/// `set_symbol_extractor` and `set_regenerate_symbol_table`).
+///
+/// Thin archives, AIX big archives, and COFF archives can't be edited.
+pub struct ArchiveEditor<'a, R: Read + Seek> {
+    archive: Archive<R>,
+    variant: Variant,
+    members: Vec<(Header, StagedData<'a>)>,
+    symbols: Vec<(Vec<u8>, Vec<u8>)>,
+    extractor: Option<Box<dyn SymbolExtractor>>,
+    sorted_symbols: bool,
+    wide_symbols: bool,
+    only_if_newer: bool,
+    metadata: MetadataPolicy,
+}
+
+impl<'a, R: Read + Seek> ArchiveEditor<'a, R> {
+    /// Create a new editor for the given archive, reading the headers of all
+    /// of its entries.  The original symbol table isn't read until the
+    /// archive is written, and not at all if a symbol extractor is set, so
+    /// an archive whose symbol table is stale or corrupt can still be given
+    /// a fresh one (as `ranlib` does).
+    pub fn new(mut archive: Archive<R>) -> Result<ArchiveEditor<'a, R>> {
+        let count = try!(archive.count_entries());
+        if archive.is_thin() {
+            let msg = "ArchiveEditor cannot edit thin archives";
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let variant = archive.variant();
+        match variant {
+            Variant::AIX => {
+                let msg = "ArchiveEditor cannot edit AIX big archives";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            Variant::COFF => {
+                let msg = "ArchiveEditor cannot edit COFF archives";
+                return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+            }
+            Variant::Common | Variant::BSD | Variant::GNU => {}
+        }
+        let (sorted_symbols, wide_symbols) = match archive.symbol_table_header
+        {
+            Some(ref location) => {
+                let identifier = location.header.identifier();
+                (
+                    identifier == BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID ||
+                        identifier == BSD_SORTED_SYM64_LOOKUP_TABLE_ID,
+                    identifier == BSD_SYM64_LOOKUP_TABLE_ID ||
+                        identifier == BSD_SORTED_SYM64_LOOKUP_TABLE_ID,
+                )
+            }
+            None => (false, false),
+        };
+        let members = (0..count)
+            .map(|index| {
+                let header = archive.entry_headers[index].header.clone();
+                (header, StagedData::Entry(index))
+            })
+            .collect();
+        Ok(ArchiveEditor {
+            archive: archive,
+            variant: variant,
+            members: members,
+            symbols: Vec::new(),
+            extractor: None,
+            sorted_symbols: sorted_symbols,
+            wide_symbols: wide_symbols,
+            only_if_newer: false,
+            metadata: MetadataPolicy::default(),
+        })
+    }
+
+    /// Returns the variant that will be written, which is the variant of the
+    /// archive being edited unless changed with `set_variant`.
+    pub fn variant(&self) -> Variant { self.variant }
+
+    /// Sets the variant to write.  This is mainly useful for giving a
+    /// symbol table to an archive that doesn't use any BSD or GNU extensions
+    /// (and so was read as `Variant::Common`, which can't have one).  As with
+    /// `StagedBuilder`, AIX big archives and COFF archives can't be written.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    /// Returns the headers of the archive's members, in order, as edited so
+    /// far.
+    pub fn headers(&self) -> Vec<&Header> {
+        self.members.iter().map(|member| &member.0).collect()
+    }
+
+    /// Sets whether the `replace` methods should leave an existing member in
+    /// place unless the replacement has a later timestamp (like `ar u`).
+    /// This is off by default.
+    pub fn set_only_if_newer(&mut self, only_if_newer: bool) {
+        self.only_if_newer = only_if_newer;
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each member are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Sets whether a BSD-variant archive's symbol table should be written
+    /// as a sorted `__.SYMDEF SORTED` table (see
+    /// `StagedBuilder::set_sorted_symbol_table`).  This defaults to whether
+    /// the original archive's symbol table was sorted.
+    pub fn set_sorted_symbol_table(&mut self, sorted: bool) {
+        self.sorted_symbols = sorted;
+    }
+
+    /// Removes the first member with the given identifier from the archive.
+    /// Returns an error if there is no such member.
+    pub fn delete(&mut self, identifier: &[u8]) -> Result<()> {
+        let index = try!(self.find(identifier));
+        self.members.remove(index);
+        Ok(())
+    }
+
+    /// Replaces the first member with the same identifier as the given
+    /// header, or adds a new member at the end of the archive if there is no
+    /// such member.  The data will be read from the given reader when the
+    /// archive is written.  Returns false if the existing member was left in
+    /// place because it isn't older than the replacement (see
+    /// `set_only_if_newer`).
+    ///
+    /// The symbols that the original symbol table gave a replaced member are
+    /// dropped, since the new data may not define them.  Unless a symbol
+    /// extractor is set (see `set_symbol_extractor` and
+    /// `set_regenerate_symbol_table`), the replacement defines no symbols
+    /// other than those added with `add_symbol`.
+    pub fn replace<S: Read + 'a>(&mut self, header: Header, data: S) -> bool {
+        self.stage(header, StagedData::Reader(Box::new(data)))
+    }
+
+    /// Like `replace`, but with the given data.  The header's size is set to
+    /// the length of the data.
+    pub fn replace_bytes(&mut self, mut header: Header, data: Vec<u8>)
+                         -> bool {
+        header.set_size(data.len() as u64);
+        self.stage(header, StagedData::Bytes(data))
+    }
+
+    /// Like `replace`, but with a file on the local filesystem, using the
+    /// file name as its identifier.  The file's metadata is read immediately,
+    /// but the file won't be opened again until the archive is written.  As
+    /// with `replace`, the replaced member's symbols are dropped unless a
+    /// symbol extractor is set.
+    pub fn replace_path<P: AsRef<Path>>(&mut self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+        let header = try!(header_for_path(path));
+        Ok(self.stage(header, StagedData::Path(path.to_path_buf())))
+    }
+
+    fn stage(&mut self, header: Header, data: StagedData<'a>) -> bool {
+        let existing = self.members
+            .iter()
+            .position(|member| member.0.identifier() == header.identifier());
+        match existing {
+            Some(index) => {
+                if self.only_if_newer &&
+                    header.mtime() <= self.members[index].0.mtime()
+                {
+                    return false;
+                }
+                self.members[index] = (header, data);
+            }
+            None => self.members.push((header, data)),
+        }
+        true
+    }
+
+    /// Moves the first member with the given identifier to just before the
+    /// first member identified by `before`.
+    pub fn move_before(&mut self, identifier: &[u8], before: &[u8])
+                       -> Result<()> {
+        self.move_member(identifier, before, 0)
+    }
+
+    /// Moves the first member with the given identifier to just after the
+    /// first member identified by `after`.
+    pub fn move_after(&mut self, identifier: &[u8], after: &[u8])
+                      -> Result<()> {
+        self.move_member(identifier, after, 1)
+    }
+
+    /// Moves the first member with the given identifier to the end of the
+    /// archive (which is what `ar m` does without a position).
+    pub fn move_to_end(&mut self, identifier: &[u8]) -> Result<()> {
+        let index = try!(self.find(identifier));
+        let member = self.members.remove(index);
+        self.members.push(member);
+        Ok(())
+    }
+
+    fn move_member(&mut self, identifier: &[u8], anchor: &[u8],
+                   offset: usize)
+                   -> Result<()> {
+        let index = try!(self.find(identifier));
+        try!(self.find(anchor));
+        if identifier == anchor {
+            return Ok(());
+        }
+        let member = self.members.remove(index);
+        let anchor_index = try!(self.find(anchor));
+        self.members.insert(anchor_index + offset, member);
+        Ok(())
+    }
+
+    fn find(&self, identifier: &[u8]) -> Result<usize> {
+        let index = self.members
+            .iter()
+            .position(|member| member.0.identifier() == identifier);
+        index.ok_or_else(|| {
+            let msg = format!(
+                "No member named {:?} in the archive",
+                String::from_utf8_lossy(identifier)
+            );
+            io::Error::new(ErrorKind::NotFound, msg)
+        })
+    }
+
+    /// Adds a symbol to the archive's symbol table, defined by the member
+    /// with the given identifier.  These symbols are written after the ones
+    /// kept from the original symbol table.
+    pub fn add_symbol(&mut self, symbol: Vec<u8>, identifier: Vec<u8>) {
+        self.symbols.push((symbol, identifier));
+    }
+
+    /// Sets a symbol extractor to replace the archive's symbol table, when it
+    /// is written, with one listing the symbols that it finds in each member
+    /// (like `ranlib`).  This discards the original symbol table (but not
+    /// symbols added with `add_symbol`), and means that the data of every
+    /// member is read an extra time (so any that was given as a reader is
+    /// buffered in memory).
+    pub fn set_symbol_extractor<E>(&mut self, extractor: E)
+    where
+        E: SymbolExtractor + 'static,
+    {
+        self.extractor = Some(Box::new(extractor));
+    }
+
+    /// Sets whether to replace the archive's symbol table with one listing
+    /// the global symbols defined by each member, as found by
+    /// `object::ObjectSymbolExtractor` (see `set_symbol_extractor`).  Members
+    /// that aren't object files in a supported format define no symbols.
+    /// Passing false removes any symbol extractor, which is the default.
+    ///
+    /// This method is only available when the `object` feature is enabled.
+    #[cfg(feature = "object")]
+    pub fn set_regenerate_symbol_table(&mut self, regenerate: bool) {
+        self.extractor = if regenerate {
+            Some(Box::new(object::ObjectSymbolExtractor))
+        } else {
+            None
+        };
+    }
+
+    /// Writes the edited archive into the given writer, returning the writer
+    /// when done.  A BSD-variant archive whose original symbol table had
+    /// 64-bit offsets (`__.SYMDEF_64`) keeps them.
+    pub fn write<W: Write>(self, writer: W) -> Result<W> {
+        let ArchiveEditor {
+            mut archive,
+            variant,
+            members,
+            symbols,
+            extractor,
+            sorted_symbols,
+            wide_symbols,
+            metadata,
+            ..
+        } = self;
+        let mut builder = StagedBuilder::new(variant);
+        builder.sorted_symbols = sorted_symbols;
+        builder.wide_symbols = wide_symbols;
+        builder.metadata = metadata;
+        builder.entries = members;
+        if extractor.is_none() {
+            let original_symbols = try!(archive.symbol_entries())
+                .map(|(symbol, index)| (symbol.to_vec(), index));
+            builder.add_entry_symbols(original_symbols);
+        }
+        for (symbol, identifier) in symbols.into_iter() {
+            builder.add_symbol(symbol, identifier);
+        }
+        builder.extractor = extractor;
+        try!(builder.extract_symbols(|index, data| {
+            let mut entry = try!(archive.jump_to_entry(index));
+            entry.read_to_end(data)
+        }));
+        builder.write_copying_entries(writer, |writer, header, index| {
+            let entry = try!(archive.jump_to_entry(index));
+            write_entry_data(writer, header, entry)
+        })
+    }
+
+    /// Writes the edited archive to the given path, atomically replacing any
+    /// existing file there (such as the original archive).  The archive is
+    /// first written to a temporary file in the same directory, which is then
+    /// renamed over the destination, so that an error part-way through leaves
+    /// the destination untouched.
+    pub fn write_to_path<P: AsRef<Path>>(self, path: P) -> Result<()> {
+        write_atomically(path.as_ref(), |writer| self.write(writer))
+    }
+}
+
+// ========================================================================= //
+
+/// The GNU name table (`//`) that stores identifiers too long to fit in an
+/// entry header.
+struct GnuNameTable {
+    // Maps each long identifier to its offset within the name table.
+    long_names: HashMap<Vec<u8>, usize>,
+    size: usize,
+    needs_padding: bool,
+}
+
+impl GnuNameTable {
+    /// Creates a name table holding those of the given identifiers that
+    /// can't be written in an entry header (see `needs_entry`).
+    fn new<'a, I>(identifiers: I) -> GnuNameTable
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        GnuNameTable::with_names(
+            identifiers.filter(|id| GnuNameTable::needs_entry(id)),
+        )
+    }
+
+    /// Creates a name table holding all of the given identifiers, as is done
+    /// for thin archives.
+    fn new_thin<'a, I>(identifiers: I) -> GnuNameTable
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        GnuNameTable::with_names(identifiers)
+    }
+
+    /// Returns true if the given identifier must be stored in the name table
+    /// of a (non-thin) GNU archive, because it is too long to fit in an entry
+    /// header, or because it contains a slash (which GNU readers take as the
+    /// end of a name in an entry header).
+    fn needs_entry(identifier: &[u8]) -> bool {
+        identifier.len() > 15 || identifier.contains(&b'/')
+    }
+
+    fn with_names<'a, I>(identifiers: I) -> GnuNameTable
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        let mut long_names = HashMap::<Vec<u8>, usize>::new();
+        let mut size: usize = 0;
+        for identifier in identifiers {
+            if !long_names.contains_key(identifier) {
+                long_names.insert(identifier.to_vec(), size);
+                size += identifier.len() + 2;
+            }
+        }
+        let needs_padding = size % 2 != 0;
+        if needs_padding {
+            size += 1;
+        }
+        GnuNameTable {
+            long_names: long_names,
+            size: size,
+            needs_padding: needs_padding,
+        }
+    }
+
+    /// Returns the total length of the name table entry (including its
+    /// header), which is zero if no name table is needed.
+    fn entry_len(&self) -> u64 {
+        if self.long_names.is_empty() {
+            0
+        } else {
+            (ENTRY_HEADER_LEN + self.size) as u64
+        }
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self.long_names.is_empty() {
+            return Ok(());
+        }
+        try!(write!(
+            writer,
+            "{:<48}{:<10}`\n",
+            str::from_utf8(GNU_NAME_TABLE_ID).unwrap(),
+            self.size
+        ));
+        let mut entries: Vec<(usize, &[u8])> = self.long_names
+            .iter()
+            .map(|(id, &start)| (start, id.as_slice()))
+            .collect();
+        entries.sort();
+        for (_, id) in entries.into_iter() {
+            try!(writer.write_all(id));
+            try!(writer.write_all(b"/\n"));
+        }
+        if self.needs_padding {
+            try!(writer.write_all(b"\n"));
+        }
+        Ok(())
+    }
+}
+
+// ========================================================================= //
+
+/// A list of symbol names, each paired with the offset (from the start of the
+/// archive) of the header of the entry that defines it.
+type SymbolTable = Vec<(Vec<u8>, u64)>;
+
+/// The on-disk layouts of symbol tables that this crate can write.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SymbolTableFormat {
+    /// The BSD `__.SYMDEF` table, with 32-bit little-endian offsets.
+    Bsd,
+    /// The BSD `__.SYMDEF SORTED` table, which is laid out like `Bsd`, but
+    /// whose symbols are sorted by name.
+    BsdSorted,
+    /// The BSD `__.SYMDEF_64` table, with 64-bit little-endian offsets.
+    Bsd64,
+    /// The BSD `__.SYMDEF_64 SORTED` table, which is laid out like `Bsd64`,
+    /// but whose symbols are sorted by name.
+    Bsd64Sorted,
+    /// The GNU `/` table, with 32-bit big-endian offsets.
+    Gnu,
+    /// The GNU `/SYM64/` table, with 64-bit big-endian offsets.
+    Gnu64,
+}
+
+impl SymbolTableFormat {
+    /// Returns the 32-bit symbol table format for the given variant (which
+    /// must be `Variant::BSD` or `Variant::GNU`).
+    fn for_variant(variant: Variant) -> SymbolTableFormat {
+        if variant == Variant::GNU {
+            SymbolTableFormat::Gnu
+        } else {
+            SymbolTableFormat::Bsd
+        }
+    }
+
+    /// Returns the identifier of a symbol table entry in this format.
+    fn identifier(self) -> &'static [u8] {
+        match self {
+            SymbolTableFormat::Bsd => BSD_SYMBOL_LOOKUP_TABLE_ID,
+            SymbolTableFormat::BsdSorted => BSD_SORTED_SYMBOL_LOOKUP_TABLE_ID,
+            SymbolTableFormat::Bsd64 => BSD_SYM64_LOOKUP_TABLE_ID,
+            SymbolTableFormat::Bsd64Sorted => {
+                BSD_SORTED_SYM64_LOOKUP_TABLE_ID
+            }
+            SymbolTableFormat::Gnu => GNU_SYMBOL_LOOKUP_TABLE_ID,
+            SymbolTableFormat::Gnu64 => GNU_SYM64_LOOKUP_TABLE_ID,
+        }
+    }
+
+    /// Returns true for the BSD formats.
+    fn is_bsd(self) -> bool {
+        self != SymbolTableFormat::Gnu && self != SymbolTableFormat::Gnu64
+    }
+
+    /// Returns true for the formats with 64-bit offsets.
+    fn is_64_bit(self) -> bool {
+        self == SymbolTableFormat::Bsd64 ||
+            self == SymbolTableFormat::Bsd64Sorted ||
+            self == SymbolTableFormat::Gnu64
+    }
+
+    /// Returns the format with 64-bit offsets that corresponds to this one.
+    fn to_64_bit(self) -> SymbolTableFormat {
+        match self {
+            SymbolTableFormat::Bsd => SymbolTableFormat::Bsd64,
+            SymbolTableFormat::BsdSorted => SymbolTableFormat::Bsd64Sorted,
+            SymbolTableFormat::Gnu => SymbolTableFormat::Gnu64,
+            format => format,
+        }
+    }
+
+    /// Returns the sorted format that corresponds to this one, if it's a BSD
+    /// format (the GNU formats are never sorted).
+    fn to_sorted(self) -> SymbolTableFormat {
+        match self {
+            SymbolTableFormat::Bsd => SymbolTableFormat::BsdSorted,
+            SymbolTableFormat::Bsd64 => SymbolTableFormat::Bsd64Sorted,
+            format => format,
+        }
+    }
+
+    /// Returns the length of the identifier that a BSD symbol table stores
+    /// after its entry header (padded to a multiple of four bytes), or zero
+    /// for the GNU formats.
+    fn extended_identifier_len(self) -> u64 {
+        if !self.is_bsd() {
+            return 0;
+        }
+        let len = self.identifier().len() as u64;
+        len + (4 - len % 4) % 4
+    }
+}
+
+/// Returns the size of the data of a symbol table in the given format, not
+/// including the entry header.
+fn symbol_table_size(format: SymbolTableFormat, symbols: &[(Vec<u8>, u64)])
+                     -> u64 {
+    let num_symbols = symbols.len() as u64;
+    let mut string_table_size: u64 = 0;
+    for (name, _) in symbols.iter() {
+        string_table_size += name.len() as u64 + 1;
+    }
+    match format {
+        SymbolTableFormat::Bsd |
+        SymbolTableFormat::BsdSorted |
+        SymbolTableFormat::Bsd64 |
+        SymbolTableFormat::Bsd64Sorted => {
+            // Pad the entry (including its header and padded name) so that
+            // the entry after it starts at a multiple of eight bytes.
+            let word = if format.is_64_bit() { 8 } else { 4 };
+            let size = word + 2 * word * num_symbols + word +
+                string_table_size;
+            let total = ENTRY_HEADER_LEN as u64 +
+                format.extended_identifier_len() + size;
+            size + (8 - total % 8) % 8
+        }
+        SymbolTableFormat::Gnu => {
+            let size = 4 + 4 * num_symbols + string_table_size;
+            size + (size % 2)
+        }
+        SymbolTableFormat::Gnu64 => {
+            let size = 8 + 8 * num_symbols + string_table_size;
+            size + (8 - size % 8) % 8
+        }
+    }
+}
+
+/// Returns the total length of a symbol table entry, including its header.
+fn symbol_table_entry_len(format: SymbolTableFormat,
+                          symbols: &[(Vec<u8>, u64)])
+                          -> u64 {
+    ENTRY_HEADER_LEN as u64 + format.extended_identifier_len() +
+        symbol_table_size(format, symbols)
+}
+
+/// Writes a complete symbol table entry (header and data) in the given
+/// format, where each symbol is paired with the offset (from the start of the
+/// archive) of the header of the entry that defines it.
+fn write_symbol_table<W: Write>(writer: &mut W, format: SymbolTableFormat,
+                                symbols: &[(Vec<u8>, u64)])
+                                -> Result<()> {
+    let size = symbol_table_size(format, symbols);
+    let mut written: u64 = 0;
+    if format.is_64_bit() && format.is_bsd() {
+        let header = Header::new(format.identifier().to_vec(), size);
+        try!(header.write_bsd_extended(writer));
+        try!(writer.write_u64::<LittleEndian>(16 * symbols.len() as u64));
+        let mut string_offset: u64 = 0;
+        for &(ref name, offset) in symbols.iter() {
+            try!(writer.write_u64::<LittleEndian>(string_offset));
+            try!(writer.write_u64::<LittleEndian>(offset));
+            string_offset += name.len() as u64 + 1;
+        }
+        try!(writer.write_u64::<LittleEndian>(string_offset));
+        written += 8 + 16 * symbols.len() as u64 + 8;
+    } else if format.is_bsd() {
+        let header = Header::new(format.identifier().to_vec(), size);
+        try!(header.write_bsd_extended(writer));
+        try!(writer.write_u32::<LittleEndian>(8 * symbols.len() as u32));
+        let mut string_offset: u64 = 0;
+        for &(ref name, offset) in symbols.iter() {
+            try!(writer.write_u32::<LittleEndian>(
+                try!(symbol_offset(string_offset)),
+            ));
+            let offset = try!(symbol_offset(offset));
+            try!(writer.write_u32::<LittleEndian>(offset));
+            string_offset += name.len() as u64 + 1;
+        }
+        try!(writer.write_u32::<LittleEndian>(
+            try!(symbol_offset(string_offset)),
+        ));
+        written += 4 + 8 * symbols.len() as u64 + 4;
+    } else {
+        try!(write!(
+            writer,
+            "{:<16}{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
+            str::from_utf8(format.identifier()).unwrap(),
+            0,
+            0,
+            0,
+            0,
+            size
+        ));
+        if format == SymbolTableFormat::Gnu64 {
+            try!(writer.write_u64::<BigEndian>(symbols.len() as u64));
+            for &(_, offset) in symbols.iter() {
+                try!(writer.write_u64::<BigEndian>(offset));
+            }
+            written += 8 + 8 * symbols.len() as u64;
+        } else {
+            try!(writer.write_u32::<BigEndian>(symbols.len() as u32));
+            for &(_, offset) in symbols.iter() {
+                let offset = try!(symbol_offset(offset));
+                try!(writer.write_u32::<BigEndian>(offset));
+            }
+            written += 4 + 4 * symbols.len() as u64;
+        }
+    }
+    for (name, _) in symbols.iter() {
+        try!(writer.write_all(name));
+        try!(writer.write_all(&[0]));
+        written += name.len() as u64 + 1;
+    }
+    try!(writer.write_all(&vec![0; (size - written) as usize]));
+    Ok(())
+}
+
+fn symbol_offset(offset: u64) -> Result<u32> {
+    if offset > u32::MAX as u64 {
+        let msg = format!(
+            "Offset ({}) is too large for a 32-bit symbol table",
+            offset
+        );
+        return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+    }
+    Ok(offset as u32)
+}
+
+/// Writes a symbol table before the first entry of an archive whose entry
+/// offsets aren't known yet, and then fills in each entry's offset once that
+/// entry is appended.
+struct SymbolTableWriter<W> {
+    format: SymbolTableFormat,
+    symbols: Vec<(Vec<u8>, u64)>,
+    // Maps each entry identifier to the indices of the symbols it defines,
+    // for entries that haven't been appended yet.
+    pending: HashMap<Vec<u8>, Vec<usize>>,
+    seek: fn(&mut W, SeekFrom) -> Result<u64>,
+    archive_start: u64,
+    offsets_start: u64,
+}
+
+impl<W: Write> SymbolTableWriter<W> {
+    fn new(writer: &mut W, variant: Variant,
+           symbol_table: Vec<(Vec<u8>, Vec<u8>)>)
+           -> Result<Option<SymbolTableWriter<W>>>
+    where
+        W: Seek,
+    {
+        if symbol_table.is_empty() {
+            return Ok(None);
+        }
+        let archive_start = try!(writer.stream_position());
+        let mut symbols = Vec::with_capacity(symbol_table.len());
+        let mut pending = HashMap::<Vec<u8>, Vec<usize>>::new();
+        for (index, (name, identifier)) in
+            symbol_table.into_iter().enumerate()
+        {
+            symbols.push((name, 0));
+            pending.entry(identifier).or_default().push(index);
+        }
+        Ok(Some(SymbolTableWriter {
+            format: SymbolTableFormat::for_variant(variant),
+            symbols: symbols,
+            pending: pending,
+            seek: <W as Seek>::seek,
+            archive_start: archive_start,
+            offsets_start: 0,
+        }))
+    }
+
+    /// Writes the symbol table, with placeholder offsets.
+    fn write_table(&mut self, writer: &mut W) -> Result<()> {
+        let table_start = try!((self.seek)(writer, SeekFrom::Current(0)));
+        self.offsets_start = if self.format == SymbolTableFormat::Gnu {
+            table_start + ENTRY_HEADER_LEN as u64 + 4
+        } else {
+            // Skip the padded "__.SYMDEF" name, the size of the ranlib array,
+            // and the string offset of the first ranlib struct.
+            table_start + ENTRY_HEADER_LEN as u64 + 12 + 4 + 4
+        };
+        write_symbol_table(writer, self.format, &self.symbols)
+    }
+
+    /// Records that the entry with the given identifier is about to be
+    /// written at the writer's current position, filling in the offsets of
+    /// any symbols that it defines.
+    fn record_entry(&mut self, writer: &mut W, identifier: &[u8])
+                    -> Result<()> {
+        let indices = match self.pending.remove(identifier) {
+            Some(indices) => indices,
+            None => return Ok(()),
+        };
+        let position = try!((self.seek)(writer, SeekFrom::Current(0)));
+        let offset = try!(symbol_offset(position - self.archive_start));
+        let stride = if self.format == SymbolTableFormat::Gnu { 4 } else { 8 };
+        for index in indices.into_iter() {
+            self.symbols[index].1 = offset as u64;
+            let slot = self.offsets_start + stride * index as u64;
+            try!((self.seek)(writer, SeekFrom::Start(slot)));
+            if self.format == SymbolTableFormat::Gnu {
+                try!(writer.write_u32::<BigEndian>(offset));
+            } else {
+                try!(writer.write_u32::<LittleEndian>(offset));
+            }
+        }
+        try!((self.seek)(writer, SeekFrom::Start(position)));
+        Ok(())
+    }
+
+    /// Returns an error if any symbol refers to an entry that was never
+    /// appended.
+    fn check_complete(&self) -> Result<()> {
+        if let Some(identifier) = self.pending.keys().next() {
+            let msg = format!(
+                "Symbol table refers to entry {:?}, which was never appended",
+                String::from_utf8_lossy(identifier)
+            );
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        Ok(())
+    }
+}
+
+/// Runs a `SymbolExtractor` over each entry that a builder appends, and then
+/// inserts a symbol table listing the symbols that it found after the
+/// archive's global header, once the archive is complete.
+struct SymbolCollector<W> {
+    extractor: Box<dyn SymbolExtractor>,
+    format: SymbolTableFormat,
+    // Each symbol paired with the offset of its entry from the start of the
+    // archive, not counting the symbol table itself.
+    symbols: SymbolTable,
+    seek: fn(&mut W, SeekFrom) -> Result<u64>,
+    insert: fn(&mut W, u64, &[u8]) -> Result<()>,
+    archive_start: u64,
+}
+
+impl<W: Read + Write + Seek> SymbolCollector<W> {
+    fn new<E>(writer: &mut W, variant: Variant, extractor: E)
+              -> Result<SymbolCollector<W>>
+    where
+        E: SymbolExtractor + 'static,
+    {
+        let archive_start = try!(writer.stream_position());
+        Ok(SymbolCollector {
+            extractor: Box::new(extractor),
+            format: SymbolTableFormat::for_variant(variant),
+            symbols: Vec::new(),
+            seek: <W as Seek>::seek,
+            insert: insert_data::<W>,
+            archive_start: archive_start,
+        })
+    }
+}
+
+impl<W: Write> SymbolCollector<W> {
+    /// Records the symbols defined by an entry that is about to be written at
+    /// the writer's current position.
+    fn record_entry(&mut self, writer: &mut W, header: &Header, data: &[u8])
+                    -> Result<()> {
+        let result = self.extractor.extract_symbols(header, data);
+        let symbols = try!(result.map_err(|error| {
+            let msg = format!(
+                "{}: {}",
+                String::from_utf8_lossy(header.identifier()),
+                error
+            );
+            io::Error::new(error.kind(), msg)
+        }));
+        if symbols.is_empty() {
+            return Ok(());
+        }
+        let position = try!((self.seek)(writer, SeekFrom::Current(0)));
+        let offset = position - self.archive_start;
+        for symbol in symbols.into_iter() {
+            self.symbols.push((symbol, offset));
+        }
+        Ok(())
+    }
+
+    /// Inserts the symbol table after the global header, moving the entries
+    /// along to make room, unless no symbols were found.
+    fn insert_table(mut self, writer: &mut W) -> Result<()> {
+        if self.symbols.is_empty() {
+            return Ok(());
+        }
+        let mut len = symbol_table_entry_len(self.format, &self.symbols);
+        if !self.format.is_64_bit() &&
+            self.symbols
+                .iter()
+                .any(|&(_, offset)| offset + len > u32::MAX as u64)
+        {
+            self.format = self.format.to_64_bit();
+            len = symbol_table_entry_len(self.format, &self.symbols);
+        }
+        for symbol in self.symbols.iter_mut() {
+            symbol.1 += len;
+        }
+        let mut table = Vec::with_capacity(len as usize);
+        try!(write_symbol_table(&mut table, self.format, &self.symbols));
+        let position = self.archive_start + GLOBAL_HEADER_LEN as u64;
+        (self.insert)(writer, position, &table)
+    }
+}
+
+/// Inserts data into a stream at the given position, moving everything from
+/// there to the stream's current position along to make room, and leaves the
+/// stream positioned just after the moved data.
+fn insert_data<W: Read + Write + Seek>(stream: &mut W, position: u64,
+                                       data: &[u8])
+                                       -> Result<()> {
+    let end = try!(stream.stream_position());
+    let shift = data.len() as u64;
+    let mut buffer = vec![0; INSERT_BUFFER_LEN];
+    // Move the data a chunk at a time, starting from the end, so that
+    // nothing is overwritten before it has been moved.
+    let mut remaining = end - position;
+    while remaining > 0 {
+        let len = cmp::min(remaining, buffer.len() as u64);
+        let start = position + remaining - len;
+        let chunk = &mut buffer[..len as usize];
+        try!(stream.seek(SeekFrom::Start(start)));
+        try!(stream.read_exact(chunk));
+        try!(stream.seek(SeekFrom::Start(start + shift)));
+        try!(stream.write_all(chunk));
+        remaining -= len;
+    }
+    try!(stream.seek(SeekFrom::Start(position)));
+    try!(stream.write_all(data));
+    try!(stream.seek(SeekFrom::Start(end + shift)));
+    Ok(())
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{AixBuilder, Archive, ArchiveEditor, Builder, Error,
+                GnuBuilder, GnuNameTable, Header, MetadataPolicy,
+                StagedBuilder, SymbolTableFormat, TEMP_FILE_COUNTER,
+                ThinBuilder, Variant, parse_source_date_epoch,
+                write_atomically, write_symbol_table};
+    use std::ffi::OsString;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom,
+                  Write};
+    use std::process;
+    use std::str;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use test_common::TestDir;
+    #[cfg(feature = "object")]
+    use test_common::import_object;
+
+    #[test]
+    fn build_common_archive() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header1 = Header::new(b"foo.txt".to_vec(), 7);
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        header1.set_gid(20);
+        header1.set_mode(0o100644);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"baz.txt".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        baz.txt         0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_bsd_archive_with_long_filenames() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header1 = Header::new(b"short".to_vec(), 1);
+        header1.set_identifier(b"this_is_a_very_long_filename.txt".to_vec());
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        header1.set_gid(20);
+        header1.set_mode(0o100644);
+        header1.set_size(7);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(
+            b"and_this_is_another_very_long_filename.txt".to_vec(),
+            4,
+        );
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        #1/32           1487552916  501   20    100644  39        `\n\
+        this_is_a_very_long_filename.txtfoobar\n\n\
+        #1/44           0           0     0     0       48        `\n\
+        and_this_is_another_very_long_filename.txt\x00\x00baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_bsd_archive_with_space_in_filename() {
+        let mut builder = Builder::new(Vec::new());
+        let header = Header::new(b"foo bar".to_vec(), 4);
+        builder.append(&header, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        #1/8            0           0     0     0       12        `\n\
+        foo bar\x00baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_strict_common_archive() {
+        let mut builder =
+            Builder::new_with_variant(Vec::new(), Variant::Common).unwrap();
+        let header = Header::new(b"foo.txt".to_vec(), 4);
+        builder.append(&header, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_strict_common_archive_with_unrepresentable_identifiers() {
+        let cases: &[(&[u8], &str)] = &[
+            (
+                b"this_is_a_very_long_filename.txt",
+                "Identifier \"this_is_a_very_long_filename.txt\" can't be \
+                 written in a common-variant header (it would need the BSD \
+                 #1/ extension)",
+            ),
+            (
+                b"foo bar",
+                "Identifier \"foo bar\" can't be written in a common-variant \
+                 header (it would need the BSD #1/ extension)",
+            ),
+            (
+                b"#1/foo",
+                "Identifier \"#1/foo\" can't be written in a common-variant \
+                 header (it would be mistaken for the BSD #1/ extension)",
+            ),
+            (
+                b"foo.txt/",
+                "Identifier \"foo.txt/\" can't be written in a \
+                 common-variant header (it would be mistaken for a GNU name)",
+            ),
+            (
+                b"/foo.txt",
+                "Identifier \"/foo.txt\" can't be written in a \
+                 common-variant header (it would be mistaken for a GNU name)",
+            ),
+        ];
+        let mut builder =
+            Builder::new_with_variant(Vec::new(), Variant::Common).unwrap();
+        for &(identifier, message) in cases {
+            let header = Header::new(identifier.to_vec(), 4);
+            let error = builder
+                .append(&header, "baz\n".as_bytes())
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(error.to_string(), message);
+        }
+        assert!(builder.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn build_gnu_archive_with_builder_and_unrepresentable_identifiers() {
+        let cases: &[(&[u8], &str)] = &[
+            (
+                b"/foo.txt",
+                "Identifier \"/foo.txt\" can't be written in a GNU archive \
+                 (it would be mistaken for a special entry or a name table \
+                 reference)",
+            ),
+            (
+                b"//",
+                "Identifier \"//\" can't be written in a GNU archive (it \
+                 would be mistaken for a special entry or a name table \
+                 reference)",
+            ),
+            (
+                b"sub/foo.txt",
+                "Identifier \"sub/foo.txt\" can't be written in a GNU \
+                 archive (GNU names end at the first slash)",
+            ),
+            (
+                b"foo.txt/",
+                "Identifier \"foo.txt/\" can't be written in a GNU archive \
+                 (GNU names end at the first slash)",
+            ),
+        ];
+        let mut builder =
+            Builder::new_with_variant(Vec::new(), Variant::GNU).unwrap();
+        for &(identifier, message) in cases {
+            let header = Header::new(identifier.to_vec(), 4);
+            let error = builder
+                .append(&header, "baz\n".as_bytes())
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(error.to_string(), message);
+        }
+        assert!(builder.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn round_trip_identifiers_with_builder() {
+        let cases: &[(Variant, &[&[u8]])] = &[
+            (
+                Variant::Common,
+                &[b"foo.txt", b"sub/foo.txt", b"exactly_16_chars"],
+            ),
+            (Variant::GNU, &[b"foo bar.txt", b"#1.o", b"a_long_name.txt"]),
+        ];
+        for &(variant, identifiers) in cases {
+            let mut builder =
+                Builder::new_with_variant(Vec::new(), variant).unwrap();
+            for &identifier in identifiers {
+                let header = Header::new(identifier.to_vec(), 0);
+                builder.append(&header, "".as_bytes()).unwrap();
+            }
+            let output = builder.into_inner().unwrap();
+            let mut archive = Archive::new(output.as_slice());
+            let mut actual = Vec::new();
+            while let Some(entry) = archive.next_entry() {
+                actual.push(entry.unwrap().header().identifier().to_vec());
+            }
+            assert_eq!(actual, identifiers);
+            assert_eq!(archive.variant(), variant);
+        }
+    }
+
+    #[test]
+    fn build_gnu_archive_with_builder() {
+        let mut builder =
+            Builder::new_with_variant(Vec::new(), Variant::GNU).unwrap();
+        let header1 = Header::new(b"foo bar.txt".to_vec(), 4);
+        builder.append(&header1, "baz\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"a_long_name.txt".to_vec(), 0);
+        builder.append(&header2, "".as_bytes()).unwrap();
+        let header3 = Header::new(b"too_long_name.txt".to_vec(), 0);
+        let error = builder.append(&header3, "".as_bytes()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Identifier \"too_long_name.txt\" is too long for a GNU archive \
+             without a name table (use GnuBuilder instead)"
+        );
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        foo bar.txt/    0           0     0     0       4         `\n\
+        baz\n\
+        a_long_name.txt/0           0     0     0       0         `\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Builder cannot write AIX big archives")]
+    fn build_aix_big_archive_with_builder() {
+        Builder::new_with_variant(Vec::new(), Variant::AIX).unwrap();
+    }
+
+    #[test]
+    fn copy_gnu_archive_into_bsd_archive() {
+        let input = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        baz.txt/        0           0     0     0       4         `\n\
+        baz\n";
+        let mut archive = Archive::new(input.as_bytes());
+        let mut builder = Builder::new(Vec::new());
+        builder.append_archive(&mut archive).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        #1/32           1487552916  501   20    100644  39        `\n\
+        this_is_a_very_long_filename.txtfoobar\n\n\
+        baz.txt         0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn copy_bsd_entries_into_gnu_archive() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       4         `\n\
+        foo\n\
+        #1/32           1487552916  501   20    100644  39        `\n\
+        this_is_a_very_long_filename.txtfoobar\n\n\
+        baz.txt         0           0     0     0       4         `\n\
+        baz\n";
+        let mut archive = Archive::new(input.as_bytes());
+        let names = vec![
+            b"this_is_a_very_long_filename.txt".to_vec(),
+            b"baz.txt".to_vec(),
+        ];
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        // Skip the first entry, then copy the second one on its own.
+        archive.next_entry().unwrap().unwrap();
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            builder.append_entry(&mut entry).unwrap();
+        }
+        builder.append_archive(&mut archive).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        baz.txt/        0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn copy_archive_into_other_builders() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  4         `\n\
+        foo\n\
+        bar.txt         0           0     0     0       3         `\n\
+        bar\n";
+        let expected = vec![
+            ("foo.txt".to_string(), "foo\n".to_string()),
+            ("bar.txt".to_string(), "bar".to_string()),
+        ];
+
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append_archive(&mut Archive::new(input.as_bytes())).unwrap();
+        let output = builder.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(output.as_slice());
+        assert_eq!(read_entries(&mut archive), expected);
+
+        let mut builder = AixBuilder::new(Cursor::new(Vec::new()));
+        builder.append_archive(&mut Archive::new(input.as_bytes())).unwrap();
+        let output = builder.into_inner().unwrap().into_inner();
+        let mut archive = Archive::new(output.as_slice());
+        assert_eq!(read_entries(&mut archive), expected);
+        assert_eq!(archive.variant(), Variant::AIX);
+
+        let names = vec![b"foo.txt".to_vec(), b"bar.txt".to_vec()];
+        let mut builder = ThinBuilder::new(Vec::new(), names);
+        builder.append_archive(&mut Archive::new(input.as_bytes())).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<thin>\n\
+        //                                              18        `\n\
+        foo.txt/\n\
+        bar.txt/\n\
+        /0              1487552916  501   20    100644  4         `\n\
+        /9              0           0     0     0       3         `\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn append_to_existing_gnu_archive() {
+        let input = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              0           0     0     0       7         `\n\
+        foobar\n\n\
+        baz.txt/        0           0     0     0       4         `\n\
+        baz\n";
+        let mut cursor = Cursor::new(input.as_bytes().to_vec());
+        cursor.set_position(5);
+        let mut builder = Builder::from_existing(cursor).unwrap();
+        let header = Header::new(b"foo bar.txt".to_vec(), 4);
+        builder.append(&header, "foo\n".as_bytes()).unwrap();
+        let header = Header::new(b"another_long_name.txt".to_vec(), 0);
+        let error = builder.append(&header, "".as_bytes()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected = format!(
+            "{}foo bar.txt/    0           0     0     0       4         \
+             `\nfoo\n",
+            input
+        );
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn append_to_existing_archive_without_final_padding() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       7         `\n\
+        foobar\n";
+        let cursor = Cursor::new(input.as_bytes().to_vec());
+        let mut builder = Builder::from_existing(cursor).unwrap();
+        let header = Header::new(b"baz.txt".to_vec(), 4);
+        builder.append(&header, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       7         `\n\
+        foobar\n\n\
+        baz.txt         0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn append_long_name_to_existing_archive_with_short_names() {
+        let mut builder = Builder::new(Cursor::new(Vec::new()));
+        let header = Header::new(b"foo.txt".to_vec(), 4);
+        builder.append(&header, "foo\n".as_bytes()).unwrap();
+        let cursor = builder.into_inner().unwrap();
+        let mut builder = Builder::from_existing(cursor).unwrap();
+        let header =
+            Header::new(b"this_is_a_very_long_filename.txt".to_vec(), 4);
+        builder.append(&header, "bar\n".as_bytes()).unwrap();
+        let header = Header::new(b"foo bar.txt".to_vec(), 4);
+        builder.append(&header, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       4         `\n\
+        foo\n\
+        #1/32           0           0     0     0       36        `\n\
+        this_is_a_very_long_filename.txtbar\n\
+        #1/12           0           0     0     0       16        `\n\
+        foo bar.txt\x00baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+        let mut archive = Archive::new(expected.as_bytes());
+        let mut identifiers = Vec::new();
+        while let Some(entry) = archive.next_entry() {
+            identifiers.push(entry.unwrap().header().identifier().to_vec());
+        }
+        let expected: Vec<&[u8]> = vec![
+            b"foo.txt",
+            b"this_is_a_very_long_filename.txt",
+            b"foo bar.txt",
+        ];
+        assert_eq!(identifiers, expected);
+    }
+
+    #[test]
+    fn append_to_empty_file() {
+        let mut builder = Builder::from_existing(Cursor::new(Vec::new()))
+            .unwrap();
+        let header =
+            Header::new(b"this_is_a_very_long_filename.txt".to_vec(), 0);
+        builder.append(&header, "".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected = "\
+        !<arch>\n\
+        #1/32           0           0     0     0       32        `\n\
+        this_is_a_very_long_filename.txt";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn append_to_invalid_existing_archives() {
+        let truncated = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       7         `\n\
+        foo";
+        let with_symbol_table = "\
+        !<arch>\n\
+        /               0           0     0     0       4         `\n\
+        \x00\x00\x00\x00";
+        let cases = [
+            (
+                truncated,
+                "Archive is truncated or has trailing data (expected 76 \
+                 bytes, found 71)",
+            ),
+            (
+                with_symbol_table,
+                "Cannot append to an archive with a symbol table (use \
+                 ArchiveEditor instead)",
+            ),
+            (
+                "!<thin>\n",
+                "Cannot append to a thin archive with Builder (use \
+                 ThinBuilder instead)",
+            ),
+        ];
+        for &(input, message) in cases.iter() {
+            let cursor = Cursor::new(input.as_bytes().to_vec());
+            let error = Builder::from_existing(cursor).err().unwrap();
+            assert_eq!(error.to_string(), message);
+        }
+        let cursor = Cursor::new(truncated.as_bytes().to_vec());
+        let error = Builder::from_existing(cursor).err().unwrap();
+        let expected = Error::UnexpectedArchiveLength {
+            expected: 76,
+            actual: 71,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn build_deterministic_archive() {
+        let mut builder = Builder::new(Vec::new());
+        let mut policy = MetadataPolicy::new();
+        policy.set_deterministic(true);
+        builder.set_metadata_policy(policy);
+        let mut header1 = Header::new(b"foo.txt".to_vec(), 7);
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        header1.set_gid(20);
+        header1.set_mode(0o100755);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"baz.txt".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        foo.txt         0           0     0     644     7         `\n\
+        foobar\n\n\
+        baz.txt         0           0     0     644     4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_archive_with_clamped_mtimes() {
+        let mut builder = Builder::new(Vec::new());
+        let mut policy = MetadataPolicy::new();
+        policy.set_mtime_clamp(Some(1500000000));
+        builder.set_metadata_policy(policy);
+        let mut header1 = Header::new(b"old.txt".to_vec(), 0);
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        builder.append(&header1, "".as_bytes()).unwrap();
+        let mut header2 = Header::new(b"new.txt".to_vec(), 0);
+        header2.set_mtime(1600000000);
+        builder.append(&header2, "".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        old.txt         1487552916  501   0     0       0         `\n\
+        new.txt         1500000000  0     0     0       0         `\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_deterministic_staged_gnu_archive() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let mut policy = MetadataPolicy::new();
+        policy.set_deterministic(true);
+        builder.set_metadata_policy(policy);
+        let mut header =
+            Header::new(b"this_is_a_very_long_filename.txt".to_vec(), 0);
+        header.set_mtime(1487552916);
+        header.set_gid(20);
+        builder.append_bytes(header, b"foo\n".to_vec());
+        let actual = builder.write(Vec::new()).unwrap();
+        let expected = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              0           0     0     644     4         `\n\
+        foo\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_source_date_epochs() {
+        let parse = |value: Option<&str>| {
+            parse_source_date_epoch(value.map(OsString::from))
+        };
+        assert_eq!(parse(Some("1487552916")).unwrap(), Some(1487552916));
+        assert_eq!(parse(Some("")).unwrap(), None);
+        assert_eq!(parse(None).unwrap(), None);
+        let error = parse(Some("yesterday")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "Invalid SOURCE_DATE_EPOCH value (\"yesterday\")"
+        );
+    }
+
+    #[test]
+    fn build_gnu_archive() {
+        let names = vec![b"baz.txt".to_vec(), b"foo.txt".to_vec()];
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        let mut header1 = Header::new(b"foo.txt".to_vec(), 7);
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        header1.set_gid(20);
+        header1.set_mode(0o100644);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"baz.txt".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        foo.txt/        1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        baz.txt/        0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_gnu_archive_with_slashes_in_identifiers() {
+        let names = vec![b"sub/foo.o".to_vec(), b"bar.o".to_vec()];
+        let expected = "\
+        !<arch>\n\
+        //                                              12        `\n\
+        sub/foo.o/\n\n\
+        /0              0           0     0     0       4         `\n\
+        foo\n\
+        bar.o/          0           0     0     0       4         `\n\
+        bar\n";
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        let header1 = Header::new(b"sub/foo.o".to_vec(), 4);
+        builder.append(&header1, "foo\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"bar.o".to_vec(), 4);
+        builder.append(&header2, "bar\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append_bytes(header1, b"foo\n".to_vec());
+        builder.append_bytes(header2, b"bar\n".to_vec());
+        let actual = builder.write(Vec::new()).unwrap();
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+
+        let mut archive = Archive::new(actual.as_slice());
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.header().identifier(), b"sub/foo.o");
+    }
+
+    #[test]
+    fn build_gnu_archive_with_long_filenames() {
+        let names = vec![
+            b"this_is_a_very_long_filename.txt".to_vec(),
+            b"and_this_is_another_very_long_filename.txt".to_vec(),
+        ];
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        let mut header1 = Header::new(
+            b"this_is_a_very_long_filename.txt".to_vec(),
+            7,
+        );
+        header1.set_mtime(1487552916);
+        header1.set_uid(501);
+        header1.set_gid(20);
+        header1.set_mode(0o100644);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(
+            b"and_this_is_another_very_long_filename.txt".to_vec(),
+            4,
+        );
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let expected = "\
+        !<arch>\n\
+        //                                              78        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        and_this_is_another_very_long_filename.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        /34             0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_and_read_gnu_archive_with_odd_length_name_table() {
+        let names = vec![b"a_sixteen_byte_n".to_vec(), b"short".to_vec()];
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        let header1 = Header::new(b"a_sixteen_byte_n".to_vec(), 3);
+        builder.append(&header1, "foo".as_bytes()).unwrap();
+        let header2 = Header::new(b"short".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap();
+        let mut archive = Archive::new(actual.as_slice());
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"a_sixteen_byte_n");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"foo");
+        }
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"short");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"baz\n");
+        }
+        assert!(archive.next_entry().is_none());
+        assert_eq!(archive.variant(), Variant::GNU);
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier \\\"bar.txt\\\" was not in the \
+                               list of identifiers passed to \
+                               GnuBuilder::new()")]
+    fn build_gnu_archive_with_unexpected_identifier() {
+        let names = vec![b"foo.txt".to_vec()];
+        let mut builder = GnuBuilder::new(Vec::new(), names);
+        let header = Header::new(b"bar.txt".to_vec(), 4);
+        builder.append(&header, "baz\n".as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn build_bsd_archive_with_symbol_table() {
+        let symbols = vec![
+            (b"foobar".to_vec(), b"foo.o".to_vec()),
+            (b"baz".to_vec(), b"bar.o".to_vec()),
+            (b"quux".to_vec(), b"foo.o".to_vec()),
+        ];
+        let mut builder =
+            Builder::new_with_symbol_table(Cursor::new(Vec::new()), symbols)
+                .unwrap();
+        let header1 = Header::new(b"foo.o".to_vec(), 7);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"bar.o".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected: &[u8] = b"\
+        !<arch>\n\
+        #1/12           0           0     0     0       60        `\n\
+        __.SYMDEF\x00\x00\x00\x18\x00\x00\x00\
+        \x00\x00\x00\x00\x80\x00\x00\x00\
+        \x07\x00\x00\x00\xc4\x00\x00\x00\
+        \x0b\x00\x00\x00\x80\x00\x00\x00\
+        \x10\x00\x00\x00foobar\x00baz\x00quux\x00\
+        foo.o           0           0     0     0       7         `\n\
+        foobar\n\n\
+        bar.o           0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(actual.as_slice(), expected);
+        let mut archive = Archive::new(Cursor::new(actual));
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"foobar", b"baz", b"quux"];
+        assert_eq!(symbols, expected);
+        assert_eq!(archive.count_entries().unwrap(), 2);
+    }
+
+    #[test]
+    fn build_gnu_archive_with_symbol_table() {
+        let names = vec![b"foo.o".to_vec(), b"bar.o".to_vec()];
+        let symbols = vec![
+            (b"foobar".to_vec(), b"foo.o".to_vec()),
+            (b"baz".to_vec(), b"bar.o".to_vec()),
+        ];
+        let mut builder = GnuBuilder::new_with_symbol_table(
+            Cursor::new(Vec::new()),
+            names,
+            symbols,
+        ).unwrap();
+        let header1 = Header::new(b"foo.o".to_vec(), 7);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"bar.o".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected: &[u8] = b"\
+        !<arch>\n\
+        /               0           0     0     0       24        `\n\
+        \x00\x00\x00\x02\x00\x00\x00\x5c\x00\x00\x00\xa0\
+        foobar\x00baz\x00\x00\
+        foo.o/          0           0     0     0       7         `\n\
+        foobar\n\n\
+        bar.o/          0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(actual.as_slice(), expected);
+        let mut archive = Archive::new(Cursor::new(actual));
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"foobar", b"baz"];
+        assert_eq!(symbols, expected);
+        assert_eq!(archive.count_entries().unwrap(), 2);
+    }
+
+    // A symbol extractor that treats each word of an entry's data as a symbol
+    // that the entry defines.
+    fn extract_words(_header: &Header, data: &[u8])
+                     -> io::Result<Vec<Vec<u8>>> {
+        Ok(data.split(|byte| byte.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_vec())
+            .collect())
+    }
+
+    #[test]
+    fn build_bsd_archive_with_symbol_extractor() {
+        let entries: &[(&[u8], &[u8])] = &[
+            (b"foo.o", b"foobar quux\n"),
+            (b"README", b"\n"),
+            (b"a_very_long_name.o", b"baz\n"),
+        ];
+        let symbols = vec![
+            (b"foobar".to_vec(), b"foo.o".to_vec()),
+            (b"quux".to_vec(), b"foo.o".to_vec()),
+            (b"baz".to_vec(), b"a_very_long_name.o".to_vec()),
+        ];
+        let mut builder =
+            Builder::new_with_symbol_table(Cursor::new(Vec::new()), symbols)
+                .unwrap();
+        for &(identifier, data) in entries.iter() {
+            let header = Header::new(identifier.to_vec(), data.len() as u64);
+            builder.append(&header, data).unwrap();
+        }
+        let expected = builder.into_inner().unwrap().into_inner();
+
+        let mut builder = Builder::new(Cursor::new(Vec::new()));
+        builder.set_symbol_extractor(extract_words).unwrap();
+        for &(identifier, data) in entries.iter() {
+            let header = Header::new(identifier.to_vec(), data.len() as u64);
+            builder.append(&header, data).unwrap();
+        }
+        let actual = builder.into_inner().unwrap().into_inner();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn build_gnu_archive_with_symbol_extractor() {
+        let names = vec![b"foo.o".to_vec(), b"a_very_long_name.o".to_vec()];
+        let symbols = vec![
+            (b"foobar".to_vec(), b"foo.o".to_vec()),
+            (b"baz".to_vec(), b"a_very_long_name.o".to_vec()),
+        ];
+        let mut builder = GnuBuilder::new_with_symbol_table(
+            Cursor::new(Vec::new()),
+            names.clone(),
+            symbols,
+        ).unwrap();
+        let header1 = Header::new(b"foo.o".to_vec(), 7);
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        let header2 = Header::new(b"a_very_long_name.o".to_vec(), 4);
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let expected = builder.into_inner().unwrap().into_inner();
+
+        let mut builder = GnuBuilder::new(Cursor::new(Vec::new()), names);
+        builder.set_symbol_extractor(extract_words).unwrap();
+        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
+        builder.append(&header2, "baz\n".as_bytes()).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn build_archive_with_symbol_extractor_into_file() {
+        let dir = TestDir::new("extractor-file");
+        let path = dir.join("foo.a");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let mut builder = Builder::new(file);
+        builder.set_symbol_extractor(extract_words).unwrap();
+        let header = Header::new(b"foo.o".to_vec(), 12);
+        builder.append(&header, "foobar quux\n".as_bytes()).unwrap();
+        builder.into_inner().unwrap();
+        let expected = fs::read(&path).unwrap();
+
+        // A buffered writer can't be read back, so StagedBuilder is the way
+        // to get the same archive through one.
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        builder.set_symbol_extractor(extract_words);
+        builder.append_bytes(header, b"foobar quux\n".to_vec());
+        let writer = BufWriter::new(File::create(&path).unwrap());
+        builder.write(writer).unwrap().into_inner().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), expected);
+    }
+
+    #[test]
+    fn build_large_archive_with_symbol_extractor() {
+        // The entries are larger than the buffer used to move them along to
+        // make room for the symbol table.
+        let data: Vec<u8> =
+            (0..200000).map(|index| b"abcdefgh "[index % 9]).collect();
+        let mut builder =
+            Builder::new_with_variant(Cursor::new(Vec::new()), Variant::GNU)
+                .unwrap();
+        let mut count = 0;
+        builder
+            .set_symbol_extractor(move |_: &Header, _: &[u8]| {
+                count += 1;
+                Ok(vec![format!("sym{}", count).into_bytes()])
+            })
+            .unwrap();
+        let header = Header::new(b"big1".to_vec(), data.len() as u64);
+        builder.append(&header, data.as_slice()).unwrap();
+        let header = Header::new(b"big2".to_vec(), data.len() as u64);
+        builder.append(&header, data.as_slice()).unwrap();
+        let output = builder.into_inner().unwrap().into_inner();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> = vec![(b"sym1", 0), (b"sym2", 1)];
+        assert_eq!(symbols, expected);
+        for index in 0..2 {
+            let mut entry = archive.jump_to_entry(index).unwrap();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            assert!(contents == data);
+        }
+    }
+
+    #[test]
+    fn symbol_extractor_errors() {
+        let mut builder = Builder::new(Cursor::new(Vec::new()));
+        builder
+            .set_symbol_extractor(|header: &Header, _: &[u8]| {
+                if header.identifier() == b"bad.o" {
+                    let msg = "not an object file";
+                    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+                } else {
+                    Ok(Vec::new())
+                }
+            })
+            .unwrap();
+        let header = Header::new(b"good.o".to_vec(), 0);
+        builder.append(&header, &[][..]).unwrap();
+        let error = builder.set_symbol_extractor(extract_words).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot set a symbol extractor after entries have been written"
+        );
+        let header = Header::new(b"bad.o".to_vec(), 0);
+        let error = builder.append(&header, &[][..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "bad.o: not an object file");
+
+        let writer = Cursor::new(Vec::new());
+        let mut builder =
+            Builder::new_with_variant(writer, Variant::Common).unwrap();
+        let error = builder.set_symbol_extractor(extract_words).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Common-variant archives cannot have a symbol table"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Symbol table refers to entry \\\"bar.o\\\", \
+                               which was never appended")]
+    fn build_archive_with_symbol_for_missing_entry() {
+        let symbols = vec![(b"baz".to_vec(), b"bar.o".to_vec())];
+        let mut builder =
+            Builder::new_with_symbol_table(Cursor::new(Vec::new()), symbols)
+                .unwrap();
+        let header = Header::new(b"foo.o".to_vec(), 4);
+        builder.append(&header, "foo\n".as_bytes()).unwrap();
+        builder.into_inner().unwrap();
+    }
+
+    #[test]
+    fn build_staged_bsd_archive_with_symbol_table() {
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        let header1 = Header::new(b"foo.o".to_vec(), 7);
+        builder.append(header1, "foobar\n".as_bytes());
+        let header2 = Header::new(b"bar.o".to_vec(), 0);
+        builder.append_bytes(header2, b"baz\n".to_vec());
+        builder.add_symbol(b"foobar".to_vec(), b"foo.o".to_vec());
+        builder.add_symbol(b"baz".to_vec(), b"bar.o".to_vec());
+        builder.add_symbol(b"quux".to_vec(), b"foo.o".to_vec());
+        let actual = builder.write(Vec::new()).unwrap();
+        let expected: &[u8] = b"\
+        !<arch>\n\
+        #1/12           0           0     0     0       60        `\n\
+        __.SYMDEF\x00\x00\x00\x18\x00\x00\x00\
+        \x00\x00\x00\x00\x80\x00\x00\x00\
+        \x07\x00\x00\x00\xc4\x00\x00\x00\
+        \x0b\x00\x00\x00\x80\x00\x00\x00\
+        \x10\x00\x00\x00foobar\x00baz\x00quux\x00\
+        foo.o           0           0     0     0       7         `\n\
+        foobar\n\n\
+        bar.o           0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(actual.as_slice(), expected);
+    }
+
+    #[test]
+    fn build_staged_gnu_archive_with_symbol_table() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let header1 = Header::new(
+            b"this_is_a_very_long_filename.o".to_vec(),
+            7,
+        );
+        builder.append(header1, "foobar\n".as_bytes());
+        let header2 = Header::new(b"bar.o".to_vec(), 4);
+        builder.append(header2, "baz\n".as_bytes());
+        builder.add_symbol(b"baz".to_vec(), b"bar.o".to_vec());
+        builder.add_symbol(
+            b"foobar".to_vec(),
+            b"this_is_a_very_long_filename.o".to_vec(),
+        );
+        let actual = builder.write(Vec::new()).unwrap();
+        let expected: &[u8] = b"\
+        !<arch>\n\
+        /               0           0     0     0       24        `\n\
+        \x00\x00\x00\x02\x00\x00\x00\xfc\x00\x00\x00\xb8\
+        baz\x00foobar\x00\x00\
+        //                                              32        `\n\
+        this_is_a_very_long_filename.o/\n\
+        /0              0           0     0     0       7         `\n\
+        foobar\n\n\
+        bar.o/          0           0     0     0       4         `\n\
+        baz\n";
+        assert_eq!(actual.as_slice(), expected);
+        let mut archive = Archive::new(Cursor::new(actual));
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"baz", b"foobar"];
+        assert_eq!(symbols, expected);
+        let entry = archive.jump_to_entry(1).unwrap();
+        assert_eq!(entry.header().identifier(), b"bar.o");
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be written in a common-variant header")]
+    fn build_staged_common_archive_with_long_filename() {
+        let mut builder = StagedBuilder::new(Variant::Common);
+        let header = Header::new(b"this_is_a_very_long_filename".to_vec(), 0);
+        builder.append_bytes(header, Vec::new());
+        builder.write(Vec::new()).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Common-variant archives cannot have a symbol \
+                               table")]
+    fn build_staged_common_archive_with_symbol_table() {
+        let mut builder = StagedBuilder::new(Variant::Common);
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0), Vec::new());
+        builder.add_symbol(b"foo".to_vec(), b"foo.o".to_vec());
+        builder.write(Vec::new()).unwrap();
+    }
+
+    #[test]
+    fn staged_gnu_archive_uses_64_bit_symbol_table_when_needed() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let header1 = Header::new(b"huge.o".to_vec(), 0x1_0000_0000);
+        builder.append(header1, io::empty());
+        let header2 = Header::new(b"small.o".to_vec(), 4);
+        builder.append(header2, io::empty());
+        builder.add_symbol(b"huge".to_vec(), b"huge.o".to_vec());
+        builder.add_symbol(b"small".to_vec(), b"small.o".to_vec());
+        let name_table = GnuNameTable::new(None.into_iter());
+        let (format, symbol_table) =
+            builder.layout_symbol_table(&name_table).unwrap();
+        assert_eq!(format, SymbolTableFormat::Gnu64);
+        // 8 (global header) + 60 (symbol table header) + 8 (count) + 16
+        // (offsets) + 11 (strings) + 5 (padding) = 108
+        assert_eq!(symbol_table[0].1, 108);
+        assert_eq!(symbol_table[1].1, 108 + 60 + 0x1_0000_0000);
+    }
+
+    #[test]
+    fn staged_bsd_archive_uses_64_bit_symbol_table_when_needed() {
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        let header1 = Header::new(b"huge.o".to_vec(), 0x1_0000_0000);
+        builder.append(header1, io::empty());
+        let header2 = Header::new(b"small.o".to_vec(), 4);
+        builder.append(header2, io::empty());
+        builder.add_symbol(b"huge".to_vec(), b"huge.o".to_vec());
+        builder.add_symbol(b"small".to_vec(), b"small.o".to_vec());
+        let name_table = GnuNameTable::new(None.into_iter());
+        let (format, symbol_table) =
+            builder.layout_symbol_table(&name_table).unwrap();
+        assert_eq!(format, SymbolTableFormat::Bsd64);
+        // 8 (global header) + 60 (symbol table header) + 12 (name) + 8
+        // (ranlib size) + 32 (ranlibs) + 8 (string table size) + 11
+        // (strings) + 5 (padding) = 144
+        assert_eq!(symbol_table[0].1, 144);
+        assert_eq!(symbol_table[1].1, 144 + 60 + 0x1_0000_0000);
+    }
+
+    #[test]
+    fn write_and_read_gnu_archive_with_64_bit_symbol_table() {
+        let symbols = vec![(b"foo".to_vec(), 100), (b"bar".to_vec(), 100)];
+        let mut output = b"!<arch>\n".to_vec();
+        write_symbol_table(&mut output, SymbolTableFormat::Gnu64, &symbols)
+            .unwrap();
+        output.extend_from_slice(
+            b"foo.o/          0           0     0     0       4         `\n\
+              foo\n",
+        );
+        let expected: &[u8] = b"\
+        !<arch>\n\
+        /SYM64/         0           0     0     0       32        `\n\
+        \x00\x00\x00\x00\x00\x00\x00\x02\
+        \x00\x00\x00\x00\x00\x00\x00\x64\
+        \x00\x00\x00\x00\x00\x00\x00\x64\
+        foo\x00bar\x00\
+        foo.o/          0           0     0     0       4         `\n\
+        foo\n";
+        assert_eq!(output.as_slice(), expected);
+        let mut archive = Archive::new(Cursor::new(output));
+        assert_eq!(archive.count_entries().unwrap(), 1);
+        assert_eq!(archive.variant(), Variant::GNU);
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"foo", b"bar"];
+        assert_eq!(symbols, expected);
+        assert_eq!(archive.find_symbol(b"bar").unwrap(), Some(0));
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.header().identifier(), b"foo.o");
+    }
+
+    fn staged_gnu_archive_for_editing() -> Vec<u8> {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let mut header1 =
+            Header::new(b"this_is_a_very_long_filename.o".to_vec(), 0);
+        header1.set_mtime(1000);
+        builder.append_bytes(header1, b"foobar\n".to_vec());
+        let mut header2 = Header::new(b"bar.o".to_vec(), 0);
+        header2.set_mtime(1000);
+        builder.append_bytes(header2, b"bar\n".to_vec());
+        let mut header3 = Header::new(b"baz.o".to_vec(), 0);
+        header3.set_mtime(1000);
+        builder.append_bytes(header3, b"baz\n".to_vec());
+        builder.add_symbol(
+            b"foobar".to_vec(),
+            b"this_is_a_very_long_filename.o".to_vec(),
+        );
+        builder.add_symbol(b"bar".to_vec(), b"bar.o".to_vec());
+        builder.add_symbol(b"baz".to_vec(), b"baz.o".to_vec());
+        builder.write(Vec::new()).unwrap()
+    }
+
+    fn read_entries<R: Read>(archive: &mut Archive<R>)
+                             -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry.unwrap();
+            let name = String::from_utf8(entry.header().identifier().to_vec())
+                .unwrap();
+            let mut data = String::new();
+            entry.read_to_string(&mut data).unwrap();
+            entries.push((name, data));
+        }
+        entries
+    }
+
+    #[test]
+    fn edit_gnu_archive() {
+        let input = staged_gnu_archive_for_editing();
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        assert_eq!(editor.variant(), Variant::GNU);
+        editor.delete(b"bar.o").unwrap();
+        let header = Header::new(b"baz.o".to_vec(), 0);
+        assert!(editor.replace_bytes(header, b"quux\n".to_vec()));
+        let header = Header::new(b"another_long_filename.o".to_vec(), 4);
+        assert!(editor.replace(header, "new\n".as_bytes()));
+        editor.add_symbol(b"quux".to_vec(), b"baz.o".to_vec());
+        editor.move_before(b"baz.o", b"this_is_a_very_long_filename.o")
+            .unwrap();
+        let identifiers: Vec<&[u8]> = editor
+            .headers()
+            .iter()
+            .map(|header| header.identifier())
+            .collect();
+        let expected: Vec<&[u8]> = vec![
+            b"baz.o",
+            b"this_is_a_very_long_filename.o",
+            b"another_long_filename.o",
+        ];
+        assert_eq!(identifiers, expected);
+        let output = editor.write(Vec::new()).unwrap();
+
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        // The symbols of the deleted and replaced members are gone.
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"foobar", 1), (b"quux", 0)];
+        assert_eq!(symbols, expected);
+        assert_eq!(archive.variant(), Variant::GNU);
+        let entries = read_entries(&mut archive);
+        let expected = vec![
+            ("baz.o".to_string(), "quux\n".to_string()),
+            (
+                "this_is_a_very_long_filename.o".to_string(),
+                "foobar\n".to_string(),
+            ),
+            ("another_long_filename.o".to_string(), "new\n".to_string()),
+        ];
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn edit_archive_only_if_newer() {
+        let input = staged_gnu_archive_for_editing();
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        editor.set_only_if_newer(true);
+        let mut header = Header::new(b"bar.o".to_vec(), 0);
+        header.set_mtime(1000);
+        assert!(!editor.replace_bytes(header, b"old\n".to_vec()));
+        let mut header = Header::new(b"baz.o".to_vec(), 0);
+        header.set_mtime(1001);
+        assert!(editor.replace_bytes(header, b"new\n".to_vec()));
+        editor.move_after(b"this_is_a_very_long_filename.o", b"baz.o")
+            .unwrap();
+        editor.move_to_end(b"bar.o").unwrap();
+        let output = editor.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(output.as_slice());
+        let entries = read_entries(&mut archive);
+        let expected = vec![
+            ("baz.o".to_string(), "new\n".to_string()),
+            (
+                "this_is_a_very_long_filename.o".to_string(),
+                "foobar\n".to_string(),
+            ),
+            ("bar.o".to_string(), "bar\n".to_string()),
+        ];
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn edit_archive_with_missing_members() {
+        let input = staged_gnu_archive_for_editing();
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        let error = editor.delete(b"foo.o").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            error.to_string(),
+            "No member named \"foo.o\" in the archive"
+        );
+        let error = editor.move_before(b"bar.o", b"foo.o").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "No member named \"foo.o\" in the archive"
+        );
+        assert_eq!(editor.headers().len(), 3);
+    }
+
+    #[test]
+    fn staged_archive_with_symbol_extractor() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append(Header::new(b"foo.o".to_vec(), 12),
+                       "foobar quux\n".as_bytes());
+        builder.append_bytes(Header::new(b"bar.o".to_vec(), 0),
+                             b"baz\n".to_vec());
+        builder.add_symbol(b"extra".to_vec(), b"bar.o".to_vec());
+        let expected = builder.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(expected.clone()));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        assert_eq!(symbols, vec![(&b"extra"[..], 1)]);
+
+        // Extracted symbols come before those added with add_symbol, and an
+        // entry given as a reader is still written after being read.
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append(Header::new(b"foo.o".to_vec(), 12),
+                       "foobar quux\n".as_bytes());
+        builder.append_bytes(Header::new(b"bar.o".to_vec(), 0),
+                             b"baz\n".to_vec());
+        builder.add_symbol(b"extra".to_vec(), b"bar.o".to_vec());
+        builder.set_symbol_extractor(extract_words);
+        let output = builder.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> = vec![
+            (b"foobar", 0),
+            (b"quux", 0),
+            (b"baz", 1),
+            (b"extra", 1),
+        ];
+        assert_eq!(symbols, expected);
+        let mut entry = archive.jump_to_entry(0).unwrap();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "foobar quux\n");
+    }
+
+    #[test]
+    fn edit_archive_with_symbol_extractor() {
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0),
+                             b"foobar\n".to_vec());
+        builder.append_bytes(Header::new(b"bar.o".to_vec(), 0),
+                             b"baz\n".to_vec());
+        builder.add_symbol(b"stale".to_vec(), b"bar.o".to_vec());
+        let input = builder.write(Vec::new()).unwrap();
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        editor.replace_bytes(Header::new(b"foo.o".to_vec(), 0),
+                             b"alpha beta\n".to_vec());
+        editor.set_symbol_extractor(extract_words);
+        let output = editor.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"alpha", 0), (b"beta", 0), (b"baz", 1)];
+        assert_eq!(symbols, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "object")]
+    fn regenerate_symbol_table() {
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        let mut header = Header::new(b"zeta.obj".to_vec(), 0);
+        header.set_mtime(1000);
+        builder.append_bytes(header, import_object(b"zeta", false));
+        builder.append_bytes(Header::new(b"README".to_vec(), 0),
+                             b"hello\n".to_vec());
+        builder.add_symbol(b"stale".to_vec(), b"README".to_vec());
+        // Import libraries often have several members with the same name.
+        let header = Header::new(b"zeta.obj".to_vec(), 0);
+        builder.append_bytes(header, import_object(b"eta", true));
+        let input = builder.write(Vec::new()).unwrap();
+
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        let data = import_object(b"alpha", true);
+        let header = Header::new(b"alpha.obj".to_vec(), data.len() as u64);
+        editor.replace(header, Cursor::new(data));
+        editor.set_regenerate_symbol_table(true);
+        editor.set_sorted_symbol_table(true);
+        let mut policy = MetadataPolicy::new();
+        policy.set_deterministic(true);
+        editor.set_metadata_policy(policy);
+        let output = editor.write(Vec::new()).unwrap();
+        assert!(output[8..].starts_with(b"#1/16 "));
+        assert_eq!(&output[68..84], b"__.SYMDEF SORTED");
+
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> = vec![
+            (b"__imp_alpha", 3),
+            (b"__imp_eta", 2),
+            (b"__imp_zeta", 0),
+            (b"zeta", 0),
+        ];
+        assert_eq!(symbols, expected);
+        let entry = archive.jump_to_entry(0).unwrap();
+        assert_eq!(entry.header().mtime(), 0);
+        drop(entry);
+
+        // The sorted flavor is kept when the archive is edited again.
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        editor.set_regenerate_symbol_table(true);
+        editor.delete(b"zeta.obj").unwrap();
+        let output = editor.write(Vec::new()).unwrap();
+        assert_eq!(&output[68..84], b"__.SYMDEF SORTED");
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<&[u8]> = archive.symbols().unwrap().collect();
+        let expected: Vec<&[u8]> = vec![b"__imp_alpha", b"__imp_eta"];
+        assert_eq!(symbols, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "object")]
+    fn regenerate_symbol_table_of_malformed_object() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let header = Header::new(b"bad.o".to_vec(), 0);
+        builder.append_bytes(header, b"\x7fELF\x02\x01\x01".to_vec());
+        let input = builder.write(Vec::new()).unwrap();
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        editor.set_regenerate_symbol_table(true);
+        let error = editor.write(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "bad.o: Malformed ELF object file (unexpected end of data)"
+        );
+    }
+
+    #[test]
+    fn edit_archive_with_stale_symbol_table() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       12        `\n\
+        \x00\x00\x00\x01\x00\x00\x03\xe7foo\x00\
+        foo.o/          0           0     0     0       4         `\n\
+        foo\n";
+        let archive = Archive::new(Cursor::new(input as &[u8]));
+        let editor = ArchiveEditor::new(archive).unwrap();
+        let error = editor.write(Vec::new()).unwrap_err();
+        let expected = Error::InvalidSymbolOffset {
+            symbol: b"foo".to_vec(),
+            entry_offset: 999,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+
+        let archive = Archive::new(Cursor::new(input as &[u8]));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        editor.set_symbol_extractor(extract_words);
+        let output = editor.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> = vec![(b"foo", 0)];
+        assert_eq!(symbols, expected);
+    }
+
+    #[test]
+    fn edit_archive_with_sorted_64_bit_symbol_table() {
+        let input = b"\
+        !<arch>\n\
+        #1/20           0           0     0     0       100       `\n\
+        __.SYMDEF_64 SORTED\x00\
+        \x30\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x04\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x0b\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x10\x00\x00\x00\x00\x00\x00\x00\
+        baz\x00foobar\x00quux\x00\
+        foo.o           1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let archive = Archive::new(Cursor::new(input as &[u8]));
+        let editor = ArchiveEditor::new(archive).unwrap();
+        let output = editor.write(Vec::new()).unwrap();
+        // The table is rewritten with 64-bit offsets, and still sorted.
+        assert!(output[8..].starts_with(b"#1/20 "));
+        assert_eq!(&output[68..88], b"__.SYMDEF_64 SORTED\0");
+        assert_eq!(output.as_slice(), &input[..]);
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"baz", 0), (b"foobar", 0), (b"quux", 0)];
+        assert_eq!(symbols, expected);
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.header().identifier(), b"foo.o");
+    }
+
+    #[test]
+    fn edit_thin_archive() {
+        let input = b"\
+        !<thin>\n\
+        foo.txt/        0           0     0     0       7         `\n";
+        let archive = Archive::new(Cursor::new(input as &[u8]));
+        let error = ArchiveEditor::new(archive).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "ArchiveEditor cannot edit thin archives"
+        );
     }
 
-    fn append_file_id(&mut self, id: Vec<u8>, file: &mut File) -> Result<()> {
-        let metadata = try!(file.metadata());
-        let header = Header::from_metadata(id, &metadata);
-        self.append(&header, file)
+    #[test]
+    fn edit_archive_in_place() {
+        let dir = TestDir::new("edit-in-place");
+        let path = dir.join("foo.a");
+        let mut builder = Builder::new(File::create(&path).unwrap());
+        let header = Header::new(b"foo.o".to_vec(), 4);
+        builder.append(&header, "foo\n".as_bytes()).unwrap();
+        let header = Header::new(b"bar.o".to_vec(), 3);
+        builder.append(&header, "bar".as_bytes()).unwrap();
+        builder.into_inner().unwrap();
+
+        let mut editor = ArchiveEditor::new(Archive::open(&path).unwrap())
+            .unwrap();
+        editor.delete(b"foo.o").unwrap();
+        let header = Header::new(b"baz.o".to_vec(), 0);
+        editor.replace_bytes(header, b"baz".to_vec());
+        editor.write_to_path(&path).unwrap();
+
+        let mut archive = Archive::open(&path).unwrap();
+        let entries = read_entries(&mut archive);
+        let expected = vec![
+            ("bar.o".to_string(), "bar".to_string()),
+            ("baz.o".to_string(), "baz".to_string()),
+        ];
+        assert_eq!(entries, expected);
+        // The temporary file should be gone.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
     }
-}
 
-#[cfg(unix)]
-fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
-    Ok(string.as_bytes().to_vec())
-}
+    #[test]
+    fn write_atomically_past_stale_temp_files() {
+        let dir = TestDir::new("write-atomically");
+        let path = dir.join("foo.a");
+        fs::write(&path, b"old").unwrap();
+        // Leave behind the temporary files that a crashed process with the
+        // same ID would have.
+        let next = TEMP_FILE_COUNTER.load(Ordering::Relaxed);
+        for count in next..next + 3 {
+            let name = format!(".foo.a.{}.{}.tmp", process::id(), count);
+            fs::write(dir.join(name), b"stale").unwrap();
+        }
+        write_atomically(&path, |mut writer| {
+            writer.write_all(b"new")?;
+            Ok(writer)
+        }).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
 
-#[cfg(windows)]
-fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
-    let mut bytes = Vec::<u8>::new();
-    for wide in string.encode_wide() {
-        // Little-endian:
-        bytes.push((wide & 0xff) as u8);
-        bytes.push((wide >> 8) as u8);
+        // Threads writing the same file at once each get their own
+        // temporary file.
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    write_atomically(&path, |mut writer| {
+                        writer.write_all(b"threaded")?;
+                        Ok(writer)
+                    })
+                })
+            })
+            .collect();
+        for thread in threads.into_iter() {
+            thread.join().unwrap().unwrap();
+        }
+        assert_eq!(fs::read(&path).unwrap(), b"threaded");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
     }
-    Ok(bytes)
-}
 
-#[cfg(not(any(unix, windows)))]
-fn osstr_to_bytes(string: &OsStr) -> Result<Vec<u8>> {
-    let utf8: &str = try!(string.to_str().ok_or_else(|| {
-        Error::new(ErrorKind::InvalidData, "Non-UTF8 file name")
-    }));
-    Ok(utf8.as_bytes().to_vec())
-}
+    #[test]
+    fn replaced_member_loses_its_symbols() {
+        let dir = TestDir::new("replace-symbols");
+        let path = dir.join("bar.o");
+        fs::write(&path, b"quux\n").unwrap();
+        let input = staged_gnu_archive_for_editing();
 
-// ========================================================================= //
+        let archive = Archive::new(Cursor::new(input.clone()));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        assert!(editor.replace_path(&path).unwrap());
+        let output = editor.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> = vec![(b"foobar", 0), (b"baz", 2)];
+        assert_eq!(symbols, expected);
 
-#[cfg(test)]
-mod tests {
-    use super::{Archive, Builder, Header, Variant};
-    use std::io::{Cursor, Read, Seek, SeekFrom};
-    use std::str;
+        let archive = Archive::new(Cursor::new(input));
+        let mut editor = ArchiveEditor::new(archive).unwrap();
+        assert!(editor.replace_path(&path).unwrap());
+        editor.set_symbol_extractor(extract_words);
+        let output = editor.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"foobar", 0), (b"quux", 1), (b"baz", 2)];
+        assert_eq!(symbols, expected);
+    }
 
     #[test]
-    fn build_common_archive() {
-        let mut builder = Builder::new(Vec::new());
-        let mut header1 = Header::new(b"foo.txt".to_vec(), 7);
+    fn build_gnu_thin_archive() {
+        let names = vec![b"sub/foo.txt".to_vec(), b"bar.txt".to_vec()];
+        let symbols = vec![(b"baz".to_vec(), b"bar.txt".to_vec())];
+        let mut builder = ThinBuilder::new_with_symbol_table(
+            Cursor::new(Vec::new()),
+            names,
+            symbols,
+        ).unwrap();
+        let mut header1 = Header::new(b"sub/foo.txt".to_vec(), 7);
         header1.set_mtime(1487552916);
         header1.set_uid(501);
         header1.set_gid(20);
         header1.set_mode(0o100644);
-        builder.append(&header1, "foobar\n".as_bytes()).unwrap();
-        let header2 = Header::new(b"baz.txt".to_vec(), 4);
-        builder.append(&header2, "baz\n".as_bytes()).unwrap();
-        let actual = builder.into_inner().unwrap();
-        let expected = "\
-        !<arch>\n\
-        foo.txt         1487552916  501   20    100644  7         `\n\
-        foobar\n\n\
-        baz.txt         0           0     0     0       4         `\n\
-        baz\n";
-        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+        builder.append(&header1).unwrap();
+        let header2 = Header::new(b"bar.txt".to_vec(), 4);
+        builder.append(&header2).unwrap();
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected: &[u8] = b"\
+        !<thin>\n\
+        /               0           0     0     0       12        `\n\
+        \x00\x00\x00\x01\x00\x00\x00\xdebaz\x00\
+        //                                              22        `\n\
+        sub/foo.txt/\n\
+        bar.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        /13             0           0     0     0       4         `\n";
+        assert_eq!(actual.as_slice(), expected);
     }
 
     #[test]
-    fn build_bsd_archive_with_long_filenames() {
-        let mut builder = Builder::new(Vec::new());
-        let mut header1 = Header::new(b"short".to_vec(), 1);
-        header1.set_identifier(b"this_is_a_very_long_filename.txt".to_vec());
+    fn build_gnu_thin_archive_with_staged_builder() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.set_thin(true);
+        let mut header1 = Header::new(b"sub/foo.txt".to_vec(), 7);
         header1.set_mtime(1487552916);
         header1.set_uid(501);
         header1.set_gid(20);
         header1.set_mode(0o100644);
-        header1.set_size(7);
+        builder.append(header1, io::empty());
+        builder.append(Header::new(b"bar.txt".to_vec(), 4), io::empty());
+        builder.add_symbol_for_entry(b"baz".to_vec(), 1);
+        let actual = builder.write(Vec::new()).unwrap();
+        let expected: &[u8] = b"\
+        !<thin>\n\
+        /               0           0     0     0       12        `\n\
+        \x00\x00\x00\x01\x00\x00\x00\xdebaz\x00\
+        //                                              22        `\n\
+        sub/foo.txt/\n\
+        bar.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        /13             0           0     0     0       4         `\n";
+        assert_eq!(actual.as_slice(), expected);
+
+        let mut builder = StagedBuilder::new(Variant::BSD);
+        builder.set_thin(true);
+        let error = builder.write(Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "Only GNU-variant archives can be thin");
+    }
+
+    #[test]
+    fn build_archive_with_symbols_for_entries_with_same_identifier() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0),
+                             b"first\n".to_vec());
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0),
+                             b"second\n".to_vec());
+        builder.add_symbol_for_entry(b"second".to_vec(), 1);
+        builder.add_symbol_for_entry(b"first".to_vec(), 0);
+        let output = builder.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        let symbols: Vec<(&[u8], usize)> =
+            archive.symbol_entries().unwrap().collect();
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"second", 1), (b"first", 0)];
+        assert_eq!(symbols, expected);
+
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0), Vec::new());
+        builder.add_symbol_for_entry(b"foo".to_vec(), 1);
+        let error = builder.write(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(),
+                   "Symbol table refers to entry index 1, but only 1 \
+                    entries were appended");
+    }
+
+    #[test]
+    fn build_and_read_gnu_thin_archive_from_paths() {
+        let dir = TestDir::new("build-thin");
+        fs::write(dir.join("foo.txt"), b"foobar\n").unwrap();
+        let path = dir.join("foo.txt");
+        let identifier = path.to_str().unwrap().as_bytes().to_vec();
+        let mut builder = ThinBuilder::new(Vec::new(), vec![identifier]);
+        builder.append_path(&path).unwrap();
+        let output = builder.into_inner().unwrap();
+        let mut archive = Archive::new(output.as_slice());
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().size(), 7);
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"foobar\n");
+        }
+        assert!(archive.next_entry().is_none());
+        assert!(archive.is_thin());
+    }
+
+    #[test]
+    fn build_aix_big_archive() {
+        let mut builder = AixBuilder::new(Cursor::new(Vec::new()));
+        let mut header = Header::new(b"foo.txt".to_vec(), 7);
+        header.set_mtime(1487552916);
+        header.set_uid(501);
+        header.set_gid(20);
+        header.set_mode(0o100644);
+        builder.append(&header, "foobar\n".as_bytes()).unwrap();
+        builder.add_symbol(b"foo".to_vec(), b"foo.txt".to_vec());
+        let actual = builder.into_inner().unwrap().into_inner();
+        let expected: &[u8] = b"\
+        <bigaf>\n\
+        258                 420                 0                   \
+        128                 128                 0                   \
+        7                   258                 0                   \
+        1487552916  501         20          100644      7   foo.txt\0`\n\
+        foobar\n\n\
+        48                  420                 128                 \
+        0           0           0           0           0   `\n\
+        1                   128                 foo.txt\0\
+        20                  0                   258                 \
+        0           0           0           0           0   `\n\
+        \x00\x00\x00\x00\x00\x00\x00\x01\
+        \x00\x00\x00\x00\x00\x00\x00\x80foo\0";
+        assert_eq!(actual.as_slice(), expected);
+    }
+
+    #[test]
+    fn build_empty_aix_big_archive() {
+        let builder = AixBuilder::new(Cursor::new(Vec::new()));
+        let actual = builder.into_inner().unwrap().into_inner();
+        let mut expected = b"<bigaf>\n".to_vec();
+        for _ in 0..6 {
+            expected.extend_from_slice(b"0                   ");
+        }
+        assert_eq!(actual, expected);
+        let mut archive = Archive::new(actual.as_slice());
+        assert!(archive.next_entry().is_none());
+        assert_eq!(archive.variant(), Variant::AIX);
+    }
+
+    #[test]
+    fn build_and_read_aix_big_archive_with_symbol_tables() {
+        let mut builder = AixBuilder::new(Cursor::new(Vec::new()));
+        let header1 = Header::new(b"foo32.o".to_vec(), 7);
         builder.append(&header1, "foobar\n".as_bytes()).unwrap();
-        let header2 = Header::new(
-            b"and_this_is_another_very_long_filename.txt".to_vec(),
-            4,
-        );
+        let header2 = Header::new(b"bar64.o".to_vec(), 4);
         builder.append(&header2, "baz\n".as_bytes()).unwrap();
-        let actual = builder.into_inner().unwrap();
-        let expected = "\
-        !<arch>\n\
-        #1/32           1487552916  501   20    100644  39        `\n\
-        this_is_a_very_long_filename.txtfoobar\n\n\
-        #1/44           0           0     0     0       48        `\n\
-        and_this_is_another_very_long_filename.txt\x00\x00baz\n";
-        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+        builder.add_symbol(b"foobar".to_vec(), b"foo32.o".to_vec());
+        builder.add_symbol_64(b"baz".to_vec(), b"bar64.o".to_vec());
+        builder.add_symbol_64(b"quux".to_vec(), b"bar64.o".to_vec());
+        let output = builder.into_inner().unwrap().into_inner();
+        let mut archive = Archive::new(Cursor::new(output));
+        assert_eq!(archive.count_entries().unwrap(), 2);
+        assert_eq!(archive.variant(), Variant::AIX);
+        let symbols = archive
+            .symbol_entries()
+            .unwrap()
+            .map(|(symbol, index)| (symbol.to_vec(), index))
+            .collect::<Vec<(Vec<u8>, usize)>>();
+        let expected = vec![
+            (b"foobar".to_vec(), 0),
+            (b"baz".to_vec(), 1),
+            (b"quux".to_vec(), 1),
+        ];
+        assert_eq!(symbols, expected);
+        {
+            let mut entry = archive.jump_to_symbol(b"quux").unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar64.o");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, b"baz\n");
+        }
+        {
+            let mut entry = archive.jump_to_entry(0).unwrap();
+            assert_eq!(entry.header().identifier(), b"foo32.o");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, b"foobar\n");
+        }
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar64.o");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, b"baz\n");
+        }
+        assert!(archive.next_entry().is_none());
     }
 
     #[test]
-    fn build_bsd_archive_with_space_in_filename() {
-        let mut builder = Builder::new(Vec::new());
-        let header = Header::new(b"foo bar".to_vec(), 4);
-        builder.append(&header, "baz\n".as_bytes()).unwrap();
-        let actual = builder.into_inner().unwrap();
-        let expected = "\
-        !<arch>\n\
-        #1/8            0           0     0     0       12        `\n\
-        foo bar\x00baz\n";
-        assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+    #[should_panic(expected = "Symbol table refers to entry \\\"bar.o\\\", \
+                               which was never appended")]
+    fn build_aix_big_archive_with_symbol_for_missing_entry() {
+        let mut builder = AixBuilder::new(Cursor::new(Vec::new()));
+        let header = Header::new(b"foo.o".to_vec(), 7);
+        builder.append(&header, "foobar\n".as_bytes()).unwrap();
+        builder.add_symbol_64(b"baz".to_vec(), b"bar.o".to_vec());
+        builder.into_inner().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "StagedBuilder cannot write AIX big archives")]
+    fn build_staged_aix_big_archive() {
+        let mut builder = StagedBuilder::new(Variant::AIX);
+        builder.append_bytes(Header::new(b"foo.o".to_vec(), 0), Vec::new());
+        builder.write(Vec::new()).unwrap();
     }
 
     #[test]
@@ -1146,44 +6794,294 @@ mod tests {
     }
 
     #[test]
-    fn read_gnu_archive_with_space_in_filename() {
-        let input = "\
+    fn read_gnu_archive_with_space_in_filename() {
+        let input = "\
+        !<arch>\n\
+        foo bar/        0           0     0     0       4         `\n\
+        baz\n";
+        let mut archive = Archive::new(input.as_bytes());
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), "foo bar".as_bytes());
+            assert_eq!(entry.header().size(), 4);
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], "baz\n".as_bytes());
+        }
+        assert_eq!(archive.variant(), Variant::GNU);
+    }
+
+    #[test]
+    fn read_gnu_archive_with_symbol_lookup_table() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       15        `\n\
+        \x00\x00\x00\x01\x00\x00\x00\xb2foobar\x00\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        foobar\n";
+        let mut archive = Archive::new(input as &[u8]);
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(
+                entry.header().identifier(),
+                "this_is_a_very_long_filename.txt".as_bytes()
+            );
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], "foobar\n".as_bytes());
+        }
+    }
+
+    #[test]
+    fn read_gnu_thin_archive() {
+        let dir = TestDir::new("read-thin");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/foo.txt"), b"foobar\n").unwrap();
+        fs::write(dir.join("bar.txt"), b"baz\n").unwrap();
+        let input = b"\
+        !<thin>\n\
+        /               0           0     0     0       16        `\n\
+        \x00\x00\x00\x01\x00\x00\x00\xe2baz\x00\x00\x00\x00\x00\
+        //                                              22        `\n\
+        sub/foo.txt/\n\
+        bar.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        /13             0           0     0     0       4         `\n";
+        fs::write(dir.join("thin.a"), &input[..]).unwrap();
+        let mut archive = Archive::open(dir.join("thin.a")).unwrap();
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"sub/foo.txt");
+            assert_eq!(entry.header().mtime(), 1487552916);
+            assert_eq!(entry.header().size(), 7);
+            // Read only part of the data, to make sure that the archive
+            // doesn't try to skip over the rest of it.
+            let mut buffer = [0; 3];
+            entry.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer, b"foo");
+        }
+        assert!(archive.is_thin());
+        assert_eq!(archive.variant(), Variant::GNU);
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar.txt");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"baz\n");
+        }
+        assert!(archive.next_entry().is_none());
+        assert_eq!(archive.count_entries().unwrap(), 2);
+        {
+            let mut entry = archive.jump_to_symbol(b"baz").unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar.txt");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"baz\n");
+        }
+        {
+            let mut entry = archive.jump_to_entry(0).unwrap();
+            let mut buffer = [0; 3];
+            entry.seek(SeekFrom::End(-4)).unwrap();
+            entry.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer, b"bar");
+        }
+    }
+
+    #[test]
+    fn read_gnu_thin_archive_with_missing_member() {
+        let dir = TestDir::new("read-thin-missing");
+        fs::write(dir.join("bar.txt"), b"baz\n").unwrap();
+        let input = b"\
+        !<thin>\n\
+        foo.txt/        0           0     0     0       7         `\n\
+        bar.txt/        0           0     0     0       4         `\n";
+        fs::write(dir.join("thin.a"), &input[..]).unwrap();
+        let mut archive = Archive::open(dir.join("thin.a")).unwrap();
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"foo.txt");
+            let mut buffer = Vec::new();
+            let error = entry.read_to_end(&mut buffer).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::NotFound);
+        }
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar.txt");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"baz\n");
+        }
+        assert!(archive.next_entry().is_none());
+    }
+
+    #[test]
+    fn read_aix_big_archive() {
+        // There is some free space between the two members, which should be
+        // skipped over.
+        let input = "\
+        <bigaf>\n\
+        0                   0                   0                   \
+        128                 264                 0                   \
+        6                   264                 0                   \
+        1487552916  501         20          100644      5   a.txt\0`\n\
+        hello\nXXXXXXXXXX\
+        6                   0                   128                 \
+        1487552349  42          12345       100664      5   b.txt\0`\n\
+        world\n";
+        let mut archive = Archive::new(input.as_bytes());
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), "a.txt".as_bytes());
+            assert_eq!(entry.header().mtime(), 1487552916);
+            assert_eq!(entry.header().uid(), 501);
+            assert_eq!(entry.header().gid(), 20);
+            assert_eq!(entry.header().mode(), 0o100644);
+            assert_eq!(entry.header().size(), 6);
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], "hello\n".as_bytes());
+        }
+        {
+            // Don't read this entry's data; it should still be skipped.
+            let entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), "b.txt".as_bytes());
+            assert_eq!(entry.header().mtime(), 1487552349);
+            assert_eq!(entry.header().uid(), 42);
+            assert_eq!(entry.header().gid(), 12345);
+            assert_eq!(entry.header().mode(), 0o100664);
+            assert_eq!(entry.header().size(), 6);
+        }
+        assert!(archive.next_entry().is_none());
+        assert_eq!(archive.variant(), Variant::AIX);
+    }
+
+    #[test]
+    fn read_aix_big_archive_with_backwards_link() {
+        let input = "\
+        <bigaf>\n\
+        0                   0                   0                   \
+        128                 264                 0                   \
+        6                   100                 0                   \
+        1487552916  501         20          100644      5   a.txt\0`\n\
+        hello\nXXXXXXXXXX\
+        6                   0                   128                 \
+        1487552349  42          12345       100664      5   b.txt\0`\n\
+        world\n";
+        let mut archive = Archive::new(input.as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "AIX big archive member at offset 128 links backwards to offset \
+             100"
+        );
+        let expected = Error::AixLinksBackwards {
+            offset: 128,
+            next: 100,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_aix_big_archive_with_overlapping_member() {
+        let input = "\
+        <bigaf>\n\
+        0                   0                   0                   \
+        128                 264                 0                   \
+        6                   200                 0                   \
+        1487552916  501         20          100644      5   a.txt\0`\n\
+        hello\nXXXXXXXXXX\
+        6                   0                   128                 \
+        1487552349  42          12345       100664      5   b.txt\0`\n\
+        world\n";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.next_entry().unwrap().unwrap();
+        let error = archive.next_entry().unwrap().err().unwrap();
+        let expected = Error::AixMemberOverlaps {
+            offset: 200,
+            position: 254,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_truncated_archives() {
+        let gnu_table = "\
         !<arch>\n\
-        foo bar/        0           0     0     0       4         `\n\
-        baz\n";
-        let mut archive = Archive::new(input.as_bytes());
-        {
-            let mut entry = archive.next_entry().unwrap().unwrap();
-            assert_eq!(entry.header().identifier(), "foo bar".as_bytes());
-            assert_eq!(entry.header().size(), 4);
-            let mut buffer = Vec::new();
-            entry.read_to_end(&mut buffer).unwrap();
-            assert_eq!(&buffer as &[u8], "baz\n".as_bytes());
+        /               0           0     0     0       8         `\n\
+        \x00\x00\x00\x02\x00\x00";
+        let aix_header = "\
+        <bigaf>\n\
+        0                   0                   0                   \
+        128                 128                 0                   \
+        6                   0                   0                   \
+        1487552916  501         20";
+        let padding = "\
+        !<arch>\n\
+        foo.txt         0           0     0     0       3         `\n\
+        foo";
+        let cases = [
+            ("!<ar", Error::TruncatedGlobalHeader),
+            ("<bigaf>\n0   ", Error::TruncatedGlobalHeader),
+            (aix_header, Error::TruncatedHeader { offset: 128 }),
+            (padding, Error::TruncatedEntry { offset: 8 }),
+        ];
+        for &(input, ref expected) in cases.iter() {
+            let mut archive = Archive::new(input.as_bytes());
+            let error = loop {
+                match archive.next_entry() {
+                    Some(Ok(_)) => {}
+                    Some(Err(error)) => break error,
+                    None => panic!("No error for {:?}", input),
+                }
+            };
+            assert_eq!(Error::from_io(&error), Some(expected));
+            assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
         }
-        assert_eq!(archive.variant(), Variant::GNU);
+        let mut archive = Archive::new(Cursor::new(gnu_table.as_bytes()));
+        let error = archive.symbols().err().unwrap();
+        let expected = Error::TruncatedSymbolTable { offset: 8 };
+        assert_eq!(Error::from_io(&error), Some(&expected));
     }
 
     #[test]
-    fn read_gnu_archive_with_symbol_lookup_table() {
+    fn read_coff_archive() {
         let input = b"\
         !<arch>\n\
-        /               0           0     0     0       15        `\n\
-        \x00\x00\x00\x01\x00\x00\x00\xb2foobar\x00\n\
-        //                                              34        `\n\
-        this_is_a_very_long_filename.txt/\n\
-        /0              1487552916  501   20    100644  7         `\n\
-        foobar\n";
+        /               0           0     0     0       28        `\n\
+        \x00\x00\x00\x03\x00\x00\x01\x12\x00\x00\x01\x52\x00\x00\x01\x52\
+        foo\x00bar\x00baz\x00\
+        /               0           0     0     0       34        `\n\
+        \x02\x00\x00\x00\x12\x01\x00\x00\x52\x01\x00\x00\
+        \x03\x00\x00\x00\x02\x00\x02\x00\x01\x00\
+        bar\x00baz\x00foo\x00\
+        //                                              23        `\n\
+        a_long_member_name.obj\x00\n\
+        foo.obj/        1487552916  0     0     100644  4         `\n\
+        FOO\n\
+        /0              1487552916  0     0     100644  4         `\n\
+        BAR\n";
         let mut archive = Archive::new(input as &[u8]);
+        {
+            let mut entry = archive.next_entry().unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), "foo.obj".as_bytes());
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], "FOO\n".as_bytes());
+        }
         {
             let mut entry = archive.next_entry().unwrap().unwrap();
             assert_eq!(
                 entry.header().identifier(),
-                "this_is_a_very_long_filename.txt".as_bytes()
+                "a_long_member_name.obj".as_bytes()
             );
             let mut buffer = Vec::new();
             entry.read_to_end(&mut buffer).unwrap();
-            assert_eq!(&buffer as &[u8], "foobar\n".as_bytes());
+            assert_eq!(&buffer as &[u8], "BAR\n".as_bytes());
         }
+        assert!(archive.next_entry().is_none());
+        assert_eq!(archive.variant(), Variant::COFF);
     }
 
     #[test]
@@ -1272,6 +7170,294 @@ mod tests {
         archive.next_entry().unwrap().unwrap();
     }
 
+    #[test]
+    fn invalid_header_fields_give_field_and_offset() {
+        let cases: Vec<(&str, &str, &[u8], u64)> = vec![
+            (
+                "!<arch>\n\
+                 foo.txt         helloworld  501   20    100644  7         `\n\
+                 foobar\n\n",
+                "timestamp",
+                b"helloworld  ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 foo.txt         1487552916  foo   20    100644  7         `\n\
+                 foobar\n\n",
+                "owner ID",
+                b"foo   ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 foo.txt         1487552916  501   bar   100644  7         `\n\
+                 foobar\n\n",
+                "group ID",
+                b"bar   ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 foo.txt         1487552916  501   20    foobar  7         `\n\
+                 foobar\n\n",
+                "file mode",
+                b"foobar  ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 foo.txt         1487552916  501   20    100644  whatever  `\n\
+                 foobar\n\n",
+                "file size",
+                b"whatever  ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 #1/foobar       1487552916  501   20    100644  39        `\n\
+                 this_is_a_very_long_filename.txtfoobar\n\n",
+                "BSD filename length",
+                b"foobar       ",
+                8,
+            ),
+            (
+                "!<arch>\n\
+                 //                                              34        `\n\
+                 this_is_a_very_long_filename.txt/\n\
+                 /foobar         1487552916  501   20    100644  7         `\n\
+                 foobar\n\n",
+                "GNU filename index",
+                b"foobar         ",
+                102,
+            ),
+        ];
+        for (input, field, bytes, offset) in cases.into_iter() {
+            let mut archive = Archive::new(input.as_bytes());
+            let error = archive.next_entry().unwrap().err().unwrap();
+            let expected = Error::InvalidNumber {
+                field: field,
+                bytes: bytes.to_vec(),
+                offset: offset,
+            };
+            assert_eq!(Error::from_io(&error), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn read_gnu_archive_with_out_of_range_filename_index() {
+        let input = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /40             1487552916  501   20    100644  7         `\n\
+        foobar\n\n";
+        let mut archive = Archive::new(input.as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "GNU filename index (40) is past the end of the name table (34 \
+             bytes)"
+        );
+        let expected = Error::InvalidGnuNameIndex {
+            index: 40,
+            name_table_len: 34,
+            offset: 102,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_bsd_archive_with_too_long_filename() {
+        let input = "\
+        !<arch>\n\
+        #1/32           1487552916  501   20    100644  20        `\n\
+        this_is_a_very_long_filename.txt";
+        let mut archive = Archive::new(input.as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Entry size (20) smaller than extended entry identifier length \
+             (32)"
+        );
+        let expected = Error::InvalidBsdNameLength {
+            length: 32,
+            size: 20,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_archive_with_invalid_global_header() {
+        let mut archive = Archive::new("!<ARCH>\n".as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "Not an archive file (invalid global header)"
+        );
+        let expected = Error::InvalidGlobalHeader {
+            bytes: b"!<ARCH>\n".to_vec(),
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_archive_with_invalid_padding() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  7         `\n\
+        foobar\nX\
+        bar.awesome.txt 1487552919  501   20    100644  22        `\n\
+        This file is awesome!\n";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.next_entry().unwrap().unwrap();
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.to_string(), "Invalid padding byte");
+        let expected = Error::InvalidPadding {
+            byte: b'X',
+            offset: 75,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_archive_with_invalid_padding_after_jump() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  7         `\n\
+        foobar\nX\
+        bar.txt         1487552919  501   20    100644  4         `\n\
+        baz\n";
+        let mut archive = Archive::new(Cursor::new(input.as_bytes()));
+        archive.jump_to_entry(0).unwrap();
+        let error = archive.next_entry().unwrap().err().unwrap();
+        let expected = Error::InvalidPadding {
+            byte: b'X',
+            offset: 75,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_truncated_archive() {
+        let input = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  7         `\n\
+        foobar\n\n\
+        bar.awesome.txt 1487552919  501   20";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.next_entry().unwrap().unwrap();
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            error.to_string(),
+            "Unexpected EOF in the middle of archive entry header"
+        );
+        let expected = Error::TruncatedHeader { offset: 76 };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+        let error = io::Error::new(io::ErrorKind::UnexpectedEof, "foo");
+        assert_eq!(Error::from_io(&error), None);
+    }
+
+    #[test]
+    fn read_archive_with_huge_sizes() {
+        // These headers claim far more data than there is, which should be
+        // reported as truncation rather than allocated up front.
+        let input = "\
+        !<arch>\n\
+        //                                              9999999999`\n\
+        this_is_a_very_long_filename.txt/\n";
+        let mut archive = Archive::new(input.as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let expected = Error::TruncatedEntry { offset: 8 };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+
+        let input = "\
+        !<arch>\n\
+        #1/4000000000   1487552916  501   20    100644  4000000010`\n\
+        this_is_a_very_long_filename.txt";
+        let mut archive = Archive::new(input.as_bytes());
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let expected = Error::TruncatedIdentifier { offset: 8 };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn read_archive_with_limits() {
+        let input = "\
+        !<arch>\n\
+        //                                              34        `\n\
+        this_is_a_very_long_filename.txt/\n\
+        /0              1487552916  501   20    100644  7         `\n\
+        foobar\n\n";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.set_max_name_table_len(33);
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "GNU name table is too large (34 bytes, but the limit is 33)"
+        );
+        let expected = Error::NameTableTooLarge {
+            size: 34,
+            limit: 33,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+
+        let mut archive = Archive::new(input.as_bytes());
+        archive.set_max_name_table_len(34);
+        archive.set_max_identifier_len(31);
+        let error = archive.next_entry().unwrap().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Entry identifier is too long (32 bytes, but the limit is 31)"
+        );
+        let expected = Error::IdentifierTooLong {
+            length: 32,
+            limit: 31,
+            offset: 102,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+
+        let mut archive = Archive::new(input.as_bytes());
+        archive.set_max_identifier_len(32);
+        assert!(archive.next_entry().unwrap().is_ok());
+
+        // For BSD identifiers, the limit applies to the padded length.
+        let input = "\
+        !<arch>\n\
+        #1/32           1487552916  501   20    100644  39        `\n\
+        this_is_a_very_long_filename.txtfoobar\n\n";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.set_max_identifier_len(31);
+        let error = archive.next_entry().unwrap().err().unwrap();
+        let expected = Error::IdentifierTooLong {
+            length: 32,
+            limit: 31,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+
+        let input = "\
+        !<arch>\n\
+        foo.txt         1487552916  501   20    100644  7         `\n\
+        foobar\n\n";
+        let mut archive = Archive::new(input.as_bytes());
+        archive.set_max_identifier_len(6);
+        let error = archive.next_entry().unwrap().err().unwrap();
+        let expected = Error::IdentifierTooLong {
+            length: 7,
+            limit: 6,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
     #[test]
     fn seek_within_entry() {
         let input = "\
@@ -1516,6 +7702,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn jump_to_next_entry() {
+        let input = b"\
+        !<arch>\n\
+        foo.txt         0           0     0     0       7         `\n\
+        foobar\n\n\
+        baz.txt         0           0     0     0       4         `\n\
+        baz\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        assert_eq!(archive.count_entries().unwrap(), 2);
+        for &(index, expected) in &[(0, "foobar\n"), (1, "baz\n")] {
+            // Scanning leaves the reader at the end of the archive, and
+            // reading an entry leaves it before the next entry's header, so
+            // jumping to the next entry still has to seek.
+            let mut entry = archive.jump_to_entry(index).unwrap();
+            let mut buffer = String::new();
+            entry.read_to_string(&mut buffer).unwrap();
+            assert_eq!(&buffer, expected);
+        }
+        assert!(archive.next_entry().is_none());
+    }
+
     #[test]
     fn list_symbols_in_bsd_archive() {
         let input = b"\
@@ -1536,6 +7744,75 @@ mod tests {
         assert_eq!(symbols, expected);
     }
 
+    #[test]
+    fn list_symbols_with_limit() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       32        `\n\
+        \x00\x00\x00\x03\x00\x00\x00\x5c\x00\x00\x00\x5c\x00\x00\x00\x5c\
+        foobar\x00baz\x00quux\x00\
+        foo.o/          1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        archive.set_max_symbol_table_len(31);
+        assert_eq!(archive.count_entries().unwrap(), 1);
+        let error = archive.symbols().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Symbol table is too large (32 bytes, but the limit is 31)"
+        );
+        let expected = Error::SymbolTableTooLarge {
+            size: 32,
+            limit: 31,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        archive.set_max_symbol_table_len(32);
+        assert_eq!(archive.symbols().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn list_symbols_with_huge_symbol_count() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       32        `\n\
+        \xff\xff\xff\xff\x00\x00\x00\x5c\x00\x00\x00\x5c\x00\x00\x00\x5c\
+        foobar\x00baz\x00quux\x00\
+        foo.o/          1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        let error = archive.symbols().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn list_symbols_in_bsd_archive_with_invalid_name_offset() {
+        let input = b"\
+        !<arch>\n\
+        #1/12           0           0     0     0       60        `\n\
+        __.SYMDEF\x00\x00\x00\x18\x00\x00\x00\
+        \x00\x00\x00\x00\x80\x00\x00\x00\
+        \x07\x00\x00\x00\x80\x00\x00\x00\
+        \x20\x00\x00\x00\x80\x00\x00\x00\
+        \x10\x00\x00\x00foobar\x00baz\x00quux\x00\
+        foo.o/          1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        let error = archive.symbols().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Symbol table refers to a name at offset 32, past the end of its \
+             string table (16 bytes)"
+        );
+        let expected = Error::InvalidSymbolNameOffset {
+            name_offset: 32,
+            string_table_len: 16,
+            offset: 8,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
     #[test]
     fn list_sorted_symbols_in_bsd_archive() {
         let input = b"\
@@ -1556,6 +7833,111 @@ mod tests {
         assert_eq!(symbols, expected);
     }
 
+    #[test]
+    fn list_symbol_entries_in_bsd_archive() {
+        let input = b"\
+        !<arch>\n\
+        #1/12           0           0     0     0       60        `\n\
+        __.SYMDEF\x00\x00\x00\x18\x00\x00\x00\
+        \x00\x00\x00\x00\x80\x00\x00\x00\
+        \x07\x00\x00\x00\xcc\x00\x00\x00\
+        \x0b\x00\x00\x00\x80\x00\x00\x00\
+        \x10\x00\x00\x00foobar\x00baz\x00quux\x00\
+        foo.o           1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n\
+        bar.o           1487552916  501   20    100644  4         `\n\
+        baz\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        let symbols = archive
+            .symbol_entries()
+            .unwrap()
+            .map(|(name, index)| (name.to_vec(), index))
+            .collect::<Vec<(Vec<u8>, usize)>>();
+        let expected = vec![
+            (b"foobar".to_vec(), 0),
+            (b"baz".to_vec(), 1),
+            (b"quux".to_vec(), 0),
+        ];
+        assert_eq!(symbols, expected);
+        let entry = archive.jump_to_entry(symbols[1].1).unwrap();
+        assert_eq!(entry.header().identifier(), b"bar.o");
+    }
+
+    #[test]
+    fn find_symbols_in_gnu_archive() {
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let header1 = Header::new(b"foo.o".to_vec(), 0);
+        builder.append_bytes(header1, b"foo".to_vec());
+        let header2 = Header::new(b"bar.o".to_vec(), 0);
+        builder.append_bytes(header2, b"bar".to_vec());
+        builder.add_symbol(b"foo".to_vec(), b"foo.o".to_vec());
+        builder.add_symbol(b"bar".to_vec(), b"bar.o".to_vec());
+        builder.add_symbol(b"shared".to_vec(), b"bar.o".to_vec());
+        builder.add_symbol(b"shared".to_vec(), b"foo.o".to_vec());
+        let output = builder.write(Vec::new()).unwrap();
+        let mut archive = Archive::new(Cursor::new(output));
+        assert_eq!(archive.find_symbol(b"foo").unwrap(), Some(0));
+        assert_eq!(archive.find_symbol(b"bar").unwrap(), Some(1));
+        assert_eq!(archive.find_symbol(b"shared").unwrap(), Some(1));
+        assert_eq!(archive.find_symbol(b"missing").unwrap(), None);
+        {
+            let mut entry = archive.jump_to_symbol(b"bar").unwrap().unwrap();
+            assert_eq!(entry.header().identifier(), b"bar.o");
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer).unwrap();
+            assert_eq!(&buffer as &[u8], b"bar");
+        }
+        assert!(archive.jump_to_symbol(b"missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn list_symbol_entries_with_invalid_offset() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       32        `\n\
+        \x00\x00\x00\x03\x00\x00\x00\x5c\x00\x00\x00\x5c\x00\x00\x00\x5c\
+        foobar\x00baz\x00quux\x00\
+        foo.o/          1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        let error = archive.symbol_entries().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Symbol \"foobar\" refers to offset 92, which is not the start of \
+             an archive entry"
+        );
+        let expected = Error::InvalidSymbolOffset {
+            symbol: b"foobar".to_vec(),
+            entry_offset: 92,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
+
+    #[test]
+    fn list_symbols_in_bsd_archive_with_64_bit_symbol_table() {
+        let input = b"\
+        !<arch>\n\
+        #1/20           0           0     0     0       100       `\n\
+        __.SYMDEF_64 SORTED\x00\
+        \x30\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x04\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x0b\x00\x00\x00\x00\x00\x00\x00\xa8\x00\x00\x00\x00\x00\x00\x00\
+        \x10\x00\x00\x00\x00\x00\x00\x00\
+        baz\x00foobar\x00quux\x00\
+        foo.o           1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        assert_eq!(archive.count_entries().unwrap(), 1);
+        assert_eq!(archive.variant(), Variant::BSD);
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"baz", b"foobar", b"quux"];
+        assert_eq!(symbols, expected);
+        assert_eq!(archive.find_symbol(b"quux").unwrap(), Some(0));
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.header().identifier(), b"foo.o");
+    }
+
     #[test]
     fn list_symbols_in_gnu_archive() {
         let input = b"\
@@ -1572,6 +7954,89 @@ mod tests {
         let expected: Vec<&[u8]> = vec![b"foobar", b"baz", b"quux"];
         assert_eq!(symbols, expected);
     }
+
+    #[test]
+    fn list_sorted_symbols_in_coff_archive() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       28        `\n\
+        \x00\x00\x00\x03\x00\x00\x01\x12\x00\x00\x01\x52\x00\x00\x01\x52\
+        foo\x00bar\x00baz\x00\
+        /               0           0     0     0       34        `\n\
+        \x02\x00\x00\x00\x12\x01\x00\x00\x52\x01\x00\x00\
+        \x03\x00\x00\x00\x02\x00\x02\x00\x01\x00\
+        bar\x00baz\x00foo\x00\
+        //                                              23        `\n\
+        a_long_member_name.obj\x00\n\
+        foo.obj/        1487552916  0     0     100644  4         `\n\
+        FOO\n\
+        /0              1487552916  0     0     100644  4         `\n\
+        BAR\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        assert_eq!(archive.count_entries().unwrap(), 2);
+        assert_eq!(archive.variant(), Variant::COFF);
+        // The first linker member is the ordinary symbol table.
+        let symbols = archive.symbols().unwrap().collect::<Vec<&[u8]>>();
+        let expected: Vec<&[u8]> = vec![b"foo", b"bar", b"baz"];
+        assert_eq!(symbols, expected);
+        // The second linker member is sorted by name.
+        let sorted_symbols =
+            archive.sorted_symbols().unwrap().collect::<Vec<(&[u8], usize)>>();
+        let expected: Vec<(&[u8], usize)> =
+            vec![(b"bar", 1), (b"baz", 1), (b"foo", 0)];
+        assert_eq!(sorted_symbols, expected);
+        let mut entry = archive.jump_to_symbol(b"baz").unwrap().unwrap();
+        let mut buffer = Vec::new();
+        entry.read_to_end(&mut buffer).unwrap();
+        assert_eq!(&buffer as &[u8], "BAR\n".as_bytes());
+    }
+
+    #[test]
+    fn list_sorted_symbols_in_gnu_archive() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       32        `\n\
+        \x00\x00\x00\x03\x00\x00\x00\x5c\x00\x00\x00\x5c\x00\x00\x00\x5c\
+        foobar\x00baz\x00quux\x00\
+        foo.o/          1487552916  501   20    100644  16        `\n\
+        foobar,baz,quux\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        assert_eq!(archive.sorted_symbols().unwrap().len(), 0);
+        assert_eq!(archive.symbols().unwrap().len(), 3);
+        assert_eq!(archive.variant(), Variant::GNU);
+    }
+
+    #[test]
+    fn list_sorted_symbols_with_invalid_member_number() {
+        let input = b"\
+        !<arch>\n\
+        /               0           0     0     0       28        `\n\
+        \x00\x00\x00\x03\x00\x00\x01\x12\x00\x00\x01\x52\x00\x00\x01\x52\
+        foo\x00bar\x00baz\x00\
+        /               0           0     0     0       34        `\n\
+        \x02\x00\x00\x00\x12\x01\x00\x00\x52\x01\x00\x00\
+        \x03\x00\x00\x00\x02\x00\x02\x00\x03\x00\
+        bar\x00baz\x00foo\x00\
+        //                                              23        `\n\
+        a_long_member_name.obj\x00\n\
+        foo.obj/        1487552916  0     0     100644  4         `\n\
+        FOO\n\
+        /0              1487552916  0     0     100644  4         `\n\
+        BAR\n";
+        let mut archive = Archive::new(Cursor::new(input as &[u8]));
+        let error = archive.sorted_symbols().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Symbol \"foo\" refers to member number 3, but there are only 2 \
+             members"
+        );
+        let expected = Error::InvalidSymbolMember {
+            symbol: b"foo".to_vec(),
+            member_number: 3,
+            num_members: 2,
+        };
+        assert_eq!(Error::from_io(&error), Some(&expected));
+    }
 }
 
 // ========================================================================= //
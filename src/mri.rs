@@ -0,0 +1,742 @@
+//! Running of MRI librarian scripts, as understood by `ar -M`.
+//!
+//! GNU `ar` can be driven by a script written in the command language of the
+//! MRI librarian, which builds one archive at a time.  This module parses
+//! such scripts and runs them, supporting these commands:
+//!
+//! * `CREATE archive` starts a new, empty archive, which will be written to
+//!   the given path when it is saved.
+//! * `OPEN archive` starts editing an existing archive.
+//! * `ADDLIB library` adds every member of another archive to the current
+//!   one, or with `ADDLIB library (module, ...)`, only the listed members.
+//! * `ADDMOD file ...` adds files as new members, named after their file
+//!   names.
+//! * `DELETE module ...` removes members.
+//! * `REPLACE file ...` replaces the members named after the given files
+//!   with those files (adding them as new members if there are no such
+//!   members).
+//! * `CLEAR` removes every member of the current archive.
+//! * `SAVE` writes the current archive and closes it.
+//! * `END` ends the script; an archive that hasn't been saved is discarded.
+//!
+//! Commands are case-insensitive, and their arguments are separated by spaces
+//! or commas.  A `*` or `;` starts a comment, which runs to the end of the
+//! line.  Errors (whether in parsing or running a script) give the line number
+//! of the command at fault.
+//!
+//! Members copied from other archives keep the symbols that those archives'
+//! symbol tables gave them, but no symbols are found for new members.
+//!
+//! # Example usage
+//!
+//! ```no_run
+//! use ar::mri::{Interpreter, Script};
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! let file = File::open("libfoo.mri").unwrap();
+//! let script = Script::parse(BufReader::new(file)).unwrap();
+//! Interpreter::new().run(&script).unwrap();
+//! ```
+
+use super::{Archive, Header, MetadataPolicy, StagedBuilder, StagedData,
+            Variant, bytes_to_path, header_for_path, write_atomically,
+            write_entry_data};
+use std::fs::File;
+use std::io::{BufRead, Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+// ========================================================================= //
+
+enum Command {
+    Create(PathBuf),
+    Open(PathBuf),
+    AddLib(PathBuf, Vec<Vec<u8>>),
+    AddMod(Vec<PathBuf>),
+    Delete(Vec<Vec<u8>>),
+    Replace(Vec<PathBuf>),
+    Clear,
+    Save,
+    End,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Token<'a> {
+    Word(&'a [u8]),
+    OpenParen,
+    CloseParen,
+}
+
+// ========================================================================= //
+
+/// A parsed MRI script.
+pub struct Script {
+    // Each command, paired with its line number.
+    commands: Vec<(usize, Command)>,
+}
+
+impl Script {
+    /// Parses an MRI script from the given reader.  Like GNU `ar`, this stops
+    /// reading at the first `END` command.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Script> {
+        let mut commands = Vec::new();
+        for (index, line) in reader.split(b'\n').enumerate() {
+            let line_number = index + 1;
+            let line = try!(line);
+            let command = try!(
+                parse_line(&line).map_err(|error| at_line(line_number, error))
+            );
+            match command {
+                Some(Command::End) => {
+                    commands.push((line_number, Command::End));
+                    break;
+                }
+                Some(command) => commands.push((line_number, command)),
+                None => {}
+            }
+        }
+        Ok(Script { commands: commands })
+    }
+}
+
+fn parse_line(line: &[u8]) -> Result<Option<Command>> {
+    let tokens = tokenize(line);
+    let (keyword, args) = match tokens.split_first() {
+        None => return Ok(None),
+        Some((&Token::Word(keyword), args)) => {
+            (keyword.to_ascii_uppercase(), args)
+        }
+        Some(_) => {
+            let msg = "Expected a command at the start of the line";
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+    };
+    let command = match keyword.as_slice() {
+        b"CREATE" => Command::Create(try!(parse_archive_arg("CREATE", args))),
+        b"OPEN" => Command::Open(try!(parse_archive_arg("OPEN", args))),
+        b"ADDLIB" => {
+            let (library, modules) = match args.split_first() {
+                Some((&Token::Word(library), modules)) => (library, modules),
+                _ => {
+                    let msg = "ADDLIB command expects a library name";
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+            };
+            let modules = match modules.split_first() {
+                None => Vec::new(),
+                Some((&Token::OpenParen, rest)) => {
+                    match rest.split_last() {
+                        Some((&Token::CloseParen, names)) => {
+                            try!(parse_name_args("ADDLIB", names))
+                        }
+                        _ => {
+                            let msg = "ADDLIB command's module list is \
+                                       missing its closing parenthesis";
+                            return Err(Error::new(ErrorKind::InvalidData,
+                                                  msg));
+                        }
+                    }
+                }
+                Some(_) => {
+                    let msg = "ADDLIB command's module list must be in \
+                               parentheses";
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+            };
+            Command::AddLib(try!(bytes_to_path(library)), modules)
+        }
+        b"ADDMOD" => Command::AddMod(try!(parse_path_args("ADDMOD", args))),
+        b"DELETE" => Command::Delete(try!(parse_name_args("DELETE", args))),
+        b"REPLACE" => {
+            Command::Replace(try!(parse_path_args("REPLACE", args)))
+        }
+        b"CLEAR" => {
+            try!(parse_no_args("CLEAR", args));
+            Command::Clear
+        }
+        b"SAVE" => {
+            try!(parse_no_args("SAVE", args));
+            Command::Save
+        }
+        b"END" => {
+            try!(parse_no_args("END", args));
+            Command::End
+        }
+        _ => {
+            let msg = format!(
+                "Unsupported MRI command {:?}",
+                String::from_utf8_lossy(&keyword)
+            );
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+    };
+    Ok(Some(command))
+}
+
+fn tokenize(line: &[u8]) -> Vec<Token<'_>> {
+    let end = line.iter()
+        .position(|&byte| byte == b'*' || byte == b';')
+        .unwrap_or(line.len());
+    let line = &line[..end];
+    let mut tokens = Vec::new();
+    let mut word_start = None;
+    for (index, &byte) in line.iter().enumerate() {
+        let token = match byte {
+            b'(' => Some(Token::OpenParen),
+            b')' => Some(Token::CloseParen),
+            b',' => None,
+            _ if byte.is_ascii_whitespace() => None,
+            _ => {
+                if word_start.is_none() {
+                    word_start = Some(index);
+                }
+                continue;
+            }
+        };
+        if let Some(start) = word_start.take() {
+            tokens.push(Token::Word(&line[start..index]));
+        }
+        tokens.extend(token);
+    }
+    if let Some(start) = word_start {
+        tokens.push(Token::Word(&line[start..]));
+    }
+    tokens
+}
+
+fn parse_name_args(command: &str, args: &[Token]) -> Result<Vec<Vec<u8>>> {
+    args.iter()
+        .map(|token| match *token {
+            Token::Word(name) => Ok(name.to_vec()),
+            _ => {
+                let msg = format!("Unexpected parenthesis in {} command",
+                                  command);
+                Err(Error::new(ErrorKind::InvalidData, msg))
+            }
+        })
+        .collect()
+}
+
+fn parse_path_args(command: &str, args: &[Token]) -> Result<Vec<PathBuf>> {
+    let names = try!(parse_name_args(command, args));
+    names.iter().map(|name| bytes_to_path(name)).collect()
+}
+
+fn parse_archive_arg(command: &str, args: &[Token]) -> Result<PathBuf> {
+    let mut paths = try!(parse_path_args(command, args));
+    if paths.len() != 1 {
+        let msg = format!("{} command expects one archive name", command);
+        return Err(Error::new(ErrorKind::InvalidData, msg));
+    }
+    Ok(paths.remove(0))
+}
+
+fn parse_no_args(command: &str, args: &[Token]) -> Result<()> {
+    if !args.is_empty() {
+        let msg = format!("{} command takes no arguments", command);
+        return Err(Error::new(ErrorKind::InvalidData, msg));
+    }
+    Ok(())
+}
+
+fn at_line(line_number: usize, error: Error) -> Error {
+    let msg = format!("MRI script line {}: {}", line_number, error);
+    Error::new(error.kind(), msg)
+}
+
+// ========================================================================= //
+
+/// Runs MRI scripts.
+pub struct Interpreter {
+    variant: Variant,
+    metadata: MetadataPolicy,
+}
+
+impl Interpreter {
+    /// Creates a new interpreter, which writes archives created by `CREATE`
+    /// in the GNU variant (like GNU `ar`).
+    pub fn new() -> Interpreter {
+        Interpreter {
+            variant: Variant::GNU,
+            metadata: MetadataPolicy::default(),
+        }
+    }
+
+    /// Sets the variant in which to write archives created by `CREATE`.
+    /// Archives opened with `OPEN` are written in their own variant (or as
+    /// the BSD variant, if they have no BSD or GNU extensions yet).  AIX big
+    /// archives and COFF archives can't be written.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    /// Sets how the timestamp, owner, group, and mode of each member are
+    /// rewritten as it is written (see `MetadataPolicy`).  By default, they
+    /// are written as given.
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata = policy;
+    }
+
+    /// Runs the given script.  The script stops at the first command that
+    /// fails, and any archive that it hasn't saved yet is discarded.
+    pub fn run(&self, script: &Script) -> Result<()> {
+        let mut run = Run {
+            interpreter: self,
+            libraries: Vec::new(),
+            library_entries: Vec::new(),
+            output: None,
+        };
+        for (line_number, command) in script.commands.iter() {
+            try!(
+                run.execute(command)
+                    .map_err(|error| at_line(*line_number, error))
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter { Interpreter::new() }
+}
+
+// ========================================================================= //
+
+/// The state of a script that is being run.
+struct Run<'i> {
+    interpreter: &'i Interpreter,
+    // Every archive that members have been copied from.
+    libraries: Vec<Archive<File>>,
+    // The library index and entry index of each member copied from a library.
+    // StagedData::Entry values are indices into this list.
+    library_entries: Vec<(usize, usize)>,
+    output: Option<Output>,
+}
+
+/// The archive that a script is currently building.
+struct Output {
+    path: PathBuf,
+    variant: Variant,
+    members: Vec<(Header, StagedData<'static>)>,
+    // Each symbol from a library's symbol table, paired with the index (into
+    // library_entries) of the member that defines it.
+    symbols: Vec<(Vec<u8>, usize)>,
+}
+
+impl<'i> Run<'i> {
+    fn execute(&mut self, command: &Command) -> Result<()> {
+        match *command {
+            Command::Create(ref path) => {
+                self.output = Some(Output {
+                    path: path.clone(),
+                    variant: self.interpreter.variant,
+                    members: Vec::new(),
+                    symbols: Vec::new(),
+                });
+            }
+            Command::Open(ref path) => {
+                let mut archive = try!(Archive::open(path));
+                try!(archive.count_entries());
+                if archive.is_thin() {
+                    let msg = "MRI scripts cannot edit thin archives";
+                    return Err(Error::new(ErrorKind::InvalidInput, msg));
+                }
+                let variant = match archive.variant() {
+                    Variant::AIX => {
+                        let msg = "MRI scripts cannot edit AIX big archives";
+                        return Err(Error::new(ErrorKind::InvalidInput, msg));
+                    }
+                    Variant::COFF => {
+                        let msg = "MRI scripts cannot edit COFF archives";
+                        return Err(Error::new(ErrorKind::InvalidInput, msg));
+                    }
+                    // An archive without any long names is read as the
+                    // common variant, but the script may add some.
+                    Variant::Common | Variant::BSD => Variant::BSD,
+                    Variant::GNU => Variant::GNU,
+                };
+                self.output = Some(Output {
+                    path: path.clone(),
+                    variant: variant,
+                    members: Vec::new(),
+                    symbols: Vec::new(),
+                });
+                try!(self.add_library(archive, &[]));
+            }
+            Command::AddLib(ref path, ref modules) => {
+                try!(self.output());
+                let archive = try!(Archive::open(path));
+                try!(self.add_library(archive, modules));
+            }
+            Command::AddMod(ref paths) => {
+                let output = try!(self.output());
+                for path in paths.iter() {
+                    let header = try!(header_for_path(path));
+                    let data = StagedData::Path(path.clone());
+                    output.members.push((header, data));
+                }
+            }
+            Command::Delete(ref modules) => {
+                let output = try!(self.output());
+                for module in modules.iter() {
+                    let index = try!(output.find(module));
+                    output.members.remove(index);
+                }
+            }
+            Command::Replace(ref paths) => {
+                let output = try!(self.output());
+                for path in paths.iter() {
+                    let header = try!(header_for_path(path));
+                    let existing = output.members.iter().position(|member| {
+                        member.0.identifier() == header.identifier()
+                    });
+                    let member = (header, StagedData::Path(path.clone()));
+                    match existing {
+                        Some(index) => output.members[index] = member,
+                        None => output.members.push(member),
+                    }
+                }
+            }
+            Command::Clear => {
+                if let Some(ref mut output) = self.output {
+                    output.members.clear();
+                    output.symbols.clear();
+                }
+            }
+            Command::Save => try!(self.save()),
+            Command::End => {}
+        }
+        Ok(())
+    }
+
+    fn output(&mut self) -> Result<&mut Output> {
+        self.output.as_mut().ok_or_else(no_open_archive)
+    }
+
+    /// Adds the members of the given archive with the given identifiers (or
+    /// all of its members, if none are given) to the output archive, along
+    /// with their symbols.
+    fn add_library(&mut self, mut archive: Archive<File>,
+                   modules: &[Vec<u8>])
+                   -> Result<()> {
+        let count = try!(archive.count_entries());
+        for module in modules.iter() {
+            let found = archive.entry_headers
+                .iter()
+                .any(|entry| entry.header.identifier() == module.as_slice());
+            if !found {
+                let msg = format!(
+                    "No member named {:?} in the library",
+                    String::from_utf8_lossy(module)
+                );
+                return Err(Error::new(ErrorKind::NotFound, msg));
+            }
+        }
+        let library_symbols: Vec<(Vec<u8>, usize)> =
+            try!(archive.symbol_entries())
+                .map(|(symbol, index)| (symbol.to_vec(), index))
+                .collect();
+        let library = self.libraries.len();
+        let output = match self.output {
+            Some(ref mut output) => output,
+            None => unreachable!(),
+        };
+        // The library_entries index of each entry that is added.
+        let mut sources = vec![None; count];
+        for (index, source) in sources.iter_mut().enumerate() {
+            let header = &archive.entry_headers[index].header;
+            if !modules.is_empty() &&
+                !modules.iter().any(|module| {
+                    module.as_slice() == header.identifier()
+                })
+            {
+                continue;
+            }
+            *source = Some(self.library_entries.len());
+            self.library_entries.push((library, index));
+            output.members.push((
+                header.clone(),
+                StagedData::Entry(self.library_entries.len() - 1),
+            ));
+        }
+        for (symbol, index) in library_symbols {
+            if let Some(source) = sources[index] {
+                output.symbols.push((symbol, source));
+            }
+        }
+        self.libraries.push(archive);
+        Ok(())
+    }
+
+    fn save(&mut self) -> Result<()> {
+        let output = try!(self.output.take().ok_or_else(no_open_archive));
+        let mut builder = StagedBuilder::new(output.variant);
+        builder.metadata = self.interpreter.metadata;
+        // Common-variant archives can't have a symbol table, so the symbols
+        // are dropped rather than making the archive impossible to write.
+        builder.entries = output.members;
+        if output.variant != Variant::Common {
+            builder.add_entry_symbols(output.symbols);
+        }
+        let libraries = &mut self.libraries;
+        let library_entries = &self.library_entries;
+        write_atomically(&output.path, |writer| {
+            builder.write_copying_entries(writer, |writer, header, source| {
+                let (library, index) = library_entries[source];
+                let entry = try!(libraries[library].jump_to_entry(index));
+                write_entry_data(writer, header, entry)
+            })
+        })
+    }
+}
+
+fn no_open_archive() -> Error {
+    let msg = "No archive is open (use CREATE or OPEN first)";
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+impl Output {
+    fn find(&self, identifier: &[u8]) -> Result<usize> {
+        let index = self.members
+            .iter()
+            .position(|member| member.0.identifier() == identifier);
+        index.ok_or_else(|| {
+            let msg = format!(
+                "No member named {:?} in the archive",
+                String::from_utf8_lossy(identifier)
+            );
+            Error::new(ErrorKind::NotFound, msg)
+        })
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Interpreter, Script, Token, tokenize};
+    use {Archive, Builder, Header, StagedBuilder, Variant};
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+    use test_common::TestDir;
+
+    fn read_entries(path: &Path) -> Vec<(String, String)> {
+        let mut archive = Archive::open(path).unwrap();
+        let mut entries = Vec::new();
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry.unwrap();
+            let identifier =
+                String::from_utf8(entry.header().identifier().to_vec())
+                    .unwrap();
+            let mut data = String::new();
+            entry.read_to_string(&mut data).unwrap();
+            entries.push((identifier, data));
+        }
+        entries
+    }
+
+    fn run_script(script: &str) -> Result<(), String> {
+        let script = try!(
+            Script::parse(script.as_bytes()).map_err(|e| e.to_string())
+        );
+        Interpreter::new().run(&script).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn tokenize_line() {
+        let tokens = tokenize(b" addlib libfoo.a(foo.o,  bar.o) ; comment\r");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word(b"addlib"),
+                Token::Word(b"libfoo.a"),
+                Token::OpenParen,
+                Token::Word(b"foo.o"),
+                Token::Word(b"bar.o"),
+                Token::CloseParen,
+            ]
+        );
+        assert_eq!(tokenize(b"* just a comment"), vec![]);
+        assert_eq!(tokenize(b"save*comment"), vec![Token::Word(b"save")]);
+    }
+
+    #[test]
+    fn parse_script() {
+        let input = "\
+        * Build libfoo.a\n\
+        \n\
+        create libfoo.a\n\
+        AddLib libbar.a (bar.o, baz.o)\n\
+        ADDMOD foo.o,qux.o\n\
+        SAVE\n\
+        END\n\
+        this line is never read\n";
+        let script = Script::parse(input.as_bytes()).unwrap();
+        let lines: Vec<usize> =
+            script.commands.iter().map(|command| command.0).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6, 7]);
+        match script.commands[0].1 {
+            Command::Create(ref path) => {
+                assert_eq!(path, Path::new("libfoo.a"))
+            }
+            _ => panic!("expected CREATE"),
+        }
+        match script.commands[1].1 {
+            Command::AddLib(ref path, ref modules) => {
+                assert_eq!(path, Path::new("libbar.a"));
+                let expected = vec![b"bar.o".to_vec(), b"baz.o".to_vec()];
+                assert_eq!(modules, &expected);
+            }
+            _ => panic!("expected ADDLIB"),
+        }
+        match script.commands[2].1 {
+            Command::AddMod(ref paths) => {
+                assert_eq!(
+                    paths,
+                    &vec![PathBuf::from("foo.o"), PathBuf::from("qux.o")]
+                );
+            }
+            _ => panic!("expected ADDMOD"),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_scripts() {
+        let cases = [
+            ("CREATE foo.a\nFROB bar\n", "line 2: Unsupported MRI command"),
+            ("CREATE\n", "line 1: CREATE command expects one archive name"),
+            ("CREATE a b\n", "line 1: CREATE command expects one archive"),
+            ("\nSAVE foo.a\n", "line 2: SAVE command takes no arguments"),
+            ("ADDLIB\n", "line 1: ADDLIB command expects a library name"),
+            ("ADDLIB foo.a (bar.o\n", "missing its closing parenthesis"),
+            ("ADDLIB foo.a bar.o\n", "module list must be in parentheses"),
+            ("DELETE (foo.o)\n", "Unexpected parenthesis in DELETE"),
+            ("(foo)\n", "line 1: Expected a command at the start"),
+        ];
+        for &(input, expected) in cases.iter() {
+            let error = Script::parse(input.as_bytes()).err().unwrap();
+            let message = error.to_string();
+            assert!(message.starts_with("MRI script line "), "{}", message);
+            assert!(message.contains(expected), "{}", message);
+        }
+    }
+
+    #[test]
+    fn run_script_merging_libraries() {
+        let dir = TestDir::new("mri-merge");
+        let mut builder = StagedBuilder::new(Variant::GNU);
+        let header = Header::new(b"this_is_a_long_name.o".to_vec(), 0);
+        builder.append_bytes(header, b"foo".to_vec());
+        let header = Header::new(b"foo2.o".to_vec(), 0);
+        builder.append_bytes(header, b"2".to_vec());
+        builder.add_symbol(b"foo".to_vec(), b"this_is_a_long_name.o".to_vec());
+        builder.add_symbol(b"foo2".to_vec(), b"foo2.o".to_vec());
+        builder.write(File::create(dir.join("libfoo.a")).unwrap()).unwrap();
+        let mut builder = Builder::new(File::create(dir.join("libbar.a"))
+                                           .unwrap());
+        builder.append(&Header::new(b"bar1.o".to_vec(), 4), &b"bar1"[..])
+            .unwrap();
+        builder.append(&Header::new(b"bar2.o".to_vec(), 4), &b"bar2"[..])
+            .unwrap();
+        builder.into_inner().unwrap();
+        fs::write(dir.join("baz.o"), b"baz").unwrap();
+
+        let script = format!(
+            "CREATE {dir}/out.a\n\
+             ADDLIB {dir}/libfoo.a\n\
+             addlib {dir}/libbar.a (bar2.o)\n\
+             ADDMOD {dir}/baz.o\n\
+             DELETE foo2.o\n\
+             SAVE\n\
+             END\n",
+            dir = dir.path().display()
+        );
+        run_script(&script).unwrap();
+
+        let path = dir.join("out.a");
+        let expected = vec![
+            ("this_is_a_long_name.o".to_string(), "foo".to_string()),
+            ("bar2.o".to_string(), "bar2".to_string()),
+            ("baz.o".to_string(), "baz".to_string()),
+        ];
+        assert_eq!(read_entries(&path), expected);
+        let mut archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.find_symbol(b"foo").unwrap(), Some(0));
+        assert_eq!(archive.find_symbol(b"foo2").unwrap(), None);
+        assert_eq!(archive.variant(), Variant::GNU);
+    }
+
+    #[test]
+    fn run_script_editing_archive() {
+        let dir = TestDir::new("mri-edit");
+        let path = dir.join("libfoo.a");
+        let mut builder = Builder::new(File::create(&path).unwrap());
+        builder.append(&Header::new(b"foo.o".to_vec(), 3), &b"foo"[..])
+            .unwrap();
+        builder.append(&Header::new(b"bar.o".to_vec(), 3), &b"bar"[..])
+            .unwrap();
+        builder.into_inner().unwrap();
+        fs::write(dir.join("foo.o"), b"new foo").unwrap();
+        fs::write(dir.join("a_rather_long_name.o"), b"long").unwrap();
+
+        let script = format!(
+            "OPEN {dir}/libfoo.a\n\
+             REPLACE {dir}/foo.o {dir}/a_rather_long_name.o\n\
+             SAVE\n\
+             CREATE {dir}/unsaved.a\n\
+             ADDMOD {dir}/foo.o\n\
+             END\n",
+            dir = dir.path().display()
+        );
+        run_script(&script).unwrap();
+
+        let expected = vec![
+            ("foo.o".to_string(), "new foo".to_string()),
+            ("bar.o".to_string(), "bar".to_string()),
+            ("a_rather_long_name.o".to_string(), "long".to_string()),
+        ];
+        assert_eq!(read_entries(&path), expected);
+        let mut archive = Archive::open(&path).unwrap();
+        archive.count_entries().unwrap();
+        assert_eq!(archive.variant(), Variant::BSD);
+        assert!(!dir.join("unsaved.a").exists());
+
+        let script = format!(
+            "OPEN {dir}/libfoo.a\n\
+             CLEAR\n\
+             SAVE\n",
+            dir = dir.path().display()
+        );
+        run_script(&script).unwrap();
+        assert_eq!(read_entries(&path), vec![]);
+    }
+
+    #[test]
+    fn run_invalid_scripts() {
+        let dir = TestDir::new("mri-invalid");
+        fs::write(dir.join("foo.o"), b"foo").unwrap();
+        let cases = [
+            ("ADDMOD {dir}/foo.o\n", "line 1: No archive is open"),
+            ("\nSAVE\n", "line 2: No archive is open"),
+            (
+                "CREATE {dir}/out.a\nDELETE foo.o\n",
+                "line 2: No member named \"foo.o\" in the archive",
+            ),
+            (
+                "CREATE {dir}/out.a\nADDMOD {dir}/foo.o\n\
+                 ADDLIB {dir}/out.a (foo.o)\n",
+                "line 3: ",
+            ),
+        ];
+        let dir_name = dir.path().display().to_string();
+        for &(script, expected) in cases.iter() {
+            let script = script.replace("{dir}", &dir_name);
+            let message = run_script(&script).err().unwrap();
+            assert!(message.starts_with("MRI script "), "{}", message);
+            assert!(message.contains(expected), "{}", message);
+        }
+        assert!(!dir.join("out.a").exists());
+    }
+}
+
+// ========================================================================= //
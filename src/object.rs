@@ -0,0 +1,1244 @@
+//! Finding the symbols defined by the object files stored in an archive.
+//!
+//! An archive's symbol table lists the global symbols defined by each of its
+//! entries, so that a linker can tell which entries it needs without reading
+//! them all.  The `defined_symbols` function finds those symbols in an object
+//! file, which can be in any of these formats:
+//!
+//! * ELF (32- or 64-bit, either byte order), as used on Linux and most other
+//!   Unix systems.
+//! * Mach-O (32- or 64-bit, either byte order), as used on Mac OS X.
+//! * COFF (including the "big object" variant), as used on Windows, as well
+//!   as the short import objects found in Windows import libraries.
+//! * LLVM bitcode (optionally with the wrapper header used on Darwin), as
+//!   produced when compiling with link-time optimization.  The symbols are
+//!   read from the symbol table that LLVM stores alongside the module (as
+//!   LLVM's own tools do), so bitcode written by LLVM releases older than 5.0,
+//!   which lacks one, defines no symbols.
+//!
+//! Following the conventions of the `ar` and `ranlib` tools on each platform,
+//! weak symbols count as defined for ELF but not for COFF, and common symbols
+//! count as defined for ELF and COFF but not for Mach-O.
+//!
+//! The `ObjectSymbolExtractor` type plugs `defined_symbols` into the
+//! builders' `SymbolExtractor` hook, so that they can build a symbol table
+//! automatically.  This module is only available when the `object` feature is
+//! enabled (which it is by default).
+//!
+//! # Example usage
+//!
+//! Listing the symbols defined by each entry in an archive:
+//!
+//! ```no_run
+//! use ar::Archive;
+//! use std::io::Read;
+//! use std::str;
+//! let mut archive = Archive::new(std::fs::File::open("foo.a").unwrap());
+//! while let Some(entry_result) = archive.next_entry() {
+//!     let mut entry = entry_result.unwrap();
+//!     let mut data = Vec::new();
+//!     entry.read_to_end(&mut data).unwrap();
+//!     for symbol in ar::object::defined_symbols(&data).unwrap() {
+//!         println!("{}", String::from_utf8_lossy(&symbol));
+//!     }
+//! }
+//! ```
+
+use super::{Header, SymbolExtractor};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::{Error, ErrorKind, Result};
+
+// ========================================================================= //
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const SHT_SYMTAB: u32 = 2;
+const SHN_UNDEF: u16 = 0;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STB_GNU_UNIQUE: u8 = 10;
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const MH_CIGAM: u32 = 0xcefaedfe;
+const MH_CIGAM_64: u32 = 0xcffaedfe;
+const LC_SYMTAB: u32 = 2;
+const N_STAB: u8 = 0xe0;
+const N_TYPE: u8 = 0x0e;
+const N_EXT: u8 = 0x01;
+const N_ABS: u8 = 0x02;
+const N_INDR: u8 = 0x0a;
+const N_SECT: u8 = 0x0e;
+
+const COFF_MACHINES: &[u16] = &[
+    0x014c, // IMAGE_FILE_MACHINE_I386
+    0x0166, // IMAGE_FILE_MACHINE_R4000
+    0x01c0, // IMAGE_FILE_MACHINE_ARM
+    0x01c2, // IMAGE_FILE_MACHINE_THUMB
+    0x01c4, // IMAGE_FILE_MACHINE_ARMNT
+    0x0200, // IMAGE_FILE_MACHINE_IA64
+    0x5032, // IMAGE_FILE_MACHINE_RISCV32
+    0x5064, // IMAGE_FILE_MACHINE_RISCV64
+    0x8664, // IMAGE_FILE_MACHINE_AMD64
+    0xa641, // IMAGE_FILE_MACHINE_ARM64EC
+    0xa64e, // IMAGE_FILE_MACHINE_ARM64X
+    0xaa64, // IMAGE_FILE_MACHINE_ARM64
+];
+const COFF_HEADER_LEN: u64 = 20;
+const COFF_SECTION_HEADER_LEN: u64 = 40;
+const COFF_SYMBOL_LEN: u64 = 18;
+const COFF_BIGOBJ_HEADER_LEN: u64 = 56;
+const COFF_BIGOBJ_SYMBOL_LEN: u64 = 20;
+const COFF_BIGOBJ_CLASS_ID: &[u8] = b"\xc7\xa1\xba\xd1\xee\xba\xa9\x4b\
+                                      \xaf\x20\xfa\xf6\x6a\xa4\xdc\xb8";
+const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+const IMAGE_SYM_UNDEFINED: i32 = 0;
+const IMAGE_SYM_ABSOLUTE: i32 = -1;
+const IMPORT_OBJECT_HEADER_LEN: u64 = 20;
+const IMPORT_OBJECT_DATA: u16 = 1;
+const IMPORT_SYMBOL_PREFIX: &[u8] = b"__imp_";
+
+const BITCODE_MAGIC: &[u8] = b"BC\xc0\xde";
+const BITCODE_WRAPPER_MAGIC: u32 = 0x0b17c0de;
+const BITCODE_TOP_LEVEL_ABBREV_WIDTH: u32 = 2;
+const BITCODE_END_BLOCK: u64 = 0;
+const BITCODE_ENTER_SUBBLOCK: u64 = 1;
+const BITCODE_DEFINE_ABBREV: u64 = 2;
+const BITCODE_UNABBREV_RECORD: u64 = 3;
+const BITCODE_STRTAB_BLOCK_ID: u64 = 23;
+const BITCODE_SYMTAB_BLOCK_ID: u64 = 25;
+// The record code of both STRTAB_BLOB and SYMTAB_BLOB.
+const BITCODE_BLOB_RECORD: u64 = 1;
+const IRSYMTAB_SYMBOL_LEN: u64 = 24;
+const IRSYMTAB_FB_UNDEFINED: u32 = 1 << 3;
+const IRSYMTAB_FB_GLOBAL: u32 = 1 << 10;
+const IRSYMTAB_FB_FORMAT_SPECIFIC: u32 = 1 << 11;
+
+// ========================================================================= //
+
+/// Returns the names of the global symbols defined by the given object file,
+/// in the order they appear in its symbol table.  If the data isn't an
+/// object file in one of the supported formats (see the module
+/// documentation), returns an empty list; if it is, but is malformed,
+/// returns an error.
+pub fn defined_symbols(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if data.starts_with(ELF_MAGIC) && data.len() > 5 {
+        return match (data[4], data[5]) {
+            (ELFCLASS32, ELFDATA2LSB) => {
+                elf_symbols::<LittleEndian>(data, false)
+            }
+            (ELFCLASS32, ELFDATA2MSB) => elf_symbols::<BigEndian>(data, false),
+            (ELFCLASS64, ELFDATA2LSB) => {
+                elf_symbols::<LittleEndian>(data, true)
+            }
+            (ELFCLASS64, ELFDATA2MSB) => elf_symbols::<BigEndian>(data, true),
+            _ => Err(malformed("ELF", "invalid class or data encoding")),
+        };
+    }
+    if data.len() >= 4 {
+        match LittleEndian::read_u32(data) {
+            MH_MAGIC => return macho_symbols::<LittleEndian>(data, false),
+            MH_MAGIC_64 => return macho_symbols::<LittleEndian>(data, true),
+            MH_CIGAM => return macho_symbols::<BigEndian>(data, false),
+            MH_CIGAM_64 => return macho_symbols::<BigEndian>(data, true),
+            _ => {}
+        }
+        let sig1 = LittleEndian::read_u16(data);
+        let sig2 = LittleEndian::read_u16(&data[2..]);
+        if sig1 == 0 && sig2 == 0xffff {
+            let version = try!(read_u16::<LittleEndian>(data, 4, "COFF"));
+            if version == 0 {
+                return import_object_symbols(data);
+            } else if version >= 2 &&
+                       data.get(12..28) == Some(COFF_BIGOBJ_CLASS_ID)
+            {
+                return coff_symbols(data, true);
+            }
+        } else if COFF_MACHINES.contains(&sig1) && is_coff_object(data) {
+            return coff_symbols(data, false);
+        }
+        if data.starts_with(BITCODE_MAGIC) ||
+            LittleEndian::read_u32(data) == BITCODE_WRAPPER_MAGIC
+        {
+            return bitcode_symbols(data);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// A `SymbolExtractor` that finds the symbols defined by each entry with
+/// `defined_symbols`.
+///
+/// # Example usage
+///
+/// ```no_run
+/// use ar::Builder;
+/// use ar::object::ObjectSymbolExtractor;
+/// use std::fs::OpenOptions;
+/// // The file must be opened for reading too, so that the symbol table can
+/// // be inserted before the entries:
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .truncate(true)
+///     .open("libfoo.a")
+///     .unwrap();
+/// let mut builder = Builder::new(file);
+/// builder.set_symbol_extractor(ObjectSymbolExtractor).unwrap();
+/// builder.append_path("foo.o").unwrap();
+/// builder.append_path("bar.o").unwrap();
+/// // The symbol table is inserted at the start of the archive:
+/// builder.into_inner().unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ObjectSymbolExtractor;
+
+impl SymbolExtractor for ObjectSymbolExtractor {
+    fn extract_symbols(&mut self, _header: &Header, data: &[u8])
+                       -> Result<Vec<Vec<u8>>> {
+        defined_symbols(data)
+    }
+}
+
+fn malformed(format: &str, problem: &str) -> Error {
+    let msg = format!("Malformed {} object file ({})", format, problem);
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+// ========================================================================= //
+
+fn slice<'a>(data: &'a [u8], offset: u64, len: u64, format: &str)
+             -> Result<&'a [u8]> {
+    let end = offset.checked_add(len);
+    match end {
+        Some(end) if end <= data.len() as u64 => {
+            Ok(&data[offset as usize..end as usize])
+        }
+        _ => Err(malformed(format, "unexpected end of data")),
+    }
+}
+
+fn read_u16<E: ByteOrder>(data: &[u8], offset: u64, format: &str)
+                          -> Result<u16> {
+    slice(data, offset, 2, format).map(E::read_u16)
+}
+
+fn read_u32<E: ByteOrder>(data: &[u8], offset: u64, format: &str)
+                          -> Result<u32> {
+    slice(data, offset, 4, format).map(E::read_u32)
+}
+
+fn read_u64<E: ByteOrder>(data: &[u8], offset: u64, format: &str)
+                          -> Result<u64> {
+    slice(data, offset, 8, format).map(E::read_u64)
+}
+
+/// Reads a null-terminated string starting at the given offset within a
+/// string table.
+fn read_string(table: &[u8], offset: u64, format: &str) -> Result<Vec<u8>> {
+    if offset >= table.len() as u64 {
+        return Err(malformed(format, "string table index out of bounds"));
+    }
+    let string = &table[offset as usize..];
+    let len = string.iter().position(|&byte| byte == 0);
+    Ok(string[..len.unwrap_or(string.len())].to_vec())
+}
+
+// ========================================================================= //
+
+struct ElfSection {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+fn elf_symbols<E: ByteOrder>(data: &[u8], is_64_bit: bool)
+                             -> Result<Vec<Vec<u8>>> {
+    let (section_table, entry_len, mut num_sections) = if is_64_bit {
+        (try!(read_u64::<E>(data, 0x28, "ELF")),
+         try!(read_u16::<E>(data, 0x3a, "ELF")) as u64,
+         try!(read_u16::<E>(data, 0x3c, "ELF")) as u64)
+    } else {
+        (try!(read_u32::<E>(data, 0x20, "ELF")) as u64,
+         try!(read_u16::<E>(data, 0x2e, "ELF")) as u64,
+         try!(read_u16::<E>(data, 0x30, "ELF")) as u64)
+    };
+    if section_table == 0 {
+        return Ok(Vec::new());
+    }
+    let header_len = if is_64_bit { 0x40 } else { 0x28 };
+    if entry_len < header_len {
+        return Err(malformed("ELF", "invalid section header size"));
+    }
+    let section = |index: u64| -> Result<ElfSection> {
+        let start = index.checked_mul(entry_len)
+            .and_then(|offset| offset.checked_add(section_table))
+            .unwrap_or(u64::MAX);
+        let header = try!(slice(data, start, header_len, "ELF"));
+        if is_64_bit {
+            Ok(ElfSection {
+                kind: E::read_u32(&header[0x04..]),
+                offset: E::read_u64(&header[0x18..]),
+                size: E::read_u64(&header[0x20..]),
+                link: E::read_u32(&header[0x28..]),
+            })
+        } else {
+            Ok(ElfSection {
+                kind: E::read_u32(&header[0x04..]),
+                offset: E::read_u32(&header[0x10..]) as u64,
+                size: E::read_u32(&header[0x14..]) as u64,
+                link: E::read_u32(&header[0x18..]),
+            })
+        }
+    };
+    // If there are too many sections to count in the ELF header, the count
+    // is stored in the size field of the first section header instead.
+    if num_sections == 0 {
+        num_sections = try!(section(0)).size;
+    }
+    let symbol_len = if is_64_bit { 24 } else { 16 };
+    let mut symbols = Vec::new();
+    for index in 0..num_sections {
+        let symtab = try!(section(index));
+        if symtab.kind != SHT_SYMTAB {
+            continue;
+        }
+        let strtab = try!(section(symtab.link as u64));
+        let strings = try!(slice(data, strtab.offset, strtab.size, "ELF"));
+        let table = try!(slice(data, symtab.offset, symtab.size, "ELF"));
+        for symbol in table.chunks(symbol_len) {
+            if symbol.len() < symbol_len {
+                return Err(malformed("ELF", "truncated symbol table"));
+            }
+            let (info, section_index) = if is_64_bit {
+                (symbol[4], E::read_u16(&symbol[6..]))
+            } else {
+                (symbol[12], E::read_u16(&symbol[14..]))
+            };
+            let binding = info >> 4;
+            if (binding == STB_GLOBAL || binding == STB_WEAK ||
+                    binding == STB_GNU_UNIQUE) &&
+                section_index != SHN_UNDEF
+            {
+                let name_offset = E::read_u32(symbol) as u64;
+                let name = try!(read_string(strings, name_offset, "ELF"));
+                if !name.is_empty() {
+                    symbols.push(name);
+                }
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+// ========================================================================= //
+
+fn macho_symbols<E: ByteOrder>(data: &[u8], is_64_bit: bool)
+                               -> Result<Vec<Vec<u8>>> {
+    let num_commands = try!(read_u32::<E>(data, 16, "Mach-O"));
+    let mut offset: u64 = if is_64_bit { 32 } else { 28 };
+    let mut symbols = Vec::new();
+    for _ in 0..num_commands {
+        let command = try!(read_u32::<E>(data, offset, "Mach-O"));
+        let command_len = try!(read_u32::<E>(data, offset + 4, "Mach-O"));
+        if command_len < 8 {
+            return Err(malformed("Mach-O", "invalid load command size"));
+        }
+        if command == LC_SYMTAB {
+            let symoff = try!(read_u32::<E>(data, offset + 8, "Mach-O"));
+            let nsyms = try!(read_u32::<E>(data, offset + 12, "Mach-O"));
+            let stroff = try!(read_u32::<E>(data, offset + 16, "Mach-O"));
+            let strsize = try!(read_u32::<E>(data, offset + 20, "Mach-O"));
+            let strings =
+                try!(slice(data, stroff as u64, strsize as u64, "Mach-O"));
+            let nlist_len: u64 = if is_64_bit { 16 } else { 12 };
+            let table = try!(slice(data,
+                                   symoff as u64,
+                                   nsyms as u64 * nlist_len,
+                                   "Mach-O"));
+            for nlist in table.chunks(nlist_len as usize) {
+                let kind = nlist[4];
+                if kind & N_STAB == 0 && kind & N_EXT != 0 &&
+                    (kind & N_TYPE == N_ABS || kind & N_TYPE == N_SECT ||
+                         kind & N_TYPE == N_INDR)
+                {
+                    let name_offset = E::read_u32(nlist) as u64;
+                    let name =
+                        try!(read_string(strings, name_offset, "Mach-O"));
+                    if !name.is_empty() {
+                        symbols.push(name);
+                    }
+                }
+            }
+        }
+        offset += command_len as u64;
+    }
+    Ok(symbols)
+}
+
+// ========================================================================= //
+
+/// Returns true if the data, which starts with a COFF machine type, has a
+/// plausible COFF object header: no optional header (which only images
+/// have), and section and symbol tables that lie within the data.  The
+/// machine type alone is only two bytes, so this keeps other data that
+/// happens to start with one from being reported as a malformed object.
+fn is_coff_object(data: &[u8]) -> bool {
+    if (data.len() as u64) < COFF_HEADER_LEN {
+        return false;
+    }
+    let num_sections = LittleEndian::read_u16(&data[2..]) as u64;
+    let symbol_table = LittleEndian::read_u32(&data[8..]) as u64;
+    let num_symbols = LittleEndian::read_u32(&data[12..]) as u64;
+    let optional_header_len = LittleEndian::read_u16(&data[16..]);
+    let sections_end =
+        COFF_HEADER_LEN + num_sections * COFF_SECTION_HEADER_LEN;
+    let symbols_end = symbol_table + num_symbols * COFF_SYMBOL_LEN;
+    optional_header_len == 0 && sections_end <= data.len() as u64 &&
+        (symbol_table == 0 ||
+             (symbol_table >= sections_end &&
+                  symbols_end <= data.len() as u64))
+}
+
+fn coff_symbols(data: &[u8], is_bigobj: bool) -> Result<Vec<Vec<u8>>> {
+    let (header_len, symbol_len, symbol_table, num_symbols) = if is_bigobj {
+        (COFF_BIGOBJ_HEADER_LEN,
+         COFF_BIGOBJ_SYMBOL_LEN,
+         try!(read_u32::<LittleEndian>(data, 48, "COFF")) as u64,
+         try!(read_u32::<LittleEndian>(data, 52, "COFF")) as u64)
+    } else {
+        (COFF_HEADER_LEN,
+         COFF_SYMBOL_LEN,
+         try!(read_u32::<LittleEndian>(data, 8, "COFF")) as u64,
+         try!(read_u32::<LittleEndian>(data, 12, "COFF")) as u64)
+    };
+    try!(slice(data, 0, header_len, "COFF"));
+    if symbol_table == 0 {
+        return Ok(Vec::new());
+    }
+    let table =
+        try!(slice(data, symbol_table, num_symbols * symbol_len, "COFF"));
+    // The string table follows the symbol table, and starts with its own
+    // length (which includes the length field itself).
+    let strings_start = symbol_table + num_symbols * symbol_len;
+    let strings_len =
+        match read_u32::<LittleEndian>(data, strings_start, "COFF") {
+            Ok(len) => len as u64,
+            Err(_) => 0,
+        };
+    let strings = try!(slice(data, strings_start, strings_len, "COFF"));
+    let mut symbols = Vec::new();
+    let mut index = 0;
+    while index < num_symbols {
+        let start = (index * symbol_len) as usize;
+        let symbol = &table[start..start + symbol_len as usize];
+        let value = LittleEndian::read_u32(&symbol[8..]);
+        let (section, class, num_aux) = if is_bigobj {
+            (LittleEndian::read_i32(&symbol[12..]), symbol[18], symbol[19])
+        } else {
+            (LittleEndian::read_i16(&symbol[12..]) as i32,
+             symbol[16],
+             symbol[17])
+        };
+        // An external symbol with no section is either undefined or, if it
+        // has a nonzero value, a common symbol of that size.
+        let is_defined = section > 0 || section == IMAGE_SYM_ABSOLUTE ||
+            (section == IMAGE_SYM_UNDEFINED && value != 0);
+        if class == IMAGE_SYM_CLASS_EXTERNAL && is_defined {
+            let name = if symbol[..4] == [0, 0, 0, 0] {
+                let name_offset = LittleEndian::read_u32(&symbol[4..]);
+                try!(read_string(strings, name_offset as u64, "COFF"))
+            } else {
+                let len = symbol[..8].iter().position(|&byte| byte == 0);
+                symbol[..len.unwrap_or(8)].to_vec()
+            };
+            if !name.is_empty() {
+                symbols.push(name);
+            }
+        }
+        index += 1 + num_aux as u64;
+    }
+    Ok(symbols)
+}
+
+/// Returns the symbols defined by a short import object: the import address
+/// table entry (the symbol name with an `__imp_` prefix), and, unless the
+/// import is of data, the symbol itself (which names a thunk).
+fn import_object_symbols(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let size_of_data = try!(read_u32::<LittleEndian>(data, 12, "COFF"));
+    let type_info = try!(read_u16::<LittleEndian>(data, 18, "COFF"));
+    let strings = try!(slice(data,
+                             IMPORT_OBJECT_HEADER_LEN,
+                             size_of_data as u64,
+                             "COFF"));
+    let name = try!(read_string(strings, 0, "COFF"));
+    let mut symbols = vec![[IMPORT_SYMBOL_PREFIX, &name].concat()];
+    if type_info & 0x3 != IMPORT_OBJECT_DATA {
+        symbols.push(name);
+    }
+    Ok(symbols)
+}
+
+// ========================================================================= //
+
+/// Returns the symbols defined by an LLVM bitcode file, as listed in the
+/// symbol table (`SYMTAB_BLOCK`) that LLVM writes alongside the module, or
+/// none if the file doesn't have one.
+fn bitcode_symbols(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    // Bitcode may be wrapped in a header that gives the offset and size of
+    // the bitcode proper.
+    let data = if LittleEndian::read_u32(data) == BITCODE_WRAPPER_MAGIC {
+        let offset = try!(read_u32::<LittleEndian>(data, 8, "LLVM bitcode"));
+        let size = try!(read_u32::<LittleEndian>(data, 12, "LLVM bitcode"));
+        try!(slice(data, offset as u64, size as u64, "LLVM bitcode"))
+    } else {
+        data
+    };
+    if !data.starts_with(BITCODE_MAGIC) {
+        return Err(malformed("LLVM bitcode", "invalid magic number"));
+    }
+    let mut reader = BitReader {
+        data: data,
+        position: 8 * BITCODE_MAGIC.len() as u64,
+    };
+    let mut symtab = None;
+    let mut strtab = None;
+    while !reader.is_at_end() {
+        // Anything other than a block at the top level (such as the padding
+        // at the end of a wrapped file) ends the stream.
+        let abbrev_id = try!(reader.read(BITCODE_TOP_LEVEL_ABBREV_WIDTH));
+        if abbrev_id != BITCODE_ENTER_SUBBLOCK {
+            break;
+        }
+        let (block_id, abbrev_width, block_end) =
+            try!(reader.enter_block());
+        if block_id == BITCODE_SYMTAB_BLOCK_ID {
+            symtab = try!(reader.find_blob(abbrev_width)).or(symtab);
+        } else if block_id == BITCODE_STRTAB_BLOCK_ID {
+            strtab = try!(reader.find_blob(abbrev_width)).or(strtab);
+        }
+        reader.position = block_end;
+    }
+    match (symtab, strtab) {
+        (Some(symtab), Some(strtab)) => irsymtab_symbols(symtab, strtab),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Returns the symbols defined by an `irsymtab::storage::Header` (and the
+/// tables that follow it), whose names are stored in the given string table.
+fn irsymtab_symbols(symtab: &[u8], strtab: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let offset = try!(read_u32::<LittleEndian>(symtab, 28, "LLVM bitcode"));
+    let count = try!(read_u32::<LittleEndian>(symtab, 32, "LLVM bitcode"));
+    let table = try!(slice(symtab,
+                           offset as u64,
+                           count as u64 * IRSYMTAB_SYMBOL_LEN,
+                           "LLVM bitcode"));
+    let mut symbols = Vec::new();
+    for symbol in table.chunks(IRSYMTAB_SYMBOL_LEN as usize) {
+        let flags = LittleEndian::read_u32(&symbol[20..]);
+        let is_defined = flags & IRSYMTAB_FB_GLOBAL != 0 &&
+            flags & (IRSYMTAB_FB_UNDEFINED | IRSYMTAB_FB_FORMAT_SPECIFIC) == 0;
+        if is_defined {
+            let name_offset = LittleEndian::read_u32(symbol) as u64;
+            let name_len = LittleEndian::read_u32(&symbol[4..]) as u64;
+            let name =
+                try!(slice(strtab, name_offset, name_len, "LLVM bitcode"));
+            if !name.is_empty() {
+                symbols.push(name.to_vec());
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// An operand in an abbreviation defined by a bitcode `DEFINE_ABBREV`
+/// record.
+#[derive(Clone, Copy)]
+enum AbbrevOp {
+    Literal(u64),
+    Fixed(u32),
+    Vbr(u32),
+    Array,
+    Char6,
+    Blob,
+}
+
+/// Reads the fields of an LLVM bitstream, which are packed into 32-bit
+/// little-endian words starting from the least significant bit.
+struct BitReader<'a> {
+    data: &'a [u8],
+    // The position of the next field, in bits.
+    position: u64,
+}
+
+impl<'a> BitReader<'a> {
+    fn is_at_end(&self) -> bool { self.position >= 8 * self.data.len() as u64 }
+
+    fn bits_left(&self) -> u64 {
+        (8 * self.data.len() as u64).saturating_sub(self.position)
+    }
+
+    fn end_of_data() -> Error {
+        malformed("LLVM bitcode", "unexpected end of data")
+    }
+
+    /// Reads a fixed-width field of at most 64 bits.
+    fn read(&mut self, width: u32) -> Result<u64> {
+        if width > 64 {
+            return Err(malformed("LLVM bitcode", "invalid field width"));
+        }
+        if self.position + width as u64 > 8 * self.data.len() as u64 {
+            return Err(BitReader::end_of_data());
+        }
+        let mut value = 0;
+        for bit in 0..width as u64 {
+            let position = self.position + bit;
+            let byte = self.data[(position / 8) as usize];
+            value |= (((byte >> (position % 8)) & 1) as u64) << bit;
+        }
+        self.position += width as u64;
+        Ok(value)
+    }
+
+    /// Reads a variable-width field, made of chunks of the given width whose
+    /// high bits say whether another chunk follows.
+    fn read_vbr(&mut self, width: u32) -> Result<u64> {
+        if width == 0 {
+            return Ok(0);
+        } else if !(2..=32).contains(&width) {
+            return Err(malformed("LLVM bitcode", "invalid field width"));
+        }
+        let high_bit = 1 << (width - 1);
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = try!(self.read(width));
+            if shift >= 64 {
+                return Err(malformed("LLVM bitcode", "invalid VBR field"));
+            }
+            value |= (chunk & (high_bit - 1)) << shift;
+            if chunk & high_bit == 0 {
+                return Ok(value);
+            }
+            shift += width - 1;
+        }
+    }
+
+    fn align_to_word(&mut self) {
+        self.position += (32 - self.position % 32) % 32;
+    }
+
+    /// Reads the given number of bytes, starting at a byte boundary.
+    fn read_bytes(&mut self, len: u64) -> Result<&'a [u8]> {
+        let start = self.position / 8;
+        let bytes = try!(slice(self.data, start, len, "LLVM bitcode"));
+        self.position += 8 * len;
+        Ok(bytes)
+    }
+
+    /// Reads the rest of an `ENTER_SUBBLOCK` record (whose abbreviation ID
+    /// has already been read), returning the new block's ID, its
+    /// abbreviation width, and the position of its end.
+    fn enter_block(&mut self) -> Result<(u64, u32, u64)> {
+        let block_id = try!(self.read_vbr(8));
+        let abbrev_width = try!(self.read_vbr(4));
+        if !(2..=32).contains(&abbrev_width) {
+            return Err(malformed("LLVM bitcode", "invalid field width"));
+        }
+        self.align_to_word();
+        let num_words = try!(self.read(32));
+        let end = self.position + 32 * num_words;
+        if end > 8 * self.data.len() as u64 {
+            return Err(BitReader::end_of_data());
+        }
+        Ok((block_id, abbrev_width as u32, end))
+    }
+
+    /// Reads the records of the block that has just been entered, until its
+    /// first blob record, and returns that blob (or None if the block ends
+    /// without one).  Nested blocks are skipped.
+    fn find_blob(&mut self, abbrev_width: u32) -> Result<Option<&'a [u8]>> {
+        let mut abbrevs = Vec::<Vec<AbbrevOp>>::new();
+        loop {
+            match try!(self.read(abbrev_width)) {
+                BITCODE_END_BLOCK => return Ok(None),
+                BITCODE_ENTER_SUBBLOCK => {
+                    let (_, _, end) = try!(self.enter_block());
+                    self.position = end;
+                }
+                BITCODE_DEFINE_ABBREV => {
+                    abbrevs.push(try!(self.read_abbrev_definition()));
+                }
+                BITCODE_UNABBREV_RECORD => {
+                    try!(self.read_vbr(6));
+                    let num_ops = try!(self.read_vbr(6));
+                    for _ in 0..num_ops {
+                        try!(self.read_vbr(6));
+                    }
+                }
+                abbrev_id => {
+                    let index = (abbrev_id - 4) as usize;
+                    let ops = try!(abbrevs.get(index).ok_or_else(|| {
+                        malformed("LLVM bitcode", "invalid abbreviation ID")
+                    }));
+                    let blob = try!(self.read_abbreviated_record(ops));
+                    if blob.is_some() {
+                        return Ok(blob);
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_abbrev_definition(&mut self) -> Result<Vec<AbbrevOp>> {
+        let num_ops = try!(self.read_vbr(5));
+        let mut ops = Vec::new();
+        for _ in 0..num_ops {
+            let is_literal = try!(self.read(1)) == 1;
+            let op = if is_literal {
+                AbbrevOp::Literal(try!(self.read_vbr(8)))
+            } else {
+                match try!(self.read(3)) {
+                    1 => AbbrevOp::Fixed(try!(self.read_vbr(5)) as u32),
+                    2 => AbbrevOp::Vbr(try!(self.read_vbr(5)) as u32),
+                    3 => AbbrevOp::Array,
+                    4 => AbbrevOp::Char6,
+                    5 => AbbrevOp::Blob,
+                    _ => {
+                        let problem = "invalid abbreviation encoding";
+                        return Err(malformed("LLVM bitcode", problem));
+                    }
+                }
+            };
+            ops.push(op);
+        }
+        Ok(ops)
+    }
+
+    fn read_scalar(&mut self, op: AbbrevOp) -> Result<u64> {
+        match op {
+            AbbrevOp::Literal(value) => Ok(value),
+            AbbrevOp::Fixed(width) => self.read(width),
+            AbbrevOp::Vbr(width) => self.read_vbr(width),
+            AbbrevOp::Char6 => self.read(6),
+            AbbrevOp::Array | AbbrevOp::Blob => {
+                let problem = "invalid array element encoding";
+                Err(malformed("LLVM bitcode", problem))
+            }
+        }
+    }
+
+    /// Reads a record using the given abbreviation, returning its blob
+    /// operand if it is a blob record.
+    fn read_abbreviated_record(&mut self, ops: &[AbbrevOp])
+                               -> Result<Option<&'a [u8]>> {
+        let mut code = None;
+        let mut blob = None;
+        let mut index = 0;
+        while index < ops.len() {
+            match ops[index] {
+                AbbrevOp::Array => {
+                    // The array's element encoding is given by the next
+                    // (and last) operand.
+                    let element = match ops.get(index + 1) {
+                        Some(&element) => element,
+                        None => {
+                            let problem = "array without element encoding";
+                            return Err(malformed("LLVM bitcode", problem));
+                        }
+                    };
+                    // Every element must take up some bits, so that a
+                    // hostile length can't make us loop without reading.
+                    let element_bits = match element {
+                        AbbrevOp::Fixed(width) |
+                        AbbrevOp::Vbr(width) if width > 0 => width as u64,
+                        AbbrevOp::Char6 => 6,
+                        _ => {
+                            let problem = "invalid array element encoding";
+                            return Err(malformed("LLVM bitcode", problem));
+                        }
+                    };
+                    let len = try!(self.read_vbr(6));
+                    if len > self.bits_left() / element_bits {
+                        return Err(BitReader::end_of_data());
+                    }
+                    for _ in 0..len {
+                        let value = try!(self.read_scalar(element));
+                        code = code.or(Some(value));
+                    }
+                    index += 1;
+                }
+                AbbrevOp::Blob => {
+                    let len = try!(self.read_vbr(6));
+                    self.align_to_word();
+                    blob = Some(try!(self.read_bytes(len)));
+                    self.align_to_word();
+                }
+                op => {
+                    let value = try!(self.read_scalar(op));
+                    code = code.or(Some(value));
+                }
+            }
+            index += 1;
+        }
+        if code == Some(BITCODE_BLOB_RECORD) {
+            Ok(blob)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::defined_symbols;
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+    fn put<E: ByteOrder>(data: &mut Vec<u8>, offset: usize, value: u64,
+                         len: usize) {
+        if data.len() < offset + len {
+            data.resize(offset + len, 0);
+        }
+        E::write_uint(&mut data[offset..offset + len], value, len);
+    }
+
+    // Builds an ELF file with a null section, a symbol table, and a string
+    // table.  Each symbol is given as (name, info, section index).
+    fn elf<E: ByteOrder>(is_64_bit: bool, big_endian: bool,
+                         symbols: &[(&str, u8, u16)])
+                         -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; if is_64_bit { 24 } else { 16 }];
+        for &(name, info, shndx) in symbols.iter() {
+            let name_offset = strtab.len() as u64;
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+            let start = symtab.len();
+            if is_64_bit {
+                put::<E>(&mut symtab, start, name_offset, 4);
+                put::<E>(&mut symtab, start + 4, info as u64, 1);
+                put::<E>(&mut symtab, start + 6, shndx as u64, 2);
+                symtab.resize(start + 24, 0);
+            } else {
+                put::<E>(&mut symtab, start, name_offset, 4);
+                put::<E>(&mut symtab, start + 4, 0, 8);
+                put::<E>(&mut symtab, start + 12, info as u64, 1);
+                put::<E>(&mut symtab, start + 14, shndx as u64, 2);
+            }
+        }
+        let header_len = if is_64_bit { 0x40 } else { 0x34 };
+        let section_len = if is_64_bit { 0x40 } else { 0x28 };
+        let symtab_offset = header_len;
+        let strtab_offset = symtab_offset + symtab.len();
+        let sections_offset = strtab_offset + strtab.len();
+        let mut data = vec![0x7f, b'E', b'L', b'F'];
+        data.push(if is_64_bit { 2 } else { 1 });
+        data.push(if big_endian { 2 } else { 1 });
+        data.resize(header_len, 0);
+        data.extend_from_slice(&symtab);
+        data.extend_from_slice(&strtab);
+        let sections = [
+            (0, 0, 0, 0),
+            (2, symtab_offset, symtab.len(), 2),
+            (3, strtab_offset, strtab.len(), 0),
+        ];
+        for (index, &(kind, offset, size, link)) in
+            sections.iter().enumerate()
+        {
+            let start = sections_offset + index * section_len;
+            put::<E>(&mut data, start + section_len - 1, 0, 1);
+            put::<E>(&mut data, start + 4, kind, 4);
+            if is_64_bit {
+                put::<E>(&mut data, start + 0x18, offset as u64, 8);
+                put::<E>(&mut data, start + 0x20, size as u64, 8);
+                put::<E>(&mut data, start + 0x28, link, 4);
+            } else {
+                put::<E>(&mut data, start + 0x10, offset as u64, 4);
+                put::<E>(&mut data, start + 0x14, size as u64, 4);
+                put::<E>(&mut data, start + 0x18, link, 4);
+            }
+        }
+        if is_64_bit {
+            put::<E>(&mut data, 0x28, sections_offset as u64, 8);
+            put::<E>(&mut data, 0x3a, section_len as u64, 2);
+            put::<E>(&mut data, 0x3c, 3, 2);
+        } else {
+            put::<E>(&mut data, 0x20, sections_offset as u64, 4);
+            put::<E>(&mut data, 0x2e, section_len as u64, 2);
+            put::<E>(&mut data, 0x30, 3, 2);
+        }
+        data
+    }
+
+    const ELF_SYMBOLS: &[(&str, u8, u16)] = &[
+        ("local", 0x02, 1),
+        ("global_func", 0x12, 1),
+        ("undefined", 0x10, 0),
+        ("weak_object", 0x21, 2),
+        ("common", 0x11, 0xfff2),
+        ("unique", 0xa1, 3),
+    ];
+
+    fn symbol_names(data: &[u8]) -> Vec<String> {
+        defined_symbols(data)
+            .unwrap()
+            .into_iter()
+            .map(|name| String::from_utf8(name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn elf_symbols() {
+        let expected = vec!["global_func", "weak_object", "common", "unique"];
+        let data = elf::<LittleEndian>(true, false, ELF_SYMBOLS);
+        assert_eq!(symbol_names(&data), expected);
+        let data = elf::<BigEndian>(true, true, ELF_SYMBOLS);
+        assert_eq!(symbol_names(&data), expected);
+        let data = elf::<LittleEndian>(false, false, ELF_SYMBOLS);
+        assert_eq!(symbol_names(&data), expected);
+        let data = elf::<BigEndian>(false, true, ELF_SYMBOLS);
+        assert_eq!(symbol_names(&data), expected);
+    }
+
+    #[test]
+    fn malformed_elf() {
+        let mut data = elf::<LittleEndian>(true, false, ELF_SYMBOLS);
+        let len = data.len();
+        data.truncate(len - 1);
+        let error = defined_symbols(&data).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "Malformed ELF object file (unexpected end of data)");
+        let data = b"\x7fELF\x03\x01".to_vec();
+        let error = defined_symbols(&data).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "Malformed ELF object file (invalid class or data \
+                    encoding)");
+    }
+
+    #[test]
+    fn macho_symbols() {
+        // (name, n_type): N_SECT|N_EXT, N_UNDF|N_EXT, N_SECT (local),
+        // N_ABS|N_EXT, N_STAB, and N_UNDF|N_EXT with a value (common).
+        let symbols: &[(&str, u8, u64)] = &[
+            ("_main", 0x0f, 0x10),
+            ("_printf", 0x01, 0),
+            ("_local", 0x0e, 0x20),
+            ("_absolute", 0x03, 0x1234),
+            ("_debug", 0x24, 0),
+            ("_common", 0x01, 8),
+        ];
+        let mut strtab = vec![0u8];
+        let mut symtab = Vec::new();
+        for &(name, kind, value) in symbols.iter() {
+            let start = symtab.len();
+            put::<LittleEndian>(&mut symtab, start, strtab.len() as u64, 4);
+            put::<LittleEndian>(&mut symtab, start + 4, kind as u64, 1);
+            put::<LittleEndian>(&mut symtab, start + 5, 1, 1);
+            put::<LittleEndian>(&mut symtab, start + 8, value, 8);
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+        let mut data = Vec::new();
+        put::<LittleEndian>(&mut data, 0, 0xfeedfacf, 4);
+        put::<LittleEndian>(&mut data, 16, 2, 4); // ncmds
+        // An unrelated load command (LC_UUID), followed by LC_SYMTAB.
+        put::<LittleEndian>(&mut data, 32, 0x1b, 4);
+        put::<LittleEndian>(&mut data, 36, 24, 4);
+        put::<LittleEndian>(&mut data, 56, 2, 4);
+        put::<LittleEndian>(&mut data, 60, 24, 4);
+        put::<LittleEndian>(&mut data, 64, 80, 4);
+        put::<LittleEndian>(&mut data, 68, symbols.len() as u64, 4);
+        let stroff = 80 + symtab.len() as u64;
+        put::<LittleEndian>(&mut data, 72, stroff, 4);
+        put::<LittleEndian>(&mut data, 76, strtab.len() as u64, 4);
+        data.extend_from_slice(&symtab);
+        data.extend_from_slice(&strtab);
+        assert_eq!(symbol_names(&data), vec!["_main", "_absolute"]);
+    }
+
+    #[test]
+    fn coff_symbols() {
+        let mut data = Vec::new();
+        put::<LittleEndian>(&mut data, 0, 0x8664, 2);
+        put::<LittleEndian>(&mut data, 8, 20, 4); // PointerToSymbolTable
+        put::<LittleEndian>(&mut data, 12, 6, 4); // NumberOfSymbols
+        data.resize(20, 0);
+        let symbol = |data: &mut Vec<u8>, name: &[u8], value: u64,
+                      section: u64, class: u64, num_aux: u64| {
+            let start = data.len();
+            data.extend_from_slice(name);
+            put::<LittleEndian>(data, start + 8, value, 4);
+            put::<LittleEndian>(data, start + 12, section, 2);
+            put::<LittleEndian>(data, start + 16, class, 1);
+            put::<LittleEndian>(data, start + 17, num_aux, 1);
+        };
+        // A section symbol with one auxiliary record, a defined external
+        // symbol with a long name, an undefined one, a common one, and a
+        // static one.
+        symbol(&mut data, b".text\0\0\0", 0, 1, 3, 1);
+        symbol(&mut data, b"tooshort", 0, 0, 0, 0);
+        symbol(&mut data, b"\0\0\0\0\x04\0\0\0", 0, 1, 2, 0);
+        symbol(&mut data, b"puts\0\0\0\0", 0, 0, 2, 0);
+        symbol(&mut data, b"counter\0", 4, 0, 2, 0);
+        symbol(&mut data, b"helper\0\0", 0, 1, 3, 0);
+        let strings = b"a_long_function_name\0";
+        put::<LittleEndian>(&mut data, 128, 4 + strings.len() as u64, 4);
+        data.extend_from_slice(strings);
+        assert_eq!(symbol_names(&data),
+                   vec!["a_long_function_name", "counter"]);
+    }
+
+    #[test]
+    fn import_object_symbols() {
+        let mut data = b"\0\0\xff\xff\0\0\x64\x86\0\0\0\0".to_vec();
+        let strings = b"GetTickCount\0KERNEL32.dll\0";
+        put::<LittleEndian>(&mut data, 12, strings.len() as u64, 4);
+        put::<LittleEndian>(&mut data, 18, 0x4, 2); // Code, by name
+        data.extend_from_slice(strings);
+        assert_eq!(symbol_names(&data),
+                   vec!["__imp_GetTickCount", "GetTickCount"]);
+        put::<LittleEndian>(&mut data, 18, 0x5, 2); // Data, by name
+        assert_eq!(symbol_names(&data), vec!["__imp_GetTickCount"]);
+    }
+
+    // Writes the fields of an LLVM bitstream.
+    struct BitWriter {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u64, width: usize) {
+            for bit in 0..width {
+                if self.position % 8 == 0 {
+                    self.data.push(0);
+                }
+                let byte = ((value >> bit) & 1) as u8;
+                self.data[self.position / 8] |= byte << (self.position % 8);
+                self.position += 1;
+            }
+        }
+
+        fn write_vbr(&mut self, mut value: u64, width: usize) {
+            let high_bit = 1 << (width - 1);
+            while value >= high_bit {
+                self.write((value & (high_bit - 1)) | high_bit, width);
+                value >>= width - 1;
+            }
+            self.write(value, width);
+        }
+
+        fn align(&mut self) {
+            while self.position % 32 != 0 {
+                self.write(0, 1);
+            }
+        }
+
+        // Starts a block, returning the position of its length field.
+        fn enter_block(&mut self, outer_width: usize, block_id: u64,
+                       width: usize)
+                       -> usize {
+            self.write(1, outer_width);
+            self.write_vbr(block_id, 8);
+            self.write_vbr(width as u64, 4);
+            self.align();
+            self.write(0, 32);
+            self.position - 32
+        }
+
+        fn end_block(&mut self, width: usize, length_position: usize) {
+            self.write(0, width);
+            self.align();
+            let num_words = (self.position - length_position) / 32 - 1;
+            let start = length_position / 8;
+            LittleEndian::write_u32(&mut self.data[start..], num_words as u32);
+        }
+
+        // Writes a block containing a single blob record.
+        fn blob_block(&mut self, block_id: u64, blob: &[u8]) {
+            let length_position = self.enter_block(2, block_id, 3);
+            // DEFINE_ABBREV: a literal record code of 1, then a blob.
+            self.write(2, 3);
+            self.write_vbr(2, 5);
+            self.write(1, 1);
+            self.write_vbr(1, 8);
+            self.write(0, 1);
+            self.write(5, 3);
+            self.write(4, 3);
+            self.write_vbr(blob.len() as u64, 6);
+            self.align();
+            for &byte in blob.iter() {
+                self.write(byte as u64, 8);
+            }
+            self.align();
+            self.end_block(3, length_position);
+        }
+    }
+
+    // Builds a bitcode file with a module block, and (optionally) a symbol
+    // table listing the given symbols.  Each symbol is given as (name, flags).
+    fn bitcode(symbols: Option<&[(&str, u32)]>) -> Vec<u8> {
+        let mut writer = BitWriter {
+            data: Vec::new(),
+            position: 0,
+        };
+        for &byte in b"BC\xc0\xde".iter() {
+            writer.write(byte as u64, 8);
+        }
+        // A module block with an unabbreviated record and a nested block.
+        let module = writer.enter_block(2, 8, 3);
+        writer.write(3, 3);
+        writer.write_vbr(1, 6);
+        writer.write_vbr(2, 6);
+        writer.write_vbr(100, 6);
+        writer.write_vbr(2000, 6);
+        let nested = writer.enter_block(3, 17, 4);
+        writer.end_block(4, nested);
+        writer.end_block(3, module);
+        if let Some(symbols) = symbols {
+            let mut symtab = vec![0u8; 40];
+            put::<LittleEndian>(&mut symtab, 28, 40, 4);
+            put::<LittleEndian>(&mut symtab, 32, symbols.len() as u64, 4);
+            let mut strtab = Vec::new();
+            for &(name, flags) in symbols.iter() {
+                let start = symtab.len();
+                let name_offset = strtab.len() as u64;
+                put::<LittleEndian>(&mut symtab, start, name_offset, 4);
+                let name_len = name.len() as u64;
+                put::<LittleEndian>(&mut symtab, start + 4, name_len, 4);
+                put::<LittleEndian>(&mut symtab, start + 20, flags as u64, 4);
+                strtab.extend_from_slice(name.as_bytes());
+            }
+            writer.blob_block(25, &symtab);
+            writer.blob_block(23, &strtab);
+        }
+        writer.data
+    }
+
+    const BITCODE_SYMBOLS: &[(&str, u32)] = &[
+        ("global_fn", 0x400),
+        ("ext", 0x408),
+        ("static_fn", 0x000),
+        ("weak_var", 0x420),
+        ("llvm.global_ctors", 0xc00),
+    ];
+
+    #[test]
+    fn bitcode_symbols() {
+        let data = bitcode(Some(BITCODE_SYMBOLS));
+        assert_eq!(symbol_names(&data), vec!["global_fn", "weak_var"]);
+        // The same bitcode, inside a wrapper header and followed by padding.
+        let mut wrapped = Vec::new();
+        put::<LittleEndian>(&mut wrapped, 0, 0x0b17c0de, 4);
+        put::<LittleEndian>(&mut wrapped, 8, 20, 4);
+        put::<LittleEndian>(&mut wrapped, 12, data.len() as u64, 4);
+        put::<LittleEndian>(&mut wrapped, 16, 7, 4);
+        wrapped.extend_from_slice(&data);
+        wrapped.extend_from_slice(&[0; 12]);
+        assert_eq!(symbol_names(&wrapped), vec!["global_fn", "weak_var"]);
+        // Bitcode without a symbol table defines no symbols.
+        let data = bitcode(None);
+        assert!(defined_symbols(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_bitcode() {
+        let mut data = bitcode(Some(BITCODE_SYMBOLS));
+        let len = data.len();
+        data.truncate(len - 8);
+        let error = defined_symbols(&data).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "Malformed LLVM bitcode object file (unexpected end of \
+                    data)");
+    }
+
+    // Builds a bitcode file with a symbol table block containing a record
+    // abbreviated as an array of the given element encoding (written as its
+    // 3-bit encoding and 5-bit width), claiming the given number of elements.
+    fn bitcode_with_array(element: u64, width: u64, len: u64) -> Vec<u8> {
+        let mut writer = BitWriter {
+            data: Vec::new(),
+            position: 0,
+        };
+        for &byte in b"BC\xc0\xde".iter() {
+            writer.write(byte as u64, 8);
+        }
+        let length_position = writer.enter_block(2, 25, 3);
+        // DEFINE_ABBREV: an array, whose element encoding follows.
+        writer.write(2, 3);
+        writer.write_vbr(2, 5);
+        writer.write(0, 1);
+        writer.write(3, 3);
+        if element == 0 {
+            writer.write(1, 1);
+            writer.write_vbr(width, 8);
+        } else {
+            writer.write(0, 1);
+            writer.write(element, 3);
+            if element == 1 || element == 2 {
+                writer.write_vbr(width, 5);
+            }
+        }
+        writer.write(4, 3);
+        writer.write_vbr(len, 6);
+        for _ in 0..100 {
+            writer.write(0, 32);
+        }
+        writer.end_block(3, length_position);
+        writer.data
+    }
+
+    #[test]
+    fn hostile_bitcode_array() {
+        // Elements that take up no bits at all are rejected, rather than
+        // read (for free) as many times as the array length says.
+        let data = bitcode_with_array(0, 1, u64::MAX);
+        let error = defined_symbols(&data).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "Malformed LLVM bitcode object file (invalid array \
+                    element encoding)");
+        let data = bitcode_with_array(1, 0, u64::MAX);
+        assert!(defined_symbols(&data).is_err());
+        let data = bitcode_with_array(2, 0, u64::MAX);
+        assert!(defined_symbols(&data).is_err());
+        // So are arrays longer than the rest of the data could hold.
+        let data = bitcode_with_array(4, 0, u64::MAX);
+        let error = defined_symbols(&data).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "Malformed LLVM bitcode object file (unexpected end of \
+                    data)");
+        let data = bitcode_with_array(1, 8, 1 << 40);
+        assert!(defined_symbols(&data).is_err());
+        // An array that does fit is read as usual.
+        let data = bitcode_with_array(1, 8, 16);
+        assert!(defined_symbols(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unrecognized_data() {
+        assert!(defined_symbols(b"").unwrap().is_empty());
+        assert!(defined_symbols(b"hello, world\n").unwrap().is_empty());
+        assert!(defined_symbols(b"\0\0\xff\xff\x01\0").unwrap().is_empty());
+        // Data that merely starts with a COFF machine type:
+        assert!(defined_symbols(b"\x00\x02 is not COFF").unwrap().is_empty());
+        let mut data = Vec::new();
+        put::<LittleEndian>(&mut data, 0, 0x8664, 2);
+        put::<LittleEndian>(&mut data, 8, 0x1000, 4); // PointerToSymbolTable
+        put::<LittleEndian>(&mut data, 12, 1, 4); // NumberOfSymbols
+        data.resize(20, 0);
+        assert!(defined_symbols(&data).unwrap().is_empty());
+        put::<LittleEndian>(&mut data, 8, 0, 4);
+        put::<LittleEndian>(&mut data, 16, 0xe0, 2); // SizeOfOptionalHeader
+        assert!(defined_symbols(&data).unwrap().is_empty());
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,300 @@
+extern crate ar;
+
+mod common;
+
+use ar::Archive;
+use common::{TestDir, import_object};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use std::str;
+use std::time::{Duration, SystemTime};
+
+// ========================================================================= //
+
+// Helpers for running `ar` in a test's scratch directory.
+trait ArDir {
+    // Runs `ar` in this directory, and checks its exit code and output.
+    fn ar(&self, args: &[&str], code: i32, stdout: &str, stderr: &str);
+
+    // Lists the members of the given archive with `ar t`.
+    fn members(&self, archive: &str) -> Vec<String>;
+}
+
+impl ArDir for TestDir {
+    fn ar(&self, args: &[&str], code: i32, stdout: &str, stderr: &str) {
+        run_ar(self.path(), args, code, stdout, stderr);
+    }
+
+    fn members(&self, archive: &str) -> Vec<String> {
+        let output = Command::new(env!("CARGO_BIN_EXE_ar"))
+            .args(["t", archive])
+            .current_dir(self.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        str::from_utf8(&output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+fn run_ar(dir: &Path, args: &[&str], code: i32, stdout: &str, stderr: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ar"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert_eq!(str::from_utf8(&output.stdout).unwrap(), stdout, "{:?}", args);
+    assert_eq!(str::from_utf8(&output.stderr).unwrap(), stderr, "{:?}", args);
+    assert_eq!(output.status.code(), Some(code), "{:?}", args);
+}
+
+// Creates `lib.a` with the members `foo.txt`, `bar.txt`, and `baz.txt`.
+fn make_archive(name: &str) -> TestDir {
+    let dir = TestDir::new(name);
+    dir.write("foo.txt", "foo\n");
+    dir.write("bar.txt", "barbar\n");
+    dir.write("baz.txt", "baz\n");
+    dir.ar(&["rc", "lib.a", "foo.txt", "bar.txt", "baz.txt"], 0, "", "");
+    dir
+}
+
+// ========================================================================= //
+
+#[test]
+fn quick_append_and_list() {
+    let dir = TestDir::new("quick-append");
+    dir.write("foo.txt", "foo\n");
+    dir.write("bar.txt", "barbar\n");
+    dir.ar(&["qv", "lib.a", "foo.txt"],
+           0,
+           "a - foo.txt\n",
+           "ar: creating lib.a\n");
+    dir.ar(&["qv", "lib.a", "bar.txt", "foo.txt"],
+           0,
+           "a - bar.txt\na - foo.txt\n",
+           "");
+    dir.ar(&["t", "lib.a"], 0, "foo.txt\nbar.txt\nfoo.txt\n", "");
+    dir.ar(&["tv", "lib.a", "bar.txt"],
+           0,
+           "rw-r--r-- 0/0      7 Jan  1 00:00 1970 bar.txt\n",
+           "");
+}
+
+#[test]
+fn replace_members() {
+    let dir = make_archive("replace");
+    dir.write("foo.txt", "new foo\n");
+    dir.write("quux.txt", "quux\n");
+    dir.ar(&["rv", "lib.a", "foo.txt", "quux.txt"],
+           0,
+           "r - foo.txt\na - quux.txt\n",
+           "");
+    dir.ar(&["p", "lib.a", "foo.txt"], 0, "new foo\n", "");
+    assert_eq!(dir.members("lib.a"),
+               vec!["foo.txt", "bar.txt", "baz.txt", "quux.txt"]);
+}
+
+#[test]
+fn replace_members_at_position() {
+    let dir = make_archive("replace-position");
+    dir.write("quux.txt", "quux\n");
+    dir.write("zap.txt", "zap\n");
+    dir.ar(&["rb", "bar.txt", "lib.a", "quux.txt"], 0, "", "");
+    assert_eq!(dir.members("lib.a"),
+               vec!["foo.txt", "quux.txt", "bar.txt", "baz.txt"]);
+    dir.ar(&["rav", "foo.txt", "lib.a", "zap.txt"],
+           0,
+           "a - zap.txt\n",
+           "");
+    assert_eq!(dir.members("lib.a"),
+               vec!["foo.txt", "zap.txt", "quux.txt", "bar.txt", "baz.txt"]);
+}
+
+#[test]
+fn replace_only_newer_members() {
+    let dir = TestDir::new("replace-newer");
+    dir.write("foo.txt", "foo\n");
+    dir.ar(&["rcU", "lib.a", "foo.txt"], 0, "", "");
+    dir.write("foo.txt", "new foo\n");
+    let file = File::options()
+        .write(true)
+        .open(dir.join("foo.txt"))
+        .unwrap();
+    let past = SystemTime::now() - Duration::from_secs(3600);
+    file.set_modified(past).unwrap();
+    drop(file);
+    // The archived copy is newer than the file, so it's kept.
+    dir.ar(&["rUuv", "lib.a", "foo.txt"], 0, "", "");
+    dir.ar(&["p", "lib.a"], 0, "foo\n", "");
+    // Without `U', members are added with a zero timestamp, so `u' replaces
+    // them anyway (after a warning).
+    dir.ar(&["rc", "zero.a", "foo.txt"], 0, "", "");
+    dir.ar(&["ruv", "zero.a", "foo.txt"],
+           0,
+           "r - foo.txt\n",
+           "ar: `u' modifier ignored since `D' is the default (see `U')\n");
+    dir.ar(&["p", "zero.a"], 0, "new foo\n", "");
+}
+
+#[test]
+fn replace_missing_file() {
+    let dir = make_archive("replace-missing");
+    dir.ar(&["r", "lib.a", "missing.txt"],
+           1,
+           "",
+           "ar: missing.txt: No such file or directory\n");
+    assert_eq!(dir.members("lib.a"), vec!["foo.txt", "bar.txt", "baz.txt"]);
+}
+
+#[test]
+fn move_members() {
+    let dir = make_archive("move");
+    dir.ar(&["mv", "lib.a", "foo.txt"], 0, "m - foo.txt\n", "");
+    assert_eq!(dir.members("lib.a"), vec!["bar.txt", "baz.txt", "foo.txt"]);
+    dir.ar(&["mb", "bar.txt", "lib.a", "baz.txt"], 0, "", "");
+    assert_eq!(dir.members("lib.a"), vec!["baz.txt", "bar.txt", "foo.txt"]);
+    dir.ar(&["mav", "foo.txt", "lib.a", "baz.txt"],
+           0,
+           "m - baz.txt\n",
+           "");
+    assert_eq!(dir.members("lib.a"), vec!["bar.txt", "foo.txt", "baz.txt"]);
+}
+
+#[test]
+fn print_members() {
+    let dir = make_archive("print");
+    dir.ar(&["p", "lib.a"], 0, "foo\nbarbar\nbaz\n", "");
+    dir.ar(&["pv", "lib.a", "bar.txt"], 0, "\n<bar.txt>\n\nbarbar\n", "");
+    dir.ar(&["p", "lib.a", "missing.txt", "baz.txt"],
+           0,
+           "baz\n",
+           "no entry missing.txt in archive\n");
+}
+
+#[test]
+fn extract_members() {
+    let dir = make_archive("extract");
+    fs::create_dir(dir.join("out")).unwrap();
+    let out = dir.join("out");
+    run_ar(&out, &["xv", "../lib.a", "bar.txt"], 0, "x - bar.txt\n", "");
+    assert_eq!(dir.read("out/bar.txt"), "barbar\n");
+    assert!(!dir.exists("out/foo.txt"));
+    run_ar(&out, &["x", "../lib.a"], 0, "", "");
+    assert_eq!(dir.read("out/foo.txt"), "foo\n");
+    assert_eq!(dir.read("out/baz.txt"), "baz\n");
+    run_ar(&out,
+           &["x", "../lib.a", "missing.txt"],
+           0,
+           "",
+           "no entry missing.txt in archive\n");
+}
+
+#[test]
+fn delete_members() {
+    let dir = make_archive("delete");
+    dir.ar(&["dv", "lib.a", "bar.txt"], 0, "d - bar.txt\n", "");
+    assert_eq!(dir.members("lib.a"), vec!["foo.txt", "baz.txt"]);
+    dir.ar(&["d", "lib.a", "missing.txt"], 0, "", "");
+    assert_eq!(dir.members("lib.a"), vec!["foo.txt", "baz.txt"]);
+    dir.ar(&["d", "lib.a", "foo.txt", "baz.txt"], 0, "", "");
+    assert!(dir.members("lib.a").is_empty());
+    assert_eq!(dir.read("lib.a"), "!<arch>\n");
+}
+
+#[test]
+fn count_duplicate_members() {
+    let dir = make_archive("count");
+    dir.write("foo.txt", "second foo\n");
+    dir.ar(&["q", "lib.a", "foo.txt"], 0, "", "");
+    fs::create_dir(dir.join("out")).unwrap();
+    let out = dir.join("out");
+    run_ar(&out, &["xN", "2", "../lib.a", "foo.txt"], 0, "", "");
+    assert_eq!(dir.read("out/foo.txt"), "second foo\n");
+    dir.ar(&["dN", "1", "lib.a", "foo.txt"], 0, "", "");
+    assert_eq!(dir.members("lib.a"), vec!["bar.txt", "baz.txt", "foo.txt"]);
+    dir.ar(&["p", "lib.a", "foo.txt"], 0, "second foo\n", "");
+    dir.ar(&["pN", "1", "lib.a", "foo.txt"],
+           1,
+           "",
+           "ar: `N' is only meaningful with the `x' and `d' options.\n");
+}
+
+#[test]
+fn symbols_of_members_with_same_name() {
+    let dir = TestDir::new("same-name-symbols");
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("b")).unwrap();
+    fs::write(dir.join("a/foo.o"), import_object(b"alpha", false)).unwrap();
+    fs::write(dir.join("b/foo.o"), import_object(b"beta", false)).unwrap();
+    dir.ar(&["q", "lib.a", "a/foo.o", "b/foo.o"],
+           0,
+           "",
+           "ar: creating lib.a\n");
+    assert_eq!(dir.members("lib.a"), vec!["foo.o", "foo.o"]);
+    // Each symbol must point at the member that defines it, not just at the
+    // first member with that name.
+    let mut archive = Archive::open(dir.join("lib.a")).unwrap();
+    let symbols: Vec<(String, usize)> = archive
+        .symbol_entries()
+        .unwrap()
+        .map(|(symbol, index)| (str::from_utf8(symbol).unwrap().into(), index))
+        .collect();
+    let expected = vec![
+        ("__imp_alpha".to_string(), 0),
+        ("alpha".to_string(), 0),
+        ("__imp_beta".to_string(), 1),
+        ("beta".to_string(), 1),
+    ];
+    assert_eq!(symbols, expected);
+}
+
+#[test]
+fn full_path_member_names() {
+    let dir = TestDir::new("full-paths");
+    fs::create_dir(dir.join("sub")).unwrap();
+    dir.write("sub/foo.o", "foo\n");
+    dir.ar(&["rcP", "lib.a", "sub/foo.o"], 0, "", "");
+    // GNU readers take a slash in an entry header as the end of the name, so
+    // the name has to go in the name table.
+    let expected = "\
+        !<arch>\n\
+        //                                              12        `\n\
+        sub/foo.o/\n\n\
+        /0              0           0     0     644     4         `\n\
+        foo\n";
+    assert_eq!(dir.read("lib.a"), expected);
+    assert_eq!(dir.members("lib.a"), vec!["sub/foo.o"]);
+    dir.ar(&["pP", "lib.a", "sub/foo.o"], 0, "foo\n", "");
+}
+
+#[test]
+fn missing_archive() {
+    let dir = TestDir::new("missing-archive");
+    for &key in ["t", "p", "x"].iter() {
+        dir.ar(&[key, "lib.a"],
+               9,
+               "",
+               "ar: lib.a: No such file or directory\n");
+    }
+    assert!(!dir.exists("lib.a"));
+    dir.ar(&["d", "lib.a", "foo.txt"], 0, "", "ar: creating lib.a\n");
+    assert_eq!(dir.read("lib.a"), "!<arch>\n");
+    dir.ar(&["t", "lib.a"], 0, "", "");
+}
+
+#[test]
+fn not_an_archive() {
+    let dir = TestDir::new("not-an-archive");
+    dir.write("foo.c", "int foo;\n");
+    dir.write("bar.o", "bar\n");
+    for args in [&["t", "foo.c"][..], &["r", "foo.c", "bar.o"][..]].iter() {
+        dir.ar(args, 1, "", "ar: foo.c: file format not recognized\n");
+    }
+    assert_eq!(dir.read("foo.c"), "int foo;\n");
+}
+
+// ========================================================================= //
@@ -0,0 +1,70 @@
+//! Fixtures shared by the library's unit tests and the command-line tests.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+// ========================================================================= //
+
+// A scratch directory for a single test, removed when the test finishes
+// (whether or not it passes).  Any directory left behind by an earlier run
+// with the same process ID is removed first.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(name: &str) -> TestDir {
+        let path = env::temp_dir()
+            .join(format!("ar-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TestDir { path: path }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        self.path.join(name)
+    }
+
+    pub fn write<C: AsRef<[u8]>>(&self, name: &str, contents: C) {
+        fs::write(self.path.join(name), contents).unwrap();
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path.join(name)).unwrap()
+    }
+
+    pub fn exists(&self, name: &str) -> bool { self.path.join(name).exists() }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path { &self.path }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.path); }
+}
+
+// ========================================================================= //
+
+// Returns a Windows short import object for the given symbol (a function,
+// unless `is_data` is true), which defines that symbol's `__imp_`
+// counterpart, and for a function the symbol itself.
+pub fn import_object(symbol: &[u8], is_data: bool) -> Vec<u8> {
+    let mut data = b"\0\0\xff\xff\0\0\x64\x86\0\0\0\0".to_vec();
+    let size = symbol.len() as u32 + 9;
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(b"\0\0");
+    data.push(if is_data { 5 } else { 4 });
+    data.push(0);
+    data.extend_from_slice(symbol);
+    data.extend_from_slice(b"\0foo.dll\0");
+    data
+}
+
+// ========================================================================= //
@@ -0,0 +1,79 @@
+extern crate ar;
+
+mod common;
+
+use ar::Archive;
+use common::{TestDir, import_object};
+use std::fs;
+use std::process::Command;
+use std::str;
+
+// Runs `ranlib` on the given archive in the given directory, and checks that
+// it succeeds without complaint.
+fn ranlib(dir: &TestDir, archive: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ranlib"))
+        .arg(archive)
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(str::from_utf8(&output.stderr).unwrap(), "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+// ========================================================================= //
+
+#[test]
+fn replace_stale_symbol_table() {
+    let dir = TestDir::new("ranlib-stale");
+    // The symbol table says that `foo` is defined by an entry at offset 999,
+    // but there's no entry there.
+    let input: &[u8] = b"\
+        !<arch>\n\
+        /               0           0     0     0       12        `\n\
+        \x00\x00\x00\x01\x00\x00\x03\xe7foo\x00\
+        foo.o/          0           0     0     0       4         `\n\
+        foo\n";
+    fs::write(dir.join("stale.a"), input).unwrap();
+    ranlib(&dir, "stale.a");
+    // The member isn't an object file, so the new symbol table is empty.
+    let expected = "\
+        !<arch>\n\
+        foo.o/          0           0     0     0       4         `\n\
+        foo\n";
+    let actual = fs::read(dir.join("stale.a")).unwrap();
+    assert_eq!(str::from_utf8(&actual).unwrap(), expected);
+}
+
+#[test]
+fn index_thin_archive_with_same_member_names() {
+    let dir = TestDir::new("ranlib-thin");
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("b")).unwrap();
+    fs::write(dir.join("a/foo.o"), import_object(b"alpha", false)).unwrap();
+    fs::write(dir.join("b/foo.o"), import_object(b"beta", false)).unwrap();
+    let input: &[u8] = b"\
+        !<thin>\n\
+        //                                              18        `\n\
+        a/foo.o/\n\
+        b/foo.o/\n\
+        /0              0           0     0     644     34        `\n\
+        /9              0           0     0     644     33        `\n";
+    fs::write(dir.join("thin.a"), input).unwrap();
+    ranlib(&dir, "thin.a");
+    let mut archive = Archive::open(dir.join("thin.a")).unwrap();
+    let symbols: Vec<(String, usize)> = archive
+        .symbol_entries()
+        .unwrap()
+        .map(|(symbol, index)| (str::from_utf8(symbol).unwrap().into(), index))
+        .collect();
+    let expected = vec![
+        ("__imp_alpha".to_string(), 0),
+        ("alpha".to_string(), 0),
+        ("__imp_beta".to_string(), 1),
+        ("beta".to_string(), 1),
+    ];
+    assert_eq!(symbols, expected);
+    assert!(archive.is_thin());
+}
+
+// ========================================================================= //